@@ -20,6 +20,11 @@ pub trait Configuration: Serialize + serde::de::DeserializeOwned {
             .ok()
     }
 
+    /// Returns `true` if the configuration file exists, even if it can not be loaded.
+    fn is_saved() -> bool {
+        get_config_file_path::<Self>().exists()
+    }
+
     fn delete() {
         let path = get_config_file_path::<Self>();
         fs::remove_file(path).ok();
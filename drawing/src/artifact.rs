@@ -0,0 +1,168 @@
+//! Artifacts tests can emit to the testrunner.
+//!
+//! Besides drawings, tests can emit labelled drawings, plain text blocks, and key / value tables.
+//! The testrunner presents the artifacts of a test in the order they were emitted, each below its
+//! own header.
+
+use crate::{capture_channel, Drawing};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::io::Write;
+
+/// The prefix of output lines that contain an unlabelled drawing.
+pub const DRAWING_PREFIX: &str = "> ";
+/// The prefix of output lines that contain an artifact.
+pub const ARTIFACT_PREFIX: &str = ">> ";
+
+/// A typed artifact emitted by a test.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum Artifact {
+    Drawing {
+        label: Option<String>,
+        drawing: Drawing,
+    },
+    Text {
+        label: Option<String>,
+        text: String,
+    },
+    Table {
+        label: Option<String>,
+        rows: Vec<(String, String)>,
+    },
+}
+
+impl Artifact {
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Artifact::Drawing { label, .. }
+            | Artifact::Text { label, .. }
+            | Artifact::Table { label, .. } => label.as_ref().map(|l| l.as_str()),
+        }
+    }
+
+    /// The name of the kind of the artifact.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Artifact::Drawing { .. } => "drawing",
+            Artifact::Text { .. } => "text",
+            Artifact::Table { .. } => "table",
+        }
+    }
+
+    /// Returns the label of the artifact, or, if it is not labelled, the name of its kind.
+    pub fn title(&self) -> &str {
+        self.label().unwrap_or_else(|| self.kind())
+    }
+
+    pub fn drawing(&self) -> Option<&Drawing> {
+        match self {
+            Artifact::Drawing { drawing, .. } => Some(drawing),
+            _ => None,
+        }
+    }
+
+    /// Emits the artifact to the testrunner.
+    ///
+    /// If the capture channel is active, the artifact is transferred through it, otherwise it is
+    /// written to stdout.
+    pub fn emit(&self) {
+        if let Some(directory) = capture_channel::active_directory() {
+            capture_channel::write(&directory, self).expect("failed to write artifact capture");
+            return;
+        }
+
+        let line = self.to_line().unwrap();
+        let mut stdout = io::stdout();
+        stdout.write_all(line.as_bytes()).unwrap();
+        stdout.write_all(b"\n").unwrap();
+    }
+
+    /// Converts the artifact to an output line without the line terminator.
+    ///
+    /// Unlabelled drawings are written in the form older versions of the testrunner expect.
+    pub fn to_line(&self) -> serde_json::Result<String> {
+        Ok(match self {
+            Artifact::Drawing {
+                label: None,
+                drawing,
+            } => format!("{}{}", DRAWING_PREFIX, serde_json::to_string(drawing)?),
+            artifact => format!("{}{}", ARTIFACT_PREFIX, serde_json::to_string(artifact)?),
+        })
+    }
+
+    /// Parses an output line, returns `None` if the line does not contain an artifact.
+    pub fn from_line(line: &str) -> Option<Artifact> {
+        if line.starts_with(ARTIFACT_PREFIX) {
+            serde_json::from_str(&line[ARTIFACT_PREFIX.len()..]).ok()
+        } else if line.starts_with(DRAWING_PREFIX) {
+            serde_json::from_str(&line[DRAWING_PREFIX.len()..])
+                .ok()
+                .map(|drawing| Artifact::Drawing {
+                    label: None,
+                    drawing,
+                })
+        } else {
+            None
+        }
+    }
+}
+
+/// Emits a labelled block of text.
+pub fn emit_text(label: impl Into<String>, text: impl Into<String>) {
+    Artifact::Text {
+        label: Some(label.into()),
+        text: text.into(),
+    }
+    .emit()
+}
+
+/// Emits a labelled table of key / value pairs.
+pub fn emit_table<K: ToString, V: ToString>(
+    label: impl Into<String>,
+    rows: impl IntoIterator<Item = (K, V)>,
+) {
+    Artifact::Table {
+        label: Some(label.into()),
+        rows: rows
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    }
+    .emit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Artifact;
+    use crate::functions::{paint, rect};
+    use crate::{Drawing, DrawingTarget};
+
+    #[test]
+    fn artifacts_roundtrip_through_output_lines() {
+        let mut drawing = Drawing::new();
+        drawing.draw(rect((0, 0), (10, 10)), paint());
+
+        let artifacts = [
+            Artifact::Drawing {
+                label: None,
+                drawing: drawing.clone(),
+            },
+            Artifact::Drawing {
+                label: Some("before".into()),
+                drawing,
+            },
+            Artifact::Table {
+                label: Some("metrics".into()),
+                rows: vec![("width".into(), "10".into())],
+            },
+        ];
+
+        for artifact in artifacts.iter() {
+            let line = artifact.to_line().unwrap();
+            assert_eq!(Artifact::from_line(&line).as_ref(), Some(artifact));
+        }
+
+        assert!(artifacts[0].to_line().unwrap().starts_with("> "));
+        assert_eq!(Artifact::from_line("plain output"), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! A side-channel that transfers artifacts from tests to the testrunner.
+//!
+//! When the testrunner sets the environment variable `EMERGENT_TEST_CAPTURES` to a directory,
+//! every artifact that is emitted in a test, for example a rendered drawing, is written to a
+//! separate file in that directory, and a line that references the file is printed to the output
+//! of the test. libtest captures the output of each test separately, so the testrunner attributes
+//! the artifacts to the tests that emitted them without relying on thread names or the order of
+//! the output lines.
+
+use crate::artifact::Artifact;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs, io, process};
+
+/// The environment variable that names the capture directory.
+pub const CAPTURE_DIRECTORY_VAR: &str = "EMERGENT_TEST_CAPTURES";
+
+/// The prefix of an output line that references an artifact file in the capture directory.
+pub const REFERENCE_PREFIX: &str = ">>> ";
+
+/// The sequence number of the next artifact written by this process.
+///
+/// Doc tests run in processes of their own that share the capture directory, so the file names
+/// contain the process id, too.
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the capture directory if the testrunner activated the side-channel.
+pub fn active_directory() -> Option<PathBuf> {
+    env::var_os(CAPTURE_DIRECTORY_VAR).map(PathBuf::from)
+}
+
+/// Writes an artifact of the current test into the capture directory and prints a reference to
+/// it to the captured output of the test.
+pub fn write(directory: &Path, artifact: &Artifact) -> io::Result<()> {
+    let sequence = SEQUENCE.fetch_add(1, Ordering::SeqCst);
+    let file_name = write_file(directory, process::id(), sequence, artifact)?;
+    // `print!()` and not `io::stdout()`, because only the former is captured by libtest.
+    println!("{}{}", REFERENCE_PREFIX, file_name);
+    Ok(())
+}
+
+/// Writes the artifact with a sequence number of a process to the capture directory and returns
+/// the name of its file.
+fn write_file(
+    directory: &Path,
+    process: u32,
+    sequence: usize,
+    artifact: &Artifact,
+) -> io::Result<String> {
+    let file_name = format!("{}-{:08}.json", process, sequence);
+    let json = serde_json::to_string(artifact)?;
+    fs::write(directory.join(&file_name), json)?;
+    Ok(file_name)
+}
+
+/// Reads the artifacts that are referenced in the output of a test from the capture directory.
+///
+/// Returns the output without the reference lines and the artifacts in the order they were
+/// emitted. The files are not removed, so the output of a test can be resolved more than once.
+///
+/// Tests may print lines that look like references themselves, so a line is only taken as a
+/// reference if it names a file in the capture directory that contains an artifact, other lines
+/// are kept in the output.
+pub fn resolve(directory: &Path, output: &str) -> (String, Vec<Artifact>) {
+    let mut text = String::new();
+    let mut artifacts = Vec::new();
+    for line in output.lines() {
+        match referenced_artifact(directory, line) {
+            Some(artifact) => artifacts.push(artifact),
+            None => {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+    }
+    (text, artifacts)
+}
+
+/// The artifact an output line references, `None` if the line is not a reference to an artifact
+/// file in the capture directory.
+fn referenced_artifact(directory: &Path, line: &str) -> Option<Artifact> {
+    if !line.starts_with(REFERENCE_PREFIX) {
+        return None;
+    }
+    let file_name = &line[REFERENCE_PREFIX.len()..];
+    // only bare file names, so that no file outside of the capture directory is read.
+    if Path::new(file_name).file_name() != Some(OsStr::new(file_name)) {
+        return None;
+    }
+    let json = fs::read_to_string(directory.join(file_name)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, write_file, REFERENCE_PREFIX};
+    use crate::artifact::Artifact;
+    use std::{env, fs, process};
+
+    #[test]
+    fn artifacts_of_processes_with_independent_sequences_are_distinct() {
+        let directory = env::temp_dir().join(format!("emergent-channel-test-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let text = |text: &str| Artifact::Text {
+            label: None,
+            text: text.into(),
+        };
+        // two doc test processes, both writing their first artifact.
+        let first = write_file(&directory, 1, 0, &text("first")).unwrap();
+        let second = write_file(&directory, 2, 0, &text("second")).unwrap();
+        assert_ne!(first, second);
+
+        let (_, first_artifacts) = resolve(&directory, &format!("{}{}\n", REFERENCE_PREFIX, first));
+        let (_, second_artifacts) =
+            resolve(&directory, &format!("{}{}\n", REFERENCE_PREFIX, second));
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(first_artifacts, vec![text("first")]);
+        assert_eq!(second_artifacts, vec![text("second")]);
+    }
+
+    #[test]
+    fn lines_that_reference_no_artifact_stay_in_the_output() {
+        let directory = env::temp_dir().join(format!("emergent-channel-lines-{}", process::id()));
+        fs::create_dir_all(directory.join("nested")).unwrap();
+        fs::write(directory.join("nested/artifact.json"), "{}").unwrap();
+        fs::write(directory.join("invalid.json"), "not an artifact").unwrap();
+
+        let output = concat!(
+            ">>> missing.json\n",
+            ">>> ../secret\n",
+            ">>> nested/artifact.json\n",
+            ">>> invalid.json\n"
+        );
+        let (text, artifacts) = resolve(&directory, output);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(text, output);
+        assert!(artifacts.is_empty());
+    }
+}
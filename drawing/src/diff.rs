@@ -0,0 +1,437 @@
+//! Structural differences between drawings.
+//!
+//! A drawing is a tree of nodes. Nodes are compared structurally, and numbers in shapes, paints,
+//! and transforms are compared with a tolerance.
+
+use crate::{
+    scalar, BlendMode, Clip, Color, Drawing, MeasureText, NearlyEqual, NearlyZero, Paint, Shape,
+    Transform, Visualize, RGB,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{fmt, iter};
+
+/// The path of a node in a drawing tree.
+///
+/// Each element is the index of the child node to descend into. Nodes that wrap a single drawing,
+/// like `Drawing::Transformed`, have one child at index 0.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct NodePath(pub Vec<usize>);
+
+impl NodePath {
+    fn child(&self, index: usize) -> NodePath {
+        let mut path = self.0.clone();
+        path.push(index);
+        NodePath(path)
+    }
+}
+
+impl fmt::Display for NodePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "/");
+        }
+        for index in &self.0 {
+            write!(f, "/{}", index)?;
+        }
+        Ok(())
+    }
+}
+
+/// A change of a node.
+///
+/// Paths of removed nodes refer to the old drawing, paths of added and modified nodes to the new
+/// drawing.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Change {
+    Added(NodePath),
+    Removed(NodePath),
+    Modified(NodePath),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added(path) => write!(f, "added {}", path),
+            Change::Removed(path) => write!(f, "removed {}", path),
+            Change::Modified(path) => write!(f, "modified {}", path),
+        }
+    }
+}
+
+/// The structural difference between two drawings.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DrawingDiff {
+    pub old: Drawing,
+    pub new: Drawing,
+    pub changes: Vec<Change>,
+}
+
+impl DrawingDiff {
+    /// Computes the difference between two drawings.
+    pub fn new(old: &Drawing, new: &Drawing) -> Self {
+        Self::with_tolerance(old, new, <scalar as NearlyZero>::NEARLY_ZERO)
+    }
+
+    /// Computes the difference between two drawings and treats numbers that differ by at most
+    /// `tolerance` as equal.
+    pub fn with_tolerance(old: &Drawing, new: &Drawing, tolerance: scalar) -> Self {
+        let mut changes = Vec::new();
+        diff_nodes(
+            old,
+            &NodePath::default(),
+            new,
+            &NodePath::default(),
+            tolerance,
+            &mut changes,
+        );
+        DrawingDiff {
+            old: old.clone(),
+            new: new.clone(),
+            changes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn diff_nodes(
+    old: &Drawing,
+    old_path: &NodePath,
+    new: &Drawing,
+    new_path: &NodePath,
+    tolerance: scalar,
+    changes: &mut Vec<Change>,
+) {
+    use Drawing::*;
+    let mut diff_child = |old, new| {
+        diff_nodes(
+            old,
+            &old_path.child(0),
+            new,
+            &new_path.child(0),
+            tolerance,
+            changes,
+        )
+    };
+
+    match (old, new) {
+        (Empty, Empty) => {}
+        (WithPaint(p1, d1), WithPaint(p2, d2)) if p1.nearly_equal(p2, tolerance) => {
+            diff_child(d1, d2)
+        }
+        (Transformed(t1, d1), Transformed(t2, d2)) if t1.nearly_equal(t2, tolerance) => {
+            diff_child(d1, d2)
+        }
+        (Clipped(c1, d1), Clipped(c2, d2)) if c1.nearly_equal(c2, tolerance) => diff_child(d1, d2),
+        (BackToFront(v1), BackToFront(v2)) => {
+            diff_children(v1, old_path, v2, new_path, tolerance, changes)
+        }
+        (Fill(b1), Fill(b2)) if b1.nearly_equal(b2, tolerance) => {}
+        (Shape(s1), Shape(s2)) if s1.nearly_equal(s2, tolerance) => {}
+        _ => changes.push(Change::Modified(new_path.clone())),
+    }
+}
+
+/// Compares the children of two `BackToFront` nodes.
+///
+/// Children that are nearly equal are matched by their longest common subsequence. Unmatched
+/// children between two matches are compared pairwise, and the remaining ones are reported as
+/// added or removed.
+fn diff_children(
+    old: &[Drawing],
+    old_path: &NodePath,
+    new: &[Drawing],
+    new_path: &NodePath,
+    tolerance: scalar,
+    changes: &mut Vec<Change>,
+) {
+    let matches = longest_common_subsequence(old, new, tolerance);
+
+    let (mut i, mut j) = (0, 0);
+    for (mi, mj) in matches
+        .into_iter()
+        .chain(iter::once((old.len(), new.len())))
+    {
+        let paired = (mi - i).min(mj - j);
+        for k in 0..paired {
+            diff_nodes(
+                &old[i + k],
+                &old_path.child(i + k),
+                &new[j + k],
+                &new_path.child(j + k),
+                tolerance,
+                changes,
+            );
+        }
+        changes.extend((i + paired..mi).map(|k| Change::Removed(old_path.child(k))));
+        changes.extend((j + paired..mj).map(|k| Change::Added(new_path.child(k))));
+        i = mi + 1;
+        j = mj + 1;
+    }
+}
+
+/// Returns the index pairs of the longest common subsequence of nearly equal drawings.
+///
+/// Each drawing is serialized once, the cells of the table compare the serialized values.
+fn longest_common_subsequence(
+    old: &[Drawing],
+    new: &[Drawing],
+    tolerance: scalar,
+) -> Vec<(usize, usize)> {
+    let old: Vec<Option<Value>> = old.iter().map(|d| serde_json::to_value(d).ok()).collect();
+    let new: Vec<Option<Value>> = new.iter().map(|d| serde_json::to_value(d).ok()).collect();
+    let equal = |i: usize, j: usize| match (&old[i], &new[j]) {
+        (Some(a), Some(b)) => values_nearly_equal(a, b, tolerance),
+        _ => false,
+    };
+
+    let (n, m) = (old.len(), new.len());
+    // lengths[i][j] is the length of the LCS of old[i..] and new[j..].
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    // matches[i][j] caches the comparison of old[i] and new[j] for the traceback.
+    let mut matches = vec![vec![false; m]; n];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            matches[i][j] = equal(i, j);
+            lengths[i][j] = if matches[i][j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            }
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if matches[i][j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+//
+// Tolerant comparison.
+//
+
+/// Compares two values by their serialized representation, numbers are compared with a
+/// tolerance.
+fn serialized_nearly_equal<T: Serialize>(a: &T, b: &T, tolerance: scalar) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => values_nearly_equal(&a, &b, tolerance),
+        _ => false,
+    }
+}
+
+fn values_nearly_equal(a: &Value, b: &Value, tolerance: scalar) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => match (a.as_f64(), b.as_f64()) {
+            (Some(a), Some(b)) => a.nearly_equal(&b, tolerance),
+            _ => a == b,
+        },
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| values_nearly_equal(a, b, tolerance))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(k, a)| match b.get(k) {
+                    Some(b) => values_nearly_equal(a, b, tolerance),
+                    None => false,
+                })
+        }
+        (a, b) => a == b,
+    }
+}
+
+macro_rules! impl_serialized_nearly_equal {
+    ($($t:ty),*) => {
+        $(
+            impl NearlyEqual for $t {
+                fn nearly_equal(&self, other: &Self, tolerance: scalar) -> bool {
+                    serialized_nearly_equal(self, other, tolerance)
+                }
+            }
+        )*
+    };
+}
+
+impl_serialized_nearly_equal!(Drawing, Shape, Paint, Transform, Clip);
+
+impl NearlyEqual for BlendMode {
+    /// Blend modes contain no numbers, so they are nearly equal only if they are equal.
+    fn nearly_equal(&self, other: &Self, _tolerance: scalar) -> bool {
+        self == other
+    }
+}
+
+//
+// Visualization
+//
+
+impl Visualize for DrawingDiff {
+    /// Visualizes the difference as an overlay.
+    ///
+    /// The new drawing is shown dimmed, removed nodes of the old drawing in red, and added or
+    /// modified nodes of the new drawing in green.
+    fn visualize(&self, _: &dyn MeasureText) -> Drawing {
+        let removed = 0xff0000.rgb();
+        let added = 0x00c000.rgb();
+
+        let mut layers = vec![recolored(&self.new, &dimmed)];
+        for change in &self.changes {
+            let layer = match change {
+                Change::Removed(path) => {
+                    node_at(&self.old, &path.0).map(|node| recolored(&node, &|_| removed))
+                }
+                Change::Added(path) | Change::Modified(path) => {
+                    node_at(&self.new, &path.0).map(|node| recolored(&node, &|_| added))
+                }
+            };
+            layers.extend(layer);
+        }
+
+        Drawing::BackToFront(layers)
+    }
+}
+
+fn dimmed(color: Color) -> Color {
+    Color::from((
+        color.red(),
+        color.green(),
+        color.blue(),
+        color.alpha() * 0.25,
+    ))
+}
+
+/// Returns the node at `path` including the transformations and clips of its ancestors.
+fn node_at(drawing: &Drawing, path: &[usize]) -> Option<Drawing> {
+    use Drawing::*;
+    let (index, rest) = match path.split_first() {
+        None => return Some(drawing.clone()),
+        Some(split) => split,
+    };
+
+    match (drawing, index) {
+        (WithPaint(paint, d), 0) => Some(WithPaint(*paint, node_at(d, rest)?.into())),
+        (Transformed(t, d), 0) => Some(Transformed(t.clone(), node_at(d, rest)?.into())),
+        (Clipped(c, d), 0) => Some(Clipped(c.clone(), node_at(d, rest)?.into())),
+        (BackToFront(v), i) => node_at(v.get(*i)?, rest),
+        _ => None,
+    }
+}
+
+/// Returns the drawing with the colors of all its paints mapped.
+fn recolored(drawing: &Drawing, f: &dyn Fn(Color) -> Color) -> Drawing {
+    fn map(drawing: &Drawing, f: &dyn Fn(Color) -> Color) -> Drawing {
+        use Drawing::*;
+        match drawing {
+            WithPaint(paint, d) => WithPaint(paint.color(f(paint.color)), map(d, f).into()),
+            Transformed(t, d) => Transformed(t.clone(), map(d, f).into()),
+            Clipped(c, d) => Clipped(c.clone(), map(d, f).into()),
+            BackToFront(v) => BackToFront(v.iter().map(|d| map(d, f)).collect()),
+            d => d.clone(),
+        }
+    }
+
+    // nodes without a paint are drawn with the default paint.
+    let default = Paint::default();
+    Drawing::WithPaint(default.color(f(default.color)), map(drawing, f).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, DrawingDiff, NodePath};
+    use crate::functions::{paint, rect};
+    use crate::{Bounds, Drawing, DrawingTarget, MeasureText, Text, Visualize, RGB};
+
+    #[test]
+    fn diff_reports_added_removed_and_modified_nodes() {
+        let mut old = Drawing::new();
+        old.draw(rect((0, 0), (10, 10)), paint());
+        old.draw(rect((10, 0), (10, 10)), paint());
+        old.draw(rect((20, 0), (10, 10)), paint());
+
+        let mut new = Drawing::new();
+        new.draw(rect((0, 0), (10, 10)), paint());
+        new.draw(rect((20.0, 0.0), (10.00001, 10.0)), paint());
+        new.draw(rect((30, 0), (10, 10)), paint());
+
+        let diff = DrawingDiff::new(&old, &new);
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::Removed(NodePath(vec![1])),
+                Change::Added(NodePath(vec![2]))
+            ]
+        );
+
+        let mut modified = Drawing::new();
+        modified.draw(rect((0, 0), (10, 10)), paint());
+        modified.draw(rect((10, 0), (12, 10)), paint());
+        modified.draw(rect((20, 0), (10, 10)), paint());
+
+        let diff = DrawingDiff::new(&old, &modified);
+        assert_eq!(diff.changes, vec![Change::Modified(NodePath(vec![1, 0]))]);
+        assert_eq!(NodePath(vec![1, 0]).to_string(), "/1/0");
+    }
+
+    struct NoText;
+
+    impl MeasureText for NoText {
+        fn measure_text(&self, _: &Text) -> Bounds {
+            unimplemented!("the drawings contain no text")
+        }
+    }
+
+    #[test]
+    fn visualized_diff_overlays_removed_and_added_nodes() {
+        let mut old = Drawing::new();
+        old.draw(rect((0, 0), (10, 10)), paint());
+        old.draw(rect((10, 0), (10, 10)), paint());
+        old.draw(rect((20, 0), (10, 10)), paint());
+
+        let mut new = Drawing::new();
+        new.draw(rect((0, 0), (10, 10)), paint());
+        new.draw(rect((20, 0), (10, 10)), paint());
+        new.draw(rect((30, 0), (10, 10)), paint());
+
+        let diff = DrawingDiff::new(&old, &new);
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::Removed(NodePath(vec![1])),
+                Change::Added(NodePath(vec![2]))
+            ]
+        );
+
+        let layers = match diff.visualize(&NoText) {
+            Drawing::BackToFront(layers) => layers,
+            d => panic!("unexpected visualization: {:?}", d),
+        };
+        let colors: Vec<_> = layers
+            .iter()
+            .map(|layer| match layer {
+                Drawing::WithPaint(layer_paint, _) => layer_paint.color,
+                d => panic!("unexpected layer: {:?}", d),
+            })
+            .collect();
+        // the dimmed new drawing below the removed and the added node.
+        assert_eq!(colors.len(), 3);
+        assert!(colors[0].alpha() < 1.0);
+        assert_eq!(colors[1], 0xff0000.rgb());
+        assert_eq!(colors[2], 0x00c000.rgb());
+    }
+}
@@ -90,6 +90,23 @@ impl Drawing {
             _ => None,
         }
     }
+
+    /// Sets the font family of all texts that use the default font, so that they are rendered
+    /// with that family instead.
+    pub fn set_default_font_family(&mut self, family: &str) {
+        match self {
+            Drawing::WithPaint(_, drawing)
+            | Drawing::Transformed(_, drawing)
+            | Drawing::Clipped(_, drawing) => drawing.set_default_font_family(family),
+            Drawing::BackToFront(drawings) => drawings
+                .iter_mut()
+                .for_each(|drawing| drawing.set_default_font_family(family)),
+            Drawing::Shape(Shape::Text(text)) if text.font.name.is_empty() => {
+                text.font.name = family.into()
+            }
+            _ => {}
+        }
+    }
 }
 
 impl Clipped for Drawing {
@@ -5,6 +5,11 @@
 #[macro_use]
 extern crate bitflags;
 
+pub mod artifact;
+pub mod capture_channel;
+pub mod diff;
+pub mod snapshot;
+
 mod drawing;
 pub use drawing::*;
 
@@ -61,18 +66,26 @@ pub trait Contains<Shape> {
 
 pub trait Render {
     fn render(&self);
+
+    /// Renders with a label that is shown in the testrunner.
+    fn render_as(&self, label: &str);
 }
 
 impl Render for Drawing {
     fn render(&self) {
-        use std::io;
-        use std::io::Write;
-
-        let rendered = serde_json::to_string(self).unwrap();
-        let mut stdout = io::stdout();
-        stdout.write_all(b"> ").unwrap();
-        stdout.write_all(rendered.as_bytes()).unwrap();
-        stdout.write_all(b"\n").unwrap();
+        artifact::Artifact::Drawing {
+            label: None,
+            drawing: self.clone(),
+        }
+        .emit()
+    }
+
+    fn render_as(&self, label: &str) {
+        artifact::Artifact::Drawing {
+            label: Some(label.into()),
+            drawing: self.clone(),
+        }
+        .emit()
     }
 }
 
@@ -0,0 +1,174 @@
+//! Golden snapshots of drawings.
+//!
+//! A snapshot is the serialized drawing a test rendered, stored in the `snapshots` directory next
+//! to the manifest of the package. The testrunner compares the drawings of every test run
+//! against their snapshots, and `assert_drawing_snapshot!` does the same in plain `cargo test`
+//! runs.
+
+use crate::diff::DrawingDiff;
+use crate::Drawing;
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::{fs, io, thread};
+
+/// The name of the snapshot directory relative to the package directory.
+pub const SNAPSHOT_DIRECTORY: &str = "snapshots";
+
+/// Identifies a drawing among the drawings of a test.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DrawingName<'a> {
+    Labelled(&'a str),
+    /// The n-th unlabelled drawing of the test, starting with 0.
+    Unlabelled(usize),
+}
+
+impl<'a> DrawingName<'a> {
+    /// Names the next drawing of a test. `unlabelled` counts the unlabelled drawings of the test
+    /// that were named before.
+    pub fn next(label: Option<&'a str>, unlabelled: &mut usize) -> Self {
+        match label {
+            Some(label) => DrawingName::Labelled(label),
+            None => {
+                *unlabelled += 1;
+                DrawingName::Unlabelled(*unlabelled - 1)
+            }
+        }
+    }
+}
+
+/// Returns the path of the snapshot of a drawing of a test.
+///
+/// Snapshots are grouped by the name of the crate the test is compiled into, so tests of the same
+/// name in different targets do not share their snapshots. Labelled drawings are stored under
+/// `<test>-<label>`, the first unlabelled drawing under the name of the test, and the following
+/// ones under `<test>#<n>`, where `n` counts from 2.
+pub fn snapshot_path(
+    package_directory: &Path,
+    crate_name: &str,
+    test: &str,
+    drawing: DrawingName,
+) -> PathBuf {
+    let test = file_name_of(test);
+    let name = match drawing {
+        DrawingName::Labelled(label) => format!("{}-{}", test, file_name_of(label)),
+        DrawingName::Unlabelled(0) => test,
+        DrawingName::Unlabelled(n) => format!("{}#{}", test, n + 1),
+    };
+    package_directory
+        .join(SNAPSHOT_DIRECTORY)
+        .join(file_name_of(crate_name))
+        .join(format!("{}.json", name))
+}
+
+/// Reads a snapshot, returns `None` if there is no snapshot at `path`.
+pub fn read(path: &Path) -> io::Result<Option<Drawing>> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Writes a snapshot and creates the snapshot directory if it does not exist.
+pub fn write(path: &Path, drawing: &Drawing) -> io::Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(drawing)?)
+}
+
+/// Converts a name to a name that can be used as a file name.
+pub fn file_name_of(name: &str) -> String {
+    name.replace("::", ".")
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+thread_local! {
+    /// The number of unlabelled drawings the test of the current thread asserted.
+    static UNLABELLED_DRAWINGS: Cell<usize> = Cell::new(0);
+}
+
+/// Compares a drawing of the current test with its snapshot and panics if they differ.
+///
+/// If there is no snapshot yet, the drawing is recorded as the new snapshot. This is the
+/// implementation of `assert_drawing_snapshot!`, `module_path` is the module path of the test.
+pub fn assert_snapshot(
+    package_directory: &Path,
+    module_path: &str,
+    drawing: &Drawing,
+    label: Option<&str>,
+) {
+    let current = thread::current();
+    let test = match current.name() {
+        Some(name) if name != "main" => name,
+        _ => panic!("snapshots can only be asserted on test threads, don't use --test-threads 1"),
+    };
+    let crate_name = module_path.split("::").next().unwrap_or(module_path);
+    // libtest runs every test on a new thread.
+    let name = UNLABELLED_DRAWINGS.with(|unlabelled| {
+        let mut count = unlabelled.get();
+        let name = DrawingName::next(label, &mut count);
+        unlabelled.set(count);
+        name
+    });
+
+    let path = snapshot_path(package_directory, crate_name, test, name);
+    match read(&path).expect("failed to read snapshot") {
+        Some(recorded) => {
+            let diff = DrawingDiff::new(&recorded, drawing);
+            if !diff.is_empty() {
+                let changes: Vec<String> = diff.changes.iter().map(|c| c.to_string()).collect();
+                panic!(
+                    "drawing does not match its snapshot {:?}: {}",
+                    path,
+                    changes.join(", ")
+                );
+            }
+        }
+        None => write(&path, drawing).expect("failed to write snapshot"),
+    }
+}
+
+/// Renders a drawing and compares it with its snapshot.
+///
+/// The snapshot is stored in the `snapshots` directory of the package the test belongs to. An
+/// optional label distinguishes multiple drawings of the same test.
+///
+/// ```ignore
+/// assert_drawing_snapshot!(drawing);
+/// assert_drawing_snapshot!(drawing, "after");
+/// ```
+#[macro_export]
+macro_rules! assert_drawing_snapshot {
+    ($drawing:expr) => {{
+        let drawing: &$crate::Drawing = &$drawing;
+        $crate::Render::render(drawing);
+        $crate::snapshot::assert_snapshot(
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+            module_path!(),
+            drawing,
+            None,
+        )
+    }};
+    ($drawing:expr, $label:expr) => {{
+        let drawing: &$crate::Drawing = &$drawing;
+        let label: &str = $label;
+        $crate::Render::render_as(drawing, label);
+        $crate::snapshot::assert_snapshot(
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+            module_path!(),
+            drawing,
+            Some(label),
+        )
+    }};
+}
@@ -1,7 +1,7 @@
 use crate::input_processor::{Subscriber, Subscription};
 use crate::{
-    AreaHitTest, Context, InputProcessor, InputState, ProcessorRecord, ScopedStore, Support, View,
-    ViewBuilder,
+    AreaHitTest, Context, InputProcessor, InputState, PersistentStates, ProcessorRecord,
+    ScopedStore, Support, View, ViewBuilder,
 };
 use emergent_drawing::{Bounds, Extent, Point};
 use emergent_presentation::Presentation;
@@ -22,6 +22,9 @@ pub struct Host<Msg> {
 
     /// The store of all captured states of the context scopes.
     store: ScopedStore,
+
+    /// The restored states of the scopes that were not presented since they were restored.
+    restored: PersistentStates,
 }
 
 impl<Msg> Host<Msg> {
@@ -31,6 +34,7 @@ impl<Msg> Host<Msg> {
             presentation: Default::default(),
             processors: Default::default(),
             store: ScopedStore::default(),
+            restored: PersistentStates::default(),
         }
     }
 
@@ -47,6 +51,8 @@ impl<Msg> Host<Msg> {
         let processor_store =
             ScopedStore::from_values(self.processors.drain(..).map(|r| r.into_scoped_state()));
         let store = store.merged(processor_store);
+        // restored states are used until the views of their scopes are presented.
+        let store = ScopedStore::from_persistent_states(&self.restored).merged(store);
 
         let context = Context::new(self.support.clone(), boundary, store);
         let builder = ViewBuilder::new(context);
@@ -64,6 +70,24 @@ impl<Msg> Host<Msg> {
         self.presentation = presentation;
         self.processors = processors;
         self.store = ScopedStore::from_values(states);
+        self.restored
+            .remove_presented(&self.store.persistent_states());
+    }
+
+    /// The states of the views that can be persisted, including the restored states of the views
+    /// that were not presented yet.
+    pub fn persistent_states(&self) -> PersistentStates {
+        let mut states = self.store.persistent_states();
+        states.extend(self.restored.clone());
+        states
+    }
+
+    /// Restores persisted view states, they are used when the views of their scopes are presented
+    /// the next time, even if that happens only in a later frame.
+    pub fn restore_states(&mut self, states: &PersistentStates) {
+        let store = mem::replace(&mut self.store, ScopedStore::default());
+        self.store = store.merged(ScopedStore::from_persistent_states(states));
+        self.restored = states.clone();
     }
 
     pub fn support(&self) -> &Support {
@@ -151,3 +175,56 @@ impl<Msg> Host<Msg> {
         msg
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Host;
+    use crate::view::tab;
+    use crate::{PathContainsPoint, PersistentScope, PersistentStates, Support, View, ViewBuilder};
+    use emergent_drawing::{Bounds, MeasureText, Path, Point, Text};
+    use emergent_presentation::Presentation;
+    use emergent_ui::{FrameLayout, DPI};
+
+    struct NoSupport;
+
+    impl MeasureText for NoSupport {
+        fn measure_text(&self, _text: &Text) -> Bounds {
+            unimplemented!()
+        }
+    }
+
+    impl PathContainsPoint for NoSupport {
+        fn path_contains_point(&self, _path: &Path, _p: Point) -> bool {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn restored_state_is_used_when_its_scope_is_presented_later() {
+        let mut host: Host<()> = Host::new(Support::new(DPI(1.0), NoSupport, NoSupport));
+        let frame = FrameLayout {
+            dimensions: (100, 100),
+            dpi: DPI(1.0),
+        };
+        let states = PersistentStates {
+            scroll_offsets: Vec::new(),
+            focused_tabs: vec![(vec![PersistentScope::Name("tabs".into())], 2)],
+        };
+        host.restore_states(&states);
+
+        // the first frame does not present the scope of the restored state.
+        host.present(frame, |b: ViewBuilder<()>| b.present(Presentation::Empty));
+        assert_eq!(host.persistent_states(), states);
+
+        let mut focused = None;
+        host.present(frame, |mut b: ViewBuilder<()>| -> View<()> {
+            let nested = b.scoped("tabs", |mut b| {
+                focused = Some(b.use_state(|| tab::State::focused(0)).focused_index);
+                b.present(Presentation::Empty)
+            });
+            b.wrapped(nested)
+        });
+        assert_eq!(focused, Some(2));
+        assert_eq!(host.persistent_states(), states);
+    }
+}
@@ -22,6 +22,9 @@ pub use input_state::*;
 mod interpolated;
 pub use interpolated::Interpolated;
 
+mod persistent_states;
+pub use persistent_states::*;
+
 mod processor_record;
 pub(crate) use processor_record::*;
 
@@ -0,0 +1,137 @@
+//! View states that outlive the application, like scroll offsets and the focused tabs.
+
+use crate::view::{scroll, tab};
+use crate::{ContextScope, ScopedStore};
+use emergent_drawing::Vector;
+use serde::{Deserialize, Serialize};
+use std::any::TypeId;
+
+/// A serializable version of a `ContextScope`.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub enum PersistentScope {
+    Name(String),
+    Index(usize),
+}
+
+/// The view states that can be persisted, together with the scope paths they were stored at.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
+pub struct PersistentStates {
+    /// The content transforms of scroll views.
+    pub scroll_offsets: Vec<(Vec<PersistentScope>, Vector)>,
+    /// The indices of the focused tabs of tab views.
+    pub focused_tabs: Vec<(Vec<PersistentScope>, usize)>,
+}
+
+impl PersistentStates {
+    /// Removes the states of the scopes that have a state of the same kind in `presented`.
+    pub fn remove_presented(&mut self, presented: &PersistentStates) {
+        self.scroll_offsets
+            .retain(|(path, _)| !presented.scroll_offsets.iter().any(|(p, _)| p == path));
+        self.focused_tabs
+            .retain(|(path, _)| !presented.focused_tabs.iter().any(|(p, _)| p == path));
+    }
+
+    /// Adds the states of `other`.
+    pub fn extend(&mut self, other: PersistentStates) {
+        self.scroll_offsets.extend(other.scroll_offsets);
+        self.focused_tabs.extend(other.focused_tabs);
+    }
+}
+
+impl ScopedStore {
+    /// Extracts the states that can be persisted.
+    pub fn persistent_states(&self) -> PersistentStates {
+        let mut states = PersistentStates::default();
+        self.collect_persistent_states(&mut Vec::new(), &mut states);
+        states
+    }
+
+    /// Creates a store that contains the persisted states.
+    pub fn from_persistent_states(states: &PersistentStates) -> ScopedStore {
+        let mut store = ScopedStore::new();
+        for (path, offset) in &states.scroll_offsets {
+            let state = scroll::State::scrolled_to(*offset);
+            store.insert_persistent_state(path, state);
+        }
+        for (path, index) in &states.focused_tabs {
+            store.insert_persistent_state(path, tab::State::focused(*index));
+        }
+        store
+    }
+
+    fn collect_persistent_states(
+        &self,
+        path: &mut Vec<PersistentScope>,
+        states: &mut PersistentStates,
+    ) {
+        if let Some(scroll) = self.typed_state::<scroll::State>() {
+            states
+                .scroll_offsets
+                .push((path.clone(), scroll.content_transform));
+        }
+        if let Some(tab) = self.typed_state::<tab::State>() {
+            states.focused_tabs.push((path.clone(), tab.focused_index));
+        }
+
+        for (scope, nested) in &self.nested {
+            path.push(scope.into());
+            nested.collect_persistent_states(path, states);
+            path.pop();
+        }
+    }
+
+    fn typed_state<S: 'static>(&self) -> Option<&S> {
+        self.states
+            .get(&TypeId::of::<S>())
+            .and_then(|s| s.downcast_ref::<S>())
+    }
+
+    fn insert_persistent_state<S: 'static>(&mut self, path: &[PersistentScope], state: S) {
+        let path: Vec<ContextScope> = path.iter().map(ContextScope::from).collect();
+        self.at_mut_or_new(&path)
+            .states
+            .insert(TypeId::of::<S>(), Box::new(state));
+    }
+}
+
+impl From<&ContextScope> for PersistentScope {
+    fn from(scope: &ContextScope) -> Self {
+        match scope {
+            ContextScope::Name(name, _) => PersistentScope::Name(name.to_string()),
+            ContextScope::Index(index) => PersistentScope::Index(*index),
+        }
+    }
+}
+
+impl From<&PersistentScope> for ContextScope {
+    fn from(scope: &PersistentScope) -> Self {
+        match scope {
+            PersistentScope::Name(name) => name.clone().into(),
+            PersistentScope::Index(index) => (*index).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PersistentScope, PersistentStates};
+    use crate::ScopedStore;
+    use emergent_drawing::Vector;
+
+    #[test]
+    fn persistent_states_roundtrip() {
+        let states = PersistentStates {
+            scroll_offsets: vec![(
+                vec![
+                    PersistentScope::Name("captures".into()),
+                    PersistentScope::Index(0),
+                ],
+                Vector::new(0.0, -120.0),
+            )],
+            focused_tabs: vec![(Vec::new(), 2)],
+        };
+
+        let store = ScopedStore::from_persistent_states(&states);
+        assert_eq!(store.persistent_states(), states);
+    }
+}
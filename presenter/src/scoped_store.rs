@@ -17,7 +17,7 @@ pub struct ScopedStore {
     pub(crate) states: TypedStore,
 
     /// Nested scoped stores.
-    nested: HashMap<ContextScope, ScopedStore>,
+    pub(crate) nested: HashMap<ContextScope, ScopedStore>,
 }
 
 impl Default for ScopedStore {
@@ -7,13 +7,23 @@ use emergent_drawing::{scalar, Point, Rect, Vector};
 use std::time::Duration;
 
 #[derive(Clone)]
-struct State {
+pub(crate) struct State {
     /// The transformation vector of the content.
-    content_transform: Vector,
+    pub(crate) content_transform: Vector,
     /// Current movement?
     movement_active: bool,
 }
 
+impl State {
+    /// A state that is scrolled to the given content transform.
+    pub(crate) fn scrolled_to(content_transform: Vector) -> Self {
+        State {
+            content_transform,
+            movement_active: false,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 struct ConstrainedContentTransform(Vector);
 
@@ -3,21 +3,28 @@
 use crate::{Direction, ScopedView, SimpleLayout, View, ViewBuilder, ViewReducer};
 use emergent_drawing::{DrawingFastBounds, Transformed, Vector};
 
-struct State {
-    focused_index: usize,
+pub(crate) struct State {
+    pub(crate) focused_index: usize,
     nested_transform: Vector,
 }
 
+impl State {
+    /// A state that focuses the tab at `index`.
+    pub(crate) fn focused(focused_index: usize) -> Self {
+        State {
+            focused_index,
+            nested_transform: Vector::default(),
+        }
+    }
+}
+
 pub fn view<Msg: 'static>(
     mut builder: ViewBuilder<Msg>,
     build_content: impl FnOnce(&mut ViewBuilder<Msg>) -> Vec<ScopedView<Msg>>,
 ) -> View<Msg> {
     let views = build_content(&mut builder);
     assert!(!views.is_empty());
-    let state = builder.use_state(|| State {
-        focused_index: 0,
-        nested_transform: Vector::default(),
-    });
+    let state = builder.use_state(|| State::focused(0));
     let focused = state.focused_index.min(views.len() - 1);
     let bounds = Direction::Row.layout_bounds(views.iter().map(|v| v.fast_bounds(&builder)));
     let focused_bounds = bounds[focused].as_bounds();
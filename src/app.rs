@@ -1,40 +1,139 @@
 use cargo_metadata::diagnostic::DiagnosticLevel;
-use crossbeam_channel::Receiver;
-use emergent::compiler_message::ToDrawing;
-use emergent::test_runner::{TestEnvironment, TestRunRequest, TestRunResult};
-use emergent::test_watcher::{Notification, TestWatcher};
+use cargo_metadata::CompilerMessage;
+use crossbeam_channel::{Receiver, Sender};
+use emergent::bench_chart::bench_chart;
+use emergent::compiler_message::{present_diagnostic, present_resolved_diagnostic, SourceFiles};
+use emergent::diagnostic_diff::{DiagnosticChange, DiagnosticDiff, DiagnosticKey};
+use emergent::editor::EditorCommand;
+use emergent::libtest::{BenchResult, TestResult, TestTotals};
+use emergent::lint::{self, LintGroup};
+use emergent::project_config::RunConfiguration;
+use emergent::suggestion::{self, Replacement};
+use emergent::test_filter::TestFilter;
+use emergent::test_history::{TestHistory, TestRunRecord};
+use emergent::test_progress::TestProgress;
+use emergent::test_runner::{TestEnvironment, TestId, TestRunRequest, TestRunResult};
+use emergent::test_stress::StressResult;
+use emergent::test_watcher::{Backoff, Notification, TestWatcher};
+use emergent::ui_state::ProjectUiState;
 use emergent::{compiler_message, WindowModel};
 use emergent::{test_watcher, Msg};
+use emergent_drawing::functions::{paint, text};
+use emergent_drawing::{font, Drawing, DrawingTarget, Font, RGB};
 use emergent_presentation::Presentation;
+use emergent_presenter::input_processor::Tap;
 use emergent_presenter::{
-    scroll, tab, AsData, Direction, IndexAccessible, Reducible, View, ViewBuilder, ViewRenderer,
+    scroll, tab, AsData, Direction, IndexAccessible, IndexMappable, InputProcessor, Item,
+    Reducible, ScopedView, View, ViewBuilder, ViewRenderer,
 };
-use std::collections::HashSet;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
 use tears::Cmd;
 
 pub struct App {
-    watcher: TestWatcher,
+    /// The running watcher, `None` if it stopped and is waiting to be restarted.
+    watcher: Option<TestWatcher>,
+    watcher_status: WatcherStatus,
+    backoff: Backoff,
+    /// The request and the environment the watcher is (re)started with.
+    request: TestRunRequest,
+    test_environment: TestEnvironment,
+    /// The run configurations of the project, the request uses one of them.
+    configurations: Vec<RunConfiguration>,
+    notification_sender: Sender<test_watcher::Notification>,
     notification_receiver: Receiver<test_watcher::Notification>,
 
-    pub(crate) test_run_result: Option<TestRunResult>,
+    history: TestHistory,
+    /// The number of the run that is shown, `None` to show the latest run.
+    selected_run: Option<usize>,
+    /// The run that is in progress.
+    started_run: Option<StartedRun>,
+    /// The progress of the test run that is currently running.
+    progress: Option<TestProgress>,
     latest_test_error: Option<String>,
-    collapsed_tests: HashSet<String>,
+    collapsed_tests: HashSet<TestId>,
+    collapsed_packages: HashSet<String>,
+    /// The pattern that filters the tests that are shown, it filters the tests that are run
+    /// after it is applied.
+    filter: String,
+    /// The diagnostics of the first run of each run configuration in the session.
+    session_diagnostics: HashMap<String, HashSet<DiagnosticKey>>,
+    /// Hide the warnings the first run of the session already reported.
+    hide_session_warnings: bool,
+    /// The clippy lints whose messages are hidden.
+    hidden_lints: HashSet<String>,
+    /// The number of times a test is run to find out if it is flaky.
+    stress_runs: usize,
+    /// The test that is run repeatedly.
+    stress_test: Option<TestId>,
+    /// The outcomes of the test that was run repeatedly last.
+    stress_result: Option<StressResult>,
+    editor: EditorCommand,
+    /// The source files the diagnostics of the shown run refer to, with the number of the run.
+    ///
+    /// The files are read once per run and not every time the run is presented.
+    displayed_sources: RefCell<Option<(usize, Rc<SourceFiles>)>>,
+}
+
+/// Problems of the watcher that are shown in a banner.
+#[derive(Clone, Default, Debug)]
+struct WatcherStatus {
+    /// The error the watcher stopped with, `None` if it is running.
+    error: Option<String>,
+    /// The number of restart attempts since the last completed test run.
+    attempt: u32,
+}
+
+/// A test run that has been started and is not completed yet.
+#[derive(Debug)]
+struct StartedRun {
+    started: SystemTime,
+    configuration: String,
+    changed_files: Vec<PathBuf>,
 }
 
 impl App {
-    pub fn new(req: TestRunRequest, test_environment: TestEnvironment) -> (Self, Cmd<Msg>) {
+    pub fn new(
+        req: TestRunRequest,
+        test_environment: TestEnvironment,
+        configurations: Vec<RunConfiguration>,
+        editor: EditorCommand,
+        stress_runs: usize,
+        ui_state: &ProjectUiState,
+    ) -> (Self, Cmd<Msg>) {
         let (sender, receiver) = crossbeam_channel::unbounded();
-        let watcher = TestWatcher::begin_watching(req, test_environment, sender).unwrap();
 
-        let emergent = App {
-            watcher,
+        let mut emergent = App {
+            watcher: None,
+            watcher_status: WatcherStatus::default(),
+            backoff: Backoff::default(),
+            request: req.with_filter(TestFilter::pattern(&ui_state.filter)),
+            test_environment,
+            configurations,
+            notification_sender: sender,
             notification_receiver: receiver,
-            test_run_result: None,
+            history: TestHistory::default(),
+            selected_run: None,
+            started_run: None,
+            progress: None,
             latest_test_error: None,
-            // TODO: this is part of the persistent state.
-            collapsed_tests: HashSet::new(),
+            collapsed_tests: ui_state.collapsed_tests.clone(),
+            collapsed_packages: ui_state.collapsed_packages.clone(),
+            filter: ui_state.filter.clone(),
+            session_diagnostics: HashMap::new(),
+            hide_session_warnings: ui_state.hide_session_warnings,
+            hidden_lints: ui_state.hidden_lints.clone(),
+            stress_runs,
+            stress_test: None,
+            stress_result: None,
+            editor,
+            displayed_sources: RefCell::new(None),
         };
 
+        emergent.restart_watcher();
         let cmd = emergent.receive_watcher_notifications();
         (emergent, cmd)
     }
@@ -47,32 +146,150 @@ impl WindowModel<Msg> for App {
             Msg::WatcherNotification(wn) => match wn {
                 Ok(notification) => return self.update_watcher(notification),
                 Err(e) => {
-                    panic!("watcher notification error: {}", e.to_string());
+                    // the app holds a sender, so this should never happen. If it does, receiving
+                    // again would fail immediately.
+                    error!("watcher notification error: {}", e);
+                    self.watcher_status.error = Some(e.to_string());
+                    return Cmd::None;
                 }
             },
-            Msg::ToggleTestcase { name } => {
-                if self.collapsed_tests.contains(&name) {
-                    self.collapsed_tests.remove(&name);
+            Msg::ToggleTestcase(test) => {
+                if self.collapsed_tests.contains(&test) {
+                    self.collapsed_tests.remove(&test);
+                } else {
+                    self.collapsed_tests.insert(test);
+                }
+            }
+            Msg::TogglePackage { name } => {
+                if self.collapsed_packages.contains(&name) {
+                    self.collapsed_packages.remove(&name);
                 } else {
-                    self.collapsed_tests.insert(name);
+                    self.collapsed_packages.insert(name);
+                }
+            }
+            Msg::AcceptSnapshots { path } => self.accept_snapshots(&path),
+            Msg::OpenLocation(location) => {
+                if let Err(e) = self.editor.open(&location) {
+                    warn!("failed to open {} in the editor: {}", location, e);
+                }
+            }
+            Msg::ApplySuggestions(replacements) => self.apply_suggestions(&replacements),
+            Msg::ApplyAllSuggestions => {
+                let replacements = self.displayed_suggestions();
+                self.apply_suggestions(&replacements)
+            }
+            Msg::ToggleSessionWarnings => self.hide_session_warnings = !self.hide_session_warnings,
+            Msg::ToggleLint(lint) => {
+                if !self.hidden_lints.remove(&lint) {
+                    self.hidden_lints.insert(lint);
                 }
             }
-            Msg::RerunTestcases(environment) => {
-                self.watcher.update_environment(environment);
+            Msg::SelectRun(number) => self.selected_run = number,
+            Msg::SelectConfiguration(name) => self.select_configuration(&name),
+            Msg::SetFilter(filter) => self.filter = filter,
+            Msg::ApplyFilter => self.apply_filter(),
+            Msg::ClearFilter => {
+                self.filter.clear();
+                self.apply_filter();
+            }
+            Msg::RunTest(test) => return self.run_test(test),
+            Msg::TestRunCompleted { run, result } => {
+                self.merge_test_run(run, result);
+                return Cmd::None;
+            }
+            Msg::StressTest(test) => return self.stress_test(test),
+            Msg::StressTestCompleted(result) => {
+                self.stress_test = None;
+                match result {
+                    Ok(result) => self.stress_result = Some(result),
+                    Err(e) => self.latest_test_error = Some(e.to_string()),
+                }
+                return Cmd::None;
+            }
+            Msg::RestartWatcher => {
+                // joining the watcher thread waits for the test run in progress.
+                if let Some(watcher) = self.watcher.take() {
+                    watcher.stop_in_background();
+                }
+                // the stopped watcher does not complete the run it started.
+                self.started_run = None;
+                self.progress = None;
+                self.restart_watcher();
+            }
+            Msg::DpiChanged(dpi) => {
+                self.test_environment.dpi = dpi;
+                if let Some(watcher) = &mut self.watcher {
+                    watcher.update_environment(self.test_environment.clone());
+                }
             }
         }
         self.receive_watcher_notifications()
     }
+
+    /// Typing edits the filter, enter applies it, and escape clears it.
+    fn received_character(&self, c: char) -> Option<Msg> {
+        match c {
+            '\r' | '\n' => Some(Msg::ApplyFilter),
+            '\u{1b}' => Some(Msg::ClearFilter),
+            '\u{8}' | '\u{7f}' => {
+                let mut filter = self.filter.clone();
+                filter.pop();
+                Some(Msg::SetFilter(filter))
+            }
+            c if c.is_control() => None,
+            c => Some(Msg::SetFilter(format!("{}{}", self.filter, c))),
+        }
+    }
 }
 
 impl App {
     fn update_watcher(&mut self, notification: test_watcher::Notification) -> Cmd<Msg> {
         match notification {
+            Notification::Progress(progress) => {
+                self.progress
+                    .get_or_insert_with(TestProgress::default)
+                    .update(progress);
+                self.receive_watcher_notifications()
+            }
+            Notification::TestRunStarted {
+                changed_files,
+                configuration,
+            } => {
+                self.started_run = Some(StartedRun {
+                    started: SystemTime::now(),
+                    configuration,
+                    changed_files,
+                });
+                self.receive_watcher_notifications()
+            }
             Notification::TestRunCompleted(r) => {
+                self.progress = None;
+                let run = self.started_run.take().unwrap_or_else(|| StartedRun {
+                    started: SystemTime::now(),
+                    configuration: self.request.configuration.name.clone(),
+                    changed_files: Vec::new(),
+                });
                 match r {
                     Ok(run_result) => {
-                        self.test_run_result = Some(run_result);
+                        self.session_diagnostics
+                            .entry(run.configuration.clone())
+                            .or_insert_with(|| {
+                                DiagnosticKey::of_messages(run_result.compiler_messages())
+                            });
+                        self.history.push(
+                            run.started,
+                            run.configuration,
+                            run.changed_files,
+                            run_result,
+                        );
+                        // the selected run may have been dropped from the history.
+                        if let Some(number) = self.selected_run {
+                            if self.history.get(number).is_none() {
+                                self.selected_run = None;
+                            }
+                        }
                         self.latest_test_error = None;
+                        self.watcher_status.attempt = 0;
                     }
                     Err(e) => {
                         self.latest_test_error = Some(e.to_string());
@@ -82,14 +299,241 @@ impl App {
             }
 
             Notification::WatcherStopped(e) => {
-                // TODO: restart it here?
-                panic!("watcher stopped: {}", e.to_string());
-                // self.latest_test_error = Some(e.to_string());
-                // Cmd::None
+                warn!("watcher stopped: {}", e);
+                self.watcher = None;
+                self.watcher_stopped(e.to_string());
+                self.receive_watcher_notifications()
+            }
+
+            Notification::RestartDue { attempt } => {
+                // restarts are only due if no other restart happened in the meantime.
+                if self.watcher.is_none() && attempt == self.watcher_status.attempt {
+                    self.restart_watcher();
+                }
+                self.receive_watcher_notifications()
+            }
+        }
+    }
+
+    /// The state of the user interface that is persisted, without the states of the views.
+    pub fn ui_state(&self) -> ProjectUiState {
+        ProjectUiState {
+            collapsed_tests: self.collapsed_tests.clone(),
+            collapsed_packages: self.collapsed_packages.clone(),
+            filter: self.filter.clone(),
+            hide_session_warnings: self.hide_session_warnings,
+            hidden_lints: self.hidden_lints.clone(),
+            view_states: Default::default(),
+        }
+    }
+
+    /// Runs only the tests that match the filter from now on.
+    fn apply_filter(&mut self) {
+        let filter = TestFilter::pattern(&self.filter);
+        if filter == self.request.filter {
+            return;
+        }
+        self.request = self.request.clone().with_filter(filter);
+        if let Some(watcher) = &mut self.watcher {
+            watcher.update_request(self.request.clone());
+        }
+    }
+
+    /// Runs the tests with the named configuration from now on and shows the latest results of
+    /// that configuration.
+    fn select_configuration(&mut self, name: &str) {
+        let configuration = match self.configurations.iter().find(|c| c.name == name) {
+            Some(configuration) => configuration.clone(),
+            None => {
+                warn!("no run configuration named {}", name);
+                return;
+            }
+        };
+        self.selected_run = None;
+        if configuration == self.request.configuration {
+            return;
+        }
+        self.request = self.request.clone().with_configuration(configuration);
+        if let Some(watcher) = &mut self.watcher {
+            watcher.update_request(self.request.clone());
+        }
+    }
+
+    /// Returns a command that runs a single test and merges its capture into the run that is
+    /// shown.
+    ///
+    /// The test is run with the configuration of the run that is shown.
+    fn run_test(&self, test: TestId) -> Cmd<Msg> {
+        let run = match self.displayed_run() {
+            Some(run) => run.number,
+            None => return Cmd::None,
+        };
+        let request = self.displayed_request().for_test(&test);
+        let environment = self.test_environment.clone();
+        Cmd::from(move || Msg::TestRunCompleted {
+            run,
+            result: request.capture_tests(environment),
+        })
+    }
+
+    /// Returns a command that runs a single test repeatedly to find out if it is flaky.
+    ///
+    /// The test is run with the configuration of the run that is shown.
+    fn stress_test(&mut self, test: TestId) -> Cmd<Msg> {
+        if self.stress_test.is_some() {
+            warn!("a test is already run repeatedly");
+            return Cmd::None;
+        }
+        self.stress_test = Some(test.clone());
+        let request = self.displayed_request();
+        let environment = self.test_environment.clone();
+        let runs = self.stress_runs;
+        Cmd::from(move || {
+            Msg::StressTestCompleted(StressResult::run(&request, &test, &environment, runs))
+        })
+    }
+
+    /// The request with the configuration of the run that is shown.
+    fn displayed_request(&self) -> TestRunRequest {
+        let name = self.displayed_configuration();
+        match self.configurations.iter().find(|c| c.name == name) {
+            Some(configuration) => self
+                .request
+                .clone()
+                .with_configuration(configuration.clone()),
+            None => self.request.clone(),
+        }
+    }
+
+    fn merge_test_run(&mut self, run: usize, result: Result<TestRunResult, failure::Error>) {
+        match result {
+            Ok(TestRunResult::TestsCaptured(_, packages)) => match self.history.get_mut(run) {
+                Some(record) => record.result.merge(packages),
+                None => warn!("run #{} is not in the history anymore", run),
+            },
+            Ok(TestRunResult::CompilationFailed(_, e)) | Err(e) => {
+                self.latest_test_error = Some(e.to_string())
             }
         }
     }
 
+    /// Starts a new watcher and schedules another attempt if that fails.
+    fn restart_watcher(&mut self) {
+        match TestWatcher::begin_watching(
+            self.request.clone(),
+            self.test_environment.clone(),
+            self.notification_sender.clone(),
+        ) {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                self.watcher_status.error = None;
+            }
+            Err(e) => self.watcher_stopped(e.to_string()),
+        }
+    }
+
+    /// Records the error of a stopped watcher and schedules the next restart attempt.
+    fn watcher_stopped(&mut self, error: String) {
+        self.watcher_status.error = Some(error);
+        self.watcher_status.attempt += 1;
+        self.backoff.schedule_restart(
+            self.watcher_status.attempt,
+            self.notification_sender.clone(),
+        );
+    }
+
+    /// Accepts the snapshots of the capture the snapshot at `path` belongs to.
+    ///
+    /// Writing the snapshots triggers a new test run.
+    fn accept_snapshots(&mut self, path: &Path) {
+        let run = match self.displayed_run().map(|r| r.number) {
+            Some(number) => self.history.get_mut(number),
+            None => None,
+        };
+        let capture = run.and_then(|r| {
+            r.result
+                .captures_mut()
+                .find(|c| c.snapshots.first().map(|s| s.path.as_path()) == Some(path))
+        });
+
+        match capture {
+            Some(capture) => {
+                if let Err(e) = capture.accept_snapshots() {
+                    self.latest_test_error = Some(e.to_string());
+                }
+            }
+            None => warn!("no capture with snapshot {:?}", path),
+        }
+    }
+
+    /// Applies suggested replacements of the compiler to the source files.
+    ///
+    /// Changing the source files triggers a new test run.
+    fn apply_suggestions(&mut self, replacements: &[Replacement]) {
+        let sources = self.source_files();
+        match suggestion::apply(replacements, &sources) {
+            Ok(applied) => info!("applied {} suggested replacements", applied),
+            Err(e) => self.latest_test_error = Some(e.to_string()),
+        }
+    }
+
+    /// The suggestions of the compiler messages of the run that is shown that can be applied
+    /// automatically.
+    ///
+    /// Only the suggestions of messages that are shown are returned, the ones of warnings hidden
+    /// because they predate the session or because their lint is hidden are not. The messages of
+    /// a file that is compiled for more than one target are duplicated, but their suggestions are
+    /// returned once.
+    fn displayed_suggestions(&self) -> Vec<Replacement> {
+        let messages = match self.displayed_run() {
+            Some(run) => run.result.compiler_messages(),
+            None => return Vec::new(),
+        };
+        let mut replacements = Vec::new();
+        for replacement in messages
+            .iter()
+            .filter(|cm| is_compiler_error(cm) || self.is_warning_shown(cm))
+            .flat_map(|cm| suggestion::machine_applicable(&cm.message))
+        {
+            if !replacements.contains(&replacement) {
+                replacements.push(replacement);
+            }
+        }
+        replacements
+    }
+
+    /// The diagnostics of the run that is shown compared with the ones of the previous run with
+    /// the same run configuration.
+    fn displayed_diagnostic_diff(&self) -> DiagnosticDiff {
+        let run = match self.displayed_run() {
+            Some(run) => run,
+            None => return DiagnosticDiff::default(),
+        };
+        let previous = self
+            .history
+            .previous_of(run.number, &run.configuration)
+            .map(|previous| previous.result.compiler_messages());
+        DiagnosticDiff::new(previous, run.result.compiler_messages())
+    }
+
+    /// Whether a message that is not an error is shown in full, it is not if it predates the
+    /// session and those warnings are hidden, or if its clippy lint is hidden.
+    fn is_warning_shown(&self, message: &CompilerMessage) -> bool {
+        let lint_hidden =
+            lint::clippy_lint(message).map_or(false, |lint| self.hidden_lints.contains(lint));
+        !lint_hidden && !(self.hide_session_warnings && self.predates_session(message))
+    }
+
+    /// Whether the first run of the session with the configuration that is shown already
+    /// reported the diagnostic of the message.
+    fn predates_session(&self, message: &CompilerMessage) -> bool {
+        self.session_diagnostics
+            .get(self.displayed_configuration())
+            .map_or(false, |keys| {
+                keys.contains(&DiagnosticKey::of(&message.message))
+            })
+    }
+
     /// Returns a command that receives watcher notifications.
     fn receive_watcher_notifications(&self) -> Cmd<Msg> {
         let receiver = self.notification_receiver.clone();
@@ -97,86 +541,575 @@ impl App {
     }
 }
 
+impl App {
+    /// Shows a banner with the run configurations, the error of a stopped watcher, and the run
+    /// that is shown if it is not the latest, above the content.
+    fn with_banner(
+        &self,
+        b: ViewBuilder<Msg>,
+        content: impl Fn(ViewBuilder<Msg>) -> View<Msg>,
+    ) -> View<Msg> {
+        let configurations =
+            Item::new(&self.configurations).map(|b, _| self.present_configurations(b));
+
+        let past_run = Item::new(&self.selected_run).map(|mut b, selected| {
+            let run = match selected.and_then(|number| self.history.get(number)) {
+                Some(run) => run,
+                None => return b.present(Presentation::Empty),
+            };
+            let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+            let line = format!(
+                "showing run #{} from {}  [ show latest run ]",
+                run.number,
+                run.time_of_day()
+            );
+            let mut drawing = Drawing::new();
+            drawing.draw(text(line, font, None), paint().color(0x0000c0.rgb()));
+            let presentation = Presentation::from(drawing).in_area();
+            b.use_input_processor(|| Tap::new().map(|_| Some(Msg::SelectRun(None))));
+            b.present(presentation)
+        });
+
+        let error = match &self.watcher_status.error {
+            Some(error) => error,
+            None => {
+                let content = Item::new(&()).map(|b, _| content(b));
+                return configurations
+                    .extend(&past_run)
+                    .extend(&content)
+                    .reduce(b, Direction::Column);
+            }
+        };
+
+        let banner = Item::new(error).map(|b, error| {
+            let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+            // the error is cleared when the watcher restarts.
+            let status = format!(
+                "watcher stopped: {} (restart attempt {})",
+                error, self.watcher_status.attempt
+            );
+            let mut drawing = Drawing::new();
+            drawing.draw(text(status, font, None), paint().color(0xc00000.rgb()));
+            b.present(drawing.into())
+        });
+
+        let restart = Item::new(&()).map(|mut b, _| {
+            let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+            let mut drawing = Drawing::new();
+            drawing.draw(
+                text("[ restart watcher ]", font, None),
+                paint().color(0x0000c0.rgb()),
+            );
+            let presentation = Presentation::from(drawing).in_area();
+            b.use_input_processor(|| Tap::new().map(|_| Some(Msg::RestartWatcher)));
+            b.present(presentation)
+        });
+
+        let content = Item::new(&()).map(|b, _| content(b));
+
+        configurations
+            .extend(&banner)
+            .extend(&restart)
+            .extend(&past_run)
+            .extend(&content)
+            .reduce(b, Direction::Column)
+    }
+}
+
+impl App {
+    /// Presents the names of the run configurations if there is more than one, tapping a name
+    /// selects the configuration.
+    fn present_configurations(&self, b: ViewBuilder<Msg>) -> View<Msg> {
+        if self.configurations.len() < 2 {
+            return b.present(Presentation::Empty);
+        }
+
+        let shown = self.displayed_configuration();
+        self.configurations
+            .as_data()
+            .map_view(|mut b, configuration| {
+                let style = if configuration.name == shown {
+                    font::Style::BOLD
+                } else {
+                    font::Style::NORMAL
+                };
+                let font = &Font::new("", style, font::Size::new(16.0));
+                let mut drawing = Drawing::new();
+                drawing.draw(
+                    text(format!("[ {} ]  ", configuration.name), font, None),
+                    paint().color(0x0000c0.rgb()),
+                );
+                let presentation = Presentation::from(drawing).in_area();
+                let name = configuration.name.clone();
+                b.use_input_processor(|| {
+                    Tap::new().map(move |_| Some(Msg::SelectConfiguration(name.clone())))
+                });
+                b.present(presentation)
+            })
+            .reduce(b, Direction::Row)
+    }
+}
+
 impl ViewRenderer<Msg> for App {
     fn render_view(&self, builder: ViewBuilder<Msg>) -> View<Msg> {
-        let create = |b: &mut ViewBuilder<Msg>| match &self.test_run_result {
+        if let Some(progress) = &self.progress {
+            // while tests are running, their captures are shown as soon as they finish.
+            let create = |b: &mut ViewBuilder<Msg>| {
+                let progress = |b: ViewBuilder<_>| progress.present(b, &self.collapsed_tests);
+                vec![b.scoped("progress", |b| {
+                    scroll::view(b, |b| self.with_banner(b, progress))
+                })]
+            };
+            return tab::view(builder, create);
+        }
+
+        let create = |b: &mut ViewBuilder<Msg>| {
+            let mut tabs = self.result_tabs(b);
+            if !self.displayed_benchmarks().is_empty() {
+                tabs.push(b.scoped("benchmarks", |b| {
+                    scroll::view(b, |b| self.with_banner(b, |b| self.present_benchmarks(b)))
+                }));
+            }
+            if self.stress_test.is_some() || self.stress_result.is_some() {
+                tabs.push(b.scoped("stress", |b| {
+                    scroll::view(b, |b| self.with_banner(b, |b| self.present_stress(b)))
+                }));
+            }
+            if self.history.latest().is_some() {
+                tabs.push(b.scoped("history", |b| {
+                    scroll::view(b, |b| self.with_banner(b, |b| self.present_history(b)))
+                }));
+            }
+            tabs
+        };
+
+        tab::view(builder, create)
+    }
+}
+
+impl App {
+    /// The run that is shown, the latest run of the current configuration if none is selected.
+    fn displayed_run(&self) -> Option<&TestRunRecord> {
+        match self.selected_run {
+            Some(number) => self.history.get(number),
+            None => self.history.latest_of(&self.request.configuration.name),
+        }
+    }
+
+    /// The source files of the project's workspace, as they are now.
+    fn source_files(&self) -> SourceFiles {
+        let workspace_root = self.request.workspace_root().unwrap_or_else(|e| {
+            warn!("failed to find the workspace root: {}", e);
+            self.request.project_directory.clone()
+        });
+        SourceFiles::new(workspace_root)
+    }
+
+    /// The source files the diagnostics of the run that is shown refer to.
+    ///
+    /// The files are read again only after another run is shown.
+    fn displayed_sources(&self) -> Rc<SourceFiles> {
+        let number = self.displayed_run().map_or(0, |run| run.number);
+        let mut cached = self.displayed_sources.borrow_mut();
+        match &*cached {
+            Some((cached_number, sources)) if *cached_number == number => sources.clone(),
+            _ => {
+                let sources = Rc::new(self.source_files());
+                *cached = Some((number, sources.clone()));
+                sources
+            }
+        }
+    }
+
+    /// The measured benchmarks of the run that is shown.
+    fn displayed_benchmarks(&self) -> Vec<(&str, BenchResult)> {
+        let run = match self.displayed_run() {
+            Some(run) => run,
+            None => return Vec::new(),
+        };
+        run.result
+            .captures()
+            .filter_map(|capture| match capture.result {
+                TestResult::Bench(result) => Some((capture.name.as_str(), result)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The name of the configuration of the run that is shown.
+    fn displayed_configuration(&self) -> &str {
+        match self.displayed_run() {
+            Some(run) => &run.configuration,
+            None => &self.request.configuration.name,
+        }
+    }
+
+    /// The tabs that show the compiler messages and the captures of the displayed run.
+    fn result_tabs(&self, b: &mut ViewBuilder<Msg>) -> Vec<ScopedView<Msg>> {
+        let sources = self.displayed_sources();
+        let diff = self.displayed_diagnostic_diff();
+        let present_message = |b: ViewBuilder<Msg>, cm: &CompilerMessage| {
+            present_changed_diagnostic(b, cm, diff.change(&cm.message), &sources)
+        };
+        match self.displayed_run().map(|r| &r.result) {
             Some(TestRunResult::CompilationFailed(compiler_messages, _e)) => {
-                let partition = compiler_messages
-                    .as_data()
-                    .partition(|c| match c.message.level {
-                        DiagnosticLevel::Error => true,
-                        _ => false,
-                    });
+                let partition = compiler_messages.as_data().partition(is_compiler_error);
 
                 let (errors, rest) = partition.result;
                 let errors = |b: ViewBuilder<Msg>| {
                     errors
                         .as_data()
-                        .map_view(|b, cm| b.present(cm.to_drawing().into()))
+                        .map_view(|b, cm| present_message(b, cm))
                         .reduce(b, Direction::Column)
                 };
 
-                let rest = |b: ViewBuilder<Msg>| {
-                    rest.as_data()
-                        .order_by(compiler_message::diagnostic_level_ordering)
-                        .map_view(|b, cm| b.present(cm.to_drawing().into()))
-                        .reduce(b, Direction::Column)
-                };
+                let rest = |b: ViewBuilder<Msg>| self.present_warnings(b, &rest, &diff, &sources);
 
                 vec![
-                    b.scoped("errors", |b| scroll::view(b, errors)),
-                    b.scoped("warnings", |b| scroll::view(b, rest)),
+                    b.scoped("errors", |b| {
+                        scroll::view(b, |b| self.with_banner(b, errors))
+                    }),
+                    b.scoped("warnings", |b| {
+                        scroll::view(b, |b| self.with_banner(b, rest))
+                    }),
                 ]
             }
 
-            Some(TestRunResult::TestsCaptured(compiler_messages, captures)) => {
-                let partition = compiler_messages
-                    .as_data()
-                    .partition(|c| match c.message.level {
-                        DiagnosticLevel::Error => true,
-                        _ => false,
-                    });
+            Some(TestRunResult::TestsCaptured(compiler_messages, packages)) => {
+                let partition = compiler_messages.as_data().partition(is_compiler_error);
 
                 let (errors, rest) = partition.result;
                 let errors = |b: ViewBuilder<_>| {
                     errors
                         .as_data()
-                        .map_view(|b, cm| b.present(cm.to_drawing().into()))
+                        .map_view(|b, cm| present_message(b, cm))
                         .reduce(b, Direction::Column)
                 };
 
-                let rest = |b: ViewBuilder<_>| {
-                    rest.as_data()
-                        .order_by(compiler_message::diagnostic_level_ordering)
-                        // TODO: support map_drawing and map_presentation?
-                        .map_view(|b, cm| b.present(cm.to_drawing().into()))
-                        .reduce(b, Direction::Column)
-                };
+                let rest = |b: ViewBuilder<_>| self.present_warnings(b, &rest, &diff, &sources);
 
                 let captures = |b: ViewBuilder<_>| {
-                    let captures = captures.0.as_data().map_view(|c, capture| {
-                        let show_contents = !self.collapsed_tests.contains(&capture.name);
-                        capture.present(c, show_contents)
+                    let filter = TestFilter::pattern(&self.filter);
+                    let configuration = self.displayed_configuration();
+                    let timeline = |test: &TestId| self.history.timeline(test, configuration);
+                    let filter_line = Item::new(&self.filter).map(|b, _| self.present_filter(b));
+                    let packages = packages.as_data().map_view(|b, package_captures| {
+                        let show_contents =
+                            !self.collapsed_packages.contains(&package_captures.package);
+                        package_captures.present(
+                            b,
+                            show_contents,
+                            &self.collapsed_tests,
+                            &timeline,
+                            filter.as_ref(),
+                        )
                     });
 
-                    captures.reduce(b, Direction::Column)
+                    filter_line.extend(&packages).reduce(b, Direction::Column)
                 };
 
                 vec![
-                    b.scoped("errors", |b| scroll::view(b, errors)),
-                    b.scoped("warnings", |b| scroll::view(b, rest)),
-                    b.scoped("captures", |b| scroll::view(b, captures)),
+                    b.scoped("errors", |b| {
+                        scroll::view(b, |b| self.with_banner(b, errors))
+                    }),
+                    b.scoped("warnings", |b| {
+                        scroll::view(b, |b| self.with_banner(b, rest))
+                    }),
+                    b.scoped("captures", |b| {
+                        scroll::view(b, |b| self.with_banner(b, captures))
+                    }),
                 ]
             }
-            // TODO: present some state that indicates that no captures where found yet or that tests are
-            // still running?
-            _ => vec![b.scoped("captures", |b| b.present(Presentation::Empty))],
+            // TODO: present some state that indicates that no captures where found yet?
+            _ => vec![b.scoped("captures", |b| {
+                self.with_banner(b, |b| b.present(Presentation::Empty))
+            })],
+        }
+    }
+
+    /// Presents a button that applies all the suggestions of the compiler that can be applied
+    /// automatically, if there are any.
+    fn present_apply_all(&self, mut b: ViewBuilder<Msg>) -> View<Msg> {
+        let count = self.displayed_suggestions().len();
+        if count == 0 {
+            return b.present(Presentation::Empty);
+        }
+        let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+        let line = format!("[ apply all {} suggested fixes ]", count);
+        let mut drawing = Drawing::new();
+        drawing.draw(text(line, font, None), paint().color(0x0000c0.rgb()));
+        b.use_input_processor(|| Tap::new().map(|_| Some(Msg::ApplyAllSuggestions)));
+        b.present(Presentation::from(drawing).in_area())
+    }
+
+    /// Presents the compiler messages that are not errors, the lints of clippy grouped by lint,
+    /// and the messages the previous run reported that are resolved now. Resolved lints are
+    /// presented in the groups of their lints.
+    fn present_warnings(
+        &self,
+        b: ViewBuilder<Msg>,
+        messages: &[CompilerMessage],
+        diff: &DiagnosticDiff,
+        sources: &SourceFiles,
+    ) -> View<Msg> {
+        let shown: Vec<CompilerMessage> = messages
+            .iter()
+            .filter(|cm| !(self.hide_session_warnings && self.predates_session(cm)))
+            .cloned()
+            .collect();
+        let hidden = messages.len() - shown.len();
+        // resolved clippy lints are shown in the groups of their lints.
+        let resolved: Vec<CompilerMessage> = diff
+            .resolved
+            .iter()
+            .filter(|cm| !is_compiler_error(cm) && lint::clippy_lint(cm).is_none())
+            .cloned()
+            .collect();
+
+        let toggle = Item::new(&hidden).map(|b, hidden| self.present_session_toggle(b, *hidden));
+        let apply_all = Item::new(&()).map(|b, _| self.present_apply_all(b));
+        let lints = lint::group_lints(&shown, &diff.resolved);
+        let compiler_messages: Vec<CompilerMessage> = shown
+            .into_iter()
+            .filter(|cm| lint::clippy_lint(cm).is_none())
+            .collect();
+        let compiler_messages = compiler_messages
+            .as_data()
+            .order_by(compiler_message::diagnostic_level_ordering)
+            // TODO: support map_drawing and map_presentation?
+            .map_view(|b, cm| present_changed_diagnostic(b, cm, diff.change(&cm.message), sources));
+        let lints = lints
+            .as_data()
+            .map_view(|b, group| self.present_lint_group(b, group, diff, sources));
+        let resolved = resolved.as_data().map_view(|b, cm| {
+            present_changed_diagnostic(b, cm, Some(DiagnosticChange::Resolved), sources)
+        });
+        toggle
+            .extend(&apply_all)
+            .extend(&compiler_messages)
+            .extend(&lints)
+            .extend(&resolved)
+            .reduce(b, Direction::Column)
+    }
+
+    /// Presents the name, the level, and the number of messages of a clippy lint and, if the
+    /// lint is not hidden, its messages followed by its resolved messages.
+    fn present_lint_group(
+        &self,
+        mut b: ViewBuilder<Msg>,
+        group: &LintGroup,
+        diff: &DiagnosticDiff,
+        sources: &SourceFiles,
+    ) -> View<Msg> {
+        let hidden = self.hidden_lints.contains(&group.lint);
+        let nested = b.scoped(&group.lint, |b| {
+            let header = Item::new(group).map(|mut b, group| {
+                let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+                let resolved = match group.resolved.len() {
+                    0 => String::new(),
+                    resolved => format!(", {} resolved", resolved),
+                };
+                let line = format!(
+                    "{} {}  {} ({}{})",
+                    if hidden { "+" } else { "-" },
+                    group.lint,
+                    compiler_message::level_name(group.level()),
+                    group.messages.len(),
+                    resolved
+                );
+                let mut drawing = Drawing::new();
+                let color = compiler_message::level_color(group.level());
+                drawing.draw(text(line, font, None), paint().color(color));
+                let lint = group.lint.clone();
+                b.use_input_processor(|| {
+                    Tap::new().map(move |_| Some(Msg::ToggleLint(lint.clone())))
+                });
+                b.present(Presentation::from(drawing).in_area())
+            });
+
+            if hidden {
+                return header.reduce(b, ());
+            }
+
+            let messages = group.messages.as_data().map_view(|b, cm| {
+                present_changed_diagnostic(b, cm, diff.change(&cm.message), sources)
+            });
+            let resolved = group.resolved.as_data().map_view(|b, cm| {
+                present_changed_diagnostic(b, cm, Some(DiagnosticChange::Resolved), sources)
+            });
+            header
+                .extend(&messages)
+                .extend(&resolved)
+                .reduce(b, Direction::Column)
+        });
+
+        b.wrapped(nested)
+    }
+
+    /// Presents a button that hides or shows the warnings that were reported before the session
+    /// started, `hidden` is the number of warnings that are hidden.
+    fn present_session_toggle(&self, mut b: ViewBuilder<Msg>, hidden: usize) -> View<Msg> {
+        let font = &Font::new("", font::Style::NORMAL, font::Size::new(16.0));
+        let line = if self.hide_session_warnings {
+            format!("[ show {} warnings from before the session ]", hidden)
+        } else {
+            "[ hide warnings from before the session ]".to_string()
         };
+        let mut drawing = Drawing::new();
+        drawing.draw(text(line, font, None), paint().color(0x0000c0.rgb()));
+        b.use_input_processor(|| Tap::new().map(|_| Some(Msg::ToggleSessionWarnings)));
+        b.present(Presentation::from(drawing).in_area())
+    }
 
-        tab::view(builder, create)
+    /// Presents the filter pattern and how it can be changed.
+    fn present_filter(&self, b: ViewBuilder<Msg>) -> View<Msg> {
+        let font = &Font::new("", font::Style::NORMAL, font::Size::new(16.0));
+        let applied = TestFilter::pattern(&self.filter) == self.request.filter;
+        let line = if self.filter.is_empty() && applied {
+            "type to filter the tests by name or glob pattern".to_string()
+        } else if applied {
+            format!("filter: {}_  (esc clears)", self.filter)
+        } else {
+            format!(
+                "filter: {}_  (enter runs the matching tests only, esc clears)",
+                self.filter
+            )
+        };
+        let mut drawing = Drawing::new();
+        drawing.draw(text(line, font, None), paint().color(0x808080.rgb()));
+        b.present(drawing.into())
+    }
+
+    /// Presents the benchmarks of the run that is shown and charts of their measurements in the
+    /// recorded runs.
+    fn present_benchmarks(&self, b: ViewBuilder<Msg>) -> View<Msg> {
+        let configuration = self.displayed_configuration();
+        self.displayed_benchmarks()
+            .as_data()
+            .map_view(|b, (name, result)| {
+                let font = &Font::new("", font::Style::NORMAL, font::Size::new(16.0));
+                let mut header = Drawing::new();
+                header.draw(text(format!("{}  {}", name, result), font, None), paint());
+                let header = Item::new(&header).map(|b, header| b.present(header.clone().into()));
+                let series = self.history.bench_series(name, configuration);
+                let chart =
+                    Item::new(&series).map(|b, series| b.present(bench_chart(series).into()));
+                header.extend(&chart).reduce(b, Direction::Column)
+            })
+            .reduce(b, Direction::Column)
+    }
+
+    /// Presents the test that is run repeatedly, or the outcomes of the test that was run
+    /// repeatedly last.
+    fn present_stress(&self, b: ViewBuilder<Msg>) -> View<Msg> {
+        match (&self.stress_test, &self.stress_result) {
+            (Some(test), _) => {
+                let font = &Font::new("", font::Style::BOLD, font::Size::new(20.0));
+                let line = format!("running {} {} times", test.name, self.stress_runs);
+                let mut drawing = Drawing::new();
+                drawing.draw(text(line, font, None), paint().color(0x808080.rgb()));
+                b.present(drawing.into())
+            }
+            (None, Some(result)) => result.present(b),
+            (None, None) => b.present(Presentation::Empty),
+        }
+    }
+
+    /// Presents the recorded runs, the latest first. Tapping a run shows its captures.
+    fn present_history(&self, b: ViewBuilder<Msg>) -> View<Msg> {
+        let runs: Vec<&TestRunRecord> = self.history.runs().rev().collect();
+        runs.as_data()
+            .map_view(|mut b, run| {
+                let selected = self.displayed_run().map(|r| r.number) == Some(run.number);
+                let summary = match &run.result {
+                    TestRunResult::CompilationFailed(_, _) => "compilation failed".to_string(),
+                    TestRunResult::TestsCaptured(_, _) => {
+                        let totals = TestTotals::from_captures(run.result.captures());
+                        format!("{} passed, {} failed", totals.passed, totals.failed)
+                    }
+                };
+                let changed_files: Vec<String> = run
+                    .changed_files
+                    .iter()
+                    .map(|f| f.display().to_string())
+                    .collect();
+                let configuration = if self.configurations.len() > 1 {
+                    format!("[{}]  ", run.configuration)
+                } else {
+                    String::new()
+                };
+                let line = format!(
+                    "{} #{}  {}  {}{}  {}",
+                    if selected { ">" } else { " " },
+                    run.number,
+                    run.time_of_day(),
+                    configuration,
+                    summary,
+                    changed_files.join(", ")
+                );
+
+                let style = if selected {
+                    font::Style::BOLD
+                } else {
+                    font::Style::NORMAL
+                };
+                let font = &Font::new("", style, font::Size::new(16.0));
+                let mut drawing = Drawing::new();
+                drawing.draw(text(line, font, None), paint());
+                let presentation = Presentation::from(drawing).in_area();
+                let number = run.number;
+                b.use_input_processor(|| {
+                    Tap::new().map(move |_| Some(Msg::SelectRun(Some(number))))
+                });
+                b.present(presentation)
+            })
+            .reduce(b, Direction::Column)
+    }
+}
+
+/// Whether the message is an error of the compiler, lints that clippy reports as errors are shown
+/// with the warnings.
+fn is_compiler_error(message: &CompilerMessage) -> bool {
+    match message.message.level {
+        DiagnosticLevel::Error => lint::clippy_lint(message).is_none(),
+        _ => false,
     }
 }
 
+/// Presents a compiler message with a badge that shows how it changed since the previous run.
+///
+/// Resolved messages are presented as they were reported, without the current source lines and
+/// without their suggestions.
+fn present_changed_diagnostic(
+    b: ViewBuilder<Msg>,
+    message: &CompilerMessage,
+    change: Option<DiagnosticChange>,
+    sources: &SourceFiles,
+) -> View<Msg> {
+    let badge = Item::new(&change).map(|b, change| b.present(change_badge(*change)));
+    let message = Item::new(message).map(|b, cm| match change {
+        Some(DiagnosticChange::Resolved) => present_resolved_diagnostic(b, &cm.message, sources),
+        _ => present_diagnostic(b, &cm.message, sources),
+    });
+    badge.extend(&message).reduce(b, Direction::Row)
+}
+
+fn change_badge(change: Option<DiagnosticChange>) -> Presentation {
+    let (label, color) = match change {
+        Some(DiagnosticChange::New) => ("new", 0x00a000),
+        Some(DiagnosticChange::Unchanged) => ("unchanged", 0x808080),
+        Some(DiagnosticChange::Resolved) => ("resolved", 0x0000c0),
+        None => return Presentation::Empty,
+    };
+    let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+    let mut drawing = Drawing::new();
+    drawing.draw(
+        text(format!("{}  ", label), font, None),
+        paint().color(color.rgb()),
+    );
+    drawing.into()
+}
+
 /// TestRunner tests.
 ///
 /// These tests are not in the library, because they would interfere with emergent itself.
@@ -190,30 +1123,39 @@ pub mod tests {
     #[test]
     fn run_tests_self() {
         let request = TestRunRequest::new_lib(&env::current_dir().unwrap());
-        if let TestRunResult::TestsCaptured(_, captures) = request
+        let result = request
             .capture_tests(TestEnvironment::from_test_environment())
-            .unwrap()
-        {
-            info!("captures:\n{:?}", captures);
+            .unwrap();
+        if let TestRunResult::TestsCaptured(_, packages) = &result {
+            info!("captures:\n{:?}", packages);
 
-            let captures = captures.0;
+            let captures: Vec<TestCapture> = result.captures().cloned().collect();
 
             assert!(captures.contains(&TestCapture {
                 name: "test_output_capture".into(),
                 result: TestResult::Ok(),
-                output: "CAPTURE_ME\n".into()
+                output: "CAPTURE_ME\n".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
             }));
 
             assert!(captures.contains(&TestCapture {
                 name: "tests::test_in_mod_capture".into(),
                 result: TestResult::Ok(),
-                output: "CAPTURE_ME_IN_MOD\n".into()
+                output: "CAPTURE_ME_IN_MOD\n".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
             }));
 
             assert!(captures.contains(&TestCapture {
                 name: "test_output_capture_multiline".into(),
                 result: TestResult::Ok(),
-                output: "CAPTURE_ME_LINE1\nCAPTURE_ME_LINE2\n".into()
+                output: "CAPTURE_ME_LINE1\nCAPTURE_ME_LINE2\n".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
             }));
         } else {
             panic!("no test results");
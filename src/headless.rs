@@ -0,0 +1,238 @@
+//! Headless test runs.
+//!
+//! Runs the tests of a project once without opening a window and writes a JSON report, the
+//! rasterized drawings of all test captures, a JUnit XML report, and an HTML report to an output
+//! directory.
+
+use crate::skia_renderer;
+use cargo_metadata::CompilerMessage;
+use emergent::libtest::{TestCapture, TestTotals};
+use emergent::report::html::html_report;
+use emergent::report::junit::junit_xml;
+use emergent::skia::text::PrimitiveText;
+use emergent::test_runner::{
+    TestEnvironment, TestRunRequest, TestRunResult, TestTarget, TestVariant,
+};
+use emergent_drawing::snapshot::file_name_of;
+use emergent_ui::DPI;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the report file in the output directory.
+const REPORT_FILE: &str = "report.json";
+/// The name of the JUnit XML report file in the output directory.
+const JUNIT_REPORT_FILE: &str = "report.xml";
+/// The name of the HTML report file in the output directory.
+const HTML_REPORT_FILE: &str = "report.html";
+
+#[derive(Serialize, Debug)]
+struct Report<'a> {
+    project_directory: &'a Path,
+    /// The name of the run configuration.
+    configuration: &'a str,
+    dpi: f64,
+    compiler_messages: &'a [CompilerMessage],
+    /// Set if the compilation failed.
+    compilation_error: Option<String>,
+    packages: Vec<PackageReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct PackageReport<'a> {
+    package: &'a str,
+    totals: TestTotals,
+    targets: Vec<TargetReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct TargetReport<'a> {
+    target: &'a TestTarget,
+    captures: Vec<CaptureReport<'a>>,
+    /// The captures of the other variants of the test environment.
+    variants: Vec<VariantReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct VariantReport<'a> {
+    variant: &'a TestVariant,
+    captures: Vec<CaptureReport<'a>>,
+}
+
+#[derive(Serialize, Debug)]
+struct CaptureReport<'a> {
+    #[serde(flatten)]
+    capture: &'a TestCapture,
+    /// The PNG files of the drawings, relative to the output directory.
+    drawings: Vec<PathBuf>,
+}
+
+/// Runs the tests once in every variant of the environment and writes the results to
+/// `output_directory`.
+///
+/// Returns `true` if the project compiled and all tests passed in all variants.
+pub fn run(
+    request: &TestRunRequest,
+    environment: TestEnvironment,
+    output_directory: &Path,
+) -> Result<bool, failure::Error> {
+    let dpi = environment.dpi;
+    let result = request.capture_tests(environment)?;
+    fs::create_dir_all(output_directory)?;
+
+    let (report, succeeded) = match &result {
+        TestRunResult::CompilationFailed(compiler_messages, e) => {
+            let report = Report {
+                project_directory: &request.project_directory,
+                configuration: &request.configuration.name,
+                dpi: dpi.0,
+                compiler_messages,
+                compilation_error: Some(e.to_string()),
+                packages: Vec::new(),
+            };
+            (report, false)
+        }
+        TestRunResult::TestsCaptured(compiler_messages, packages) => {
+            let mut package_reports = Vec::new();
+            for package_captures in packages {
+                let package = &package_captures.package;
+                let mut target_reports = Vec::new();
+                for target_captures in &package_captures.targets {
+                    let target = &target_captures.target;
+                    let base_name = format!("{}.{}", package, target);
+                    let captures = &target_captures.captures.0;
+                    let reports = capture_reports(&base_name, captures, dpi, output_directory)?;
+
+                    let mut variant_reports = Vec::new();
+                    for variant_captures in &target_captures.variants {
+                        let variant = &variant_captures.variant;
+                        let base_name = format!("{}.{}", base_name, variant);
+                        let captures = &variant_captures.captures.0;
+                        variant_reports.push(VariantReport {
+                            variant,
+                            captures: capture_reports(
+                                &base_name,
+                                captures,
+                                variant.dpi,
+                                output_directory,
+                            )?,
+                        });
+                    }
+
+                    target_reports.push(TargetReport {
+                        target,
+                        captures: reports,
+                        variants: variant_reports,
+                    });
+                }
+                package_reports.push(PackageReport {
+                    package,
+                    totals: package_captures.totals(),
+                    targets: target_reports,
+                })
+            }
+
+            let succeeded = packages.iter().all(|p| p.totals().failed == 0);
+
+            let report = Report {
+                project_directory: &request.project_directory,
+                configuration: &request.configuration.name,
+                dpi: dpi.0,
+                compiler_messages,
+                compilation_error: None,
+                packages: package_reports,
+            };
+            (report, succeeded)
+        }
+    };
+
+    let report_path = output_directory.join(REPORT_FILE);
+    fs::write(&report_path, serde_json::to_string_pretty(&report)?)?;
+    info!("report written to {:?}", report_path);
+
+    let configuration = &request.configuration.name;
+    let junit_path = output_directory.join(JUNIT_REPORT_FILE);
+    fs::write(&junit_path, junit_xml(&result, configuration))?;
+    info!("JUnit report written to {:?}", junit_path);
+
+    let html_path = output_directory.join(HTML_REPORT_FILE);
+    fs::write(
+        &html_path,
+        html_report(&result, configuration, dpi, PrimitiveText::new),
+    )?;
+    info!("HTML report written to {:?}", html_path);
+
+    Ok(succeeded)
+}
+
+/// Writes the drawings of the captures and returns the reports of the captures.
+fn capture_reports<'a>(
+    base_name: &str,
+    captures: &'a [TestCapture],
+    dpi: DPI,
+    output_directory: &Path,
+) -> Result<Vec<CaptureReport<'a>>, failure::Error> {
+    let measure = PrimitiveText::new(dpi);
+    let mut reports = Vec::new();
+    for capture in captures {
+        let base_name = format!("{}.{}", base_name, capture.name);
+        let drawings = write_drawings(&base_name, capture, dpi, &measure, output_directory)?;
+        reports.push(CaptureReport { capture, drawings });
+    }
+    Ok(reports)
+}
+
+/// Rasterizes all the drawings of a capture and returns the names of the files written.
+///
+/// Labelled drawings are named after their labels, unlabelled drawings are numbered. Drawings
+/// whose names would be the same as the name of a previous drawing get the index of their
+/// artifact appended, and a counter if that is taken, too.
+fn write_drawings(
+    base_name: &str,
+    capture: &TestCapture,
+    dpi: DPI,
+    measure: &PrimitiveText,
+    output_directory: &Path,
+) -> Result<Vec<PathBuf>, failure::Error> {
+    let drawings: Vec<_> = capture
+        .artifacts
+        .iter()
+        .filter_map(|a| a.drawing().map(|d| (a.label(), d)))
+        .collect();
+    let base_name = file_name_of(base_name);
+
+    let mut files = Vec::new();
+    let mut used_names = HashSet::new();
+    for (i, (label, drawing)) in drawings.iter().enumerate() {
+        let png = match skia_renderer::rasterize_to_png(drawing, dpi, measure) {
+            Some(png) => png,
+            None => {
+                warn!("drawing {} of {} is empty, skipping", i, capture.name);
+                continue;
+            }
+        };
+
+        let mut name = match label {
+            Some(label) => format!("{}-{}", base_name, file_name_of(label)),
+            None if drawings.len() == 1 => base_name.clone(),
+            None => format!("{}-{}", base_name, i),
+        };
+        if used_names.contains(&name) {
+            name = format!("{}-{}", name, i);
+        }
+        let indexed = name.clone();
+        let mut counter = 2;
+        while used_names.contains(&name) {
+            name = format!("{}-{}", indexed, counter);
+            counter += 1;
+        }
+        let file_name: PathBuf = format!("{}.png", name).into();
+        used_names.insert(name);
+
+        fs::write(output_directory.join(&file_name), png)?;
+        files.push(file_name);
+    }
+
+    Ok(files)
+}
@@ -0,0 +1,64 @@
+//! Line charts of the measurements of benchmarks in a number of runs.
+
+use crate::test_history::BenchSeries;
+use emergent_drawing::functions::{circle, line, paint, rect};
+use emergent_drawing::{scalar, Drawing, DrawingTarget, Paint, Point, RGB};
+
+const CHART_WIDTH: scalar = 240.0;
+const CHART_HEIGHT: scalar = 60.0;
+/// The space between the frame of the chart and its data points.
+const CHART_MARGIN: scalar = 6.0;
+
+/// Draws the medians of the measurements as a line, and their deviations as vertical bars.
+///
+/// The vertical axis spans the range of the measurements and their deviations, so that changes
+/// are visible even if they are small compared to the medians.
+pub fn bench_chart(series: &BenchSeries) -> Drawing {
+    let mut drawing = Drawing::new();
+    let frame = rect((0.0, 0.0), (CHART_WIDTH, CHART_HEIGHT));
+    drawing.draw(frame.clone(), paint().color(0xf8f8f8.rgb()));
+
+    let low = series
+        .iter()
+        .map(|(_, r)| r.median.saturating_sub(r.deviation))
+        .min();
+    let high = series.iter().map(|(_, r)| r.median + r.deviation).max();
+    if let (Some(low), Some(high)) = (low, high) {
+        let range = (high - low).max(1) as scalar;
+        let width = CHART_WIDTH - 2.0 * CHART_MARGIN;
+        let height = CHART_HEIGHT - 2.0 * CHART_MARGIN;
+        let point = |index: usize, ns: u64| -> Point {
+            let x = if series.len() > 1 {
+                index as scalar * width / (series.len() - 1) as scalar
+            } else {
+                width / 2.0
+            };
+            let y = (1.0 - (ns - low) as scalar / range) * height;
+            (CHART_MARGIN + x, CHART_MARGIN + y).into()
+        };
+
+        for (index, (_, result)) in series.iter().enumerate() {
+            let bottom = point(index, result.median.saturating_sub(result.deviation));
+            let top = point(index, result.median + result.deviation);
+            drawing.draw(line(bottom, top), Paint::stroke(0xa0b0e0.rgb()));
+        }
+
+        let medians: Vec<Point> = series
+            .iter()
+            .enumerate()
+            .map(|(index, (_, result))| point(index, result.median))
+            .collect();
+        for segment in medians.windows(2) {
+            drawing.draw(
+                line(segment[0], segment[1]),
+                Paint::stroke(0x0000c0.rgb()).width(2.0),
+            );
+        }
+        for median in medians {
+            drawing.draw(circle(median, 2.5), paint().color(0x0000c0.rgb()));
+        }
+    }
+
+    drawing.draw(frame, Paint::stroke(0x808080.rgb()));
+    drawing
+}
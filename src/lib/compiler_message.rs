@@ -1,10 +1,24 @@
 //! Rendering of compiler messages.
 
-use cargo_metadata::diagnostic::DiagnosticLevel;
+use crate::test_failure::SourceLocation;
+use crate::{suggestion, text_as_lines, Msg};
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel, DiagnosticSpan};
 use cargo_metadata::CompilerMessage;
-use emergent_drawing::{font, functions::*, Drawing, DrawingTarget, Font, Paint, RGB};
+use emergent_drawing::text::Properties;
+use emergent_drawing::{font, functions::*, Color, Drawing, DrawingTarget, Font, Paint, RGB};
+use emergent_presentation::Presentation;
+use emergent_presenter::input_processor::Tap;
+use emergent_presenter::{
+    Data, Direction, IndexAccessible, IndexMappable, InputProcessor, Item, Reducible,
+};
+use emergent_presenter::{View, ViewBuilder};
 use emergent_terminal::{color_schemes, term, text_attributor};
+use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 pub trait ToDrawing {
     fn to_drawing(&self) -> Drawing;
@@ -13,12 +27,9 @@ pub trait ToDrawing {
 struct ANSIString(Vec<u8>);
 
 impl ANSIString {
-    pub fn from_str(str: &str) -> Option<ANSIString> {
-        if str.is_ascii() {
-            Some(ANSIString(str.as_bytes().iter().copied().collect()))
-        } else {
-            None
-        }
+    /// The terminal parser decodes UTF-8, so any string can be converted.
+    pub fn from_str(str: &str) -> ANSIString {
+        ANSIString(str.as_bytes().to_vec())
     }
 }
 
@@ -71,7 +82,7 @@ impl ToDrawing for CompilerMessage {
         let mut drawing = Drawing::new();
         match &self.message.rendered {
             Some(rendered) => {
-                let ansi = ANSIString::from_str(rendered).unwrap();
+                let ansi = ANSIString::from_str(rendered);
                 ansi.to_drawing()
             }
             // TODO: test non-rendered messages (are there any?)
@@ -91,6 +102,513 @@ impl ToDrawing for CompilerMessage {
     }
 }
 
+/// The number of lines of a span that are shown at most, longer spans are elided in the middle.
+const MAX_SPAN_LINES: usize = 4;
+
+/// The number of columns a tab is expanded to.
+const TAB_WIDTH: usize = 4;
+
+/// The source files diagnostics refer to, each file is read once when one of its lines is
+/// needed.
+///
+/// Relative file names are resolved against the root directory of the workspace, cargo reports
+/// them relative to it.
+pub struct SourceFiles {
+    workspace_root: PathBuf,
+    /// The lines of the files read so far, `None` if the file could not be read.
+    files: RefCell<HashMap<String, Option<Vec<String>>>>,
+}
+
+impl SourceFiles {
+    pub fn new(workspace_root: impl Into<PathBuf>) -> Self {
+        SourceFiles {
+            workspace_root: workspace_root.into(),
+            files: RefCell::default(),
+        }
+    }
+
+    /// The path of a file a diagnostic refers to, `None` if there is no such file.
+    pub fn resolve(&self, file_name: &str) -> Option<PathBuf> {
+        Some(self.workspace_root.join(file_name)).filter(|path| path.is_file())
+    }
+
+    /// Returns a line of a source file, lines start at 1.
+    ///
+    /// Returns `None` if the file can not be read, is not UTF-8, or has no such line.
+    fn line(&self, file_name: &str, line: usize) -> Option<String> {
+        let mut files = self.files.borrow_mut();
+        let lines = files.entry(file_name.to_owned()).or_insert_with(|| {
+            let source = fs::read_to_string(self.resolve(file_name)?).ok()?;
+            let lines = text_as_lines(&source).map(|l| l.trim_end_matches('\r').to_owned());
+            Some(lines.collect())
+        });
+        lines.as_ref()?.get(line.checked_sub(1)?).cloned()
+    }
+}
+
+/// Presents a diagnostic like rustc renders it, but from its spans and children instead of its
+/// rendered text.
+///
+/// The snippets show the lines of the source files the spans refer to. If a file can not be
+/// read or changed since, the lines rustc included in the spans are shown instead. Tapping a
+/// location or a line of a span opens it in the editor.
+///
+/// If the diagnostic has suggestions that can be applied automatically, a button below applies
+/// them.
+pub fn present_diagnostic(
+    b: ViewBuilder<Msg>,
+    diagnostic: &Diagnostic,
+    sources: &SourceFiles,
+) -> View<Msg> {
+    let rendered = DiagnosticRenderer::render(diagnostic, sources, true).segments;
+    let segments = present_segments(&rendered);
+
+    let replacements = suggestion::machine_applicable(diagnostic);
+    let apply = Item::new(&replacements).map(|mut b, replacements| {
+        if replacements.is_empty() {
+            return b.present(Presentation::Empty);
+        }
+        let replacements = replacements.clone();
+        let font = &Font::new("", font::Style::BOLD, font::Size::new(12.0));
+        let mut drawing = Drawing::new();
+        drawing.draw(
+            text("[ apply fix ]", font, None),
+            paint().color(0x0000c0.rgb()),
+        );
+        b.use_input_processor(|| {
+            Tap::new().map(move |_| Some(Msg::ApplySuggestions(replacements.clone())))
+        });
+        b.present(Presentation::from(drawing).in_area())
+    });
+
+    segments.extend(&apply).reduce(b, Direction::Column)
+}
+
+/// Presents a diagnostic a previous run reported that is resolved now.
+///
+/// The source files changed since the diagnostic was reported, so the snippets show the lines
+/// rustc included in the spans, and its suggestions can not be applied anymore.
+pub fn present_resolved_diagnostic(
+    b: ViewBuilder<Msg>,
+    diagnostic: &Diagnostic,
+    sources: &SourceFiles,
+) -> View<Msg> {
+    let segments = DiagnosticRenderer::render(diagnostic, sources, false).segments;
+    present_segments(&segments).reduce(b, Direction::Column)
+}
+
+/// The views of the segments of a rendered diagnostic, tapping a segment that refers to a
+/// location opens it in the editor.
+fn present_segments(segments: &[Segment]) -> impl IndexMappable<Msg> + '_ {
+    Data::new(segments).map_view(|mut b, segment| {
+        let presentation = Presentation::from(segment.drawing());
+        match &segment.location {
+            Some(location) => {
+                let location = location.clone();
+                b.use_input_processor(|| {
+                    Tap::new().map(move |_| Some(Msg::OpenLocation(location.clone())))
+                });
+                b.present(presentation.in_area())
+            }
+            None => b.present(presentation),
+        }
+    })
+}
+
+/// Consecutive lines of a rendered diagnostic that refer to the same source location, if any.
+#[derive(Clone, Debug)]
+struct Segment {
+    runs: Vec<(String, Highlight)>,
+    location: Option<SourceLocation>,
+}
+
+impl Segment {
+    fn drawing(&self) -> Drawing {
+        let font = Font::new(
+            "JetBrains Mono",
+            font::Style::default(),
+            font::Size::new(10.0),
+        );
+        let mut block = text_block(&font, None);
+        let last = self.runs.len().saturating_sub(1);
+        for (i, (text, highlight)) in self.runs.iter().enumerate() {
+            // the segments are laid out below each other, so the last line break is dropped.
+            let text = if i == last {
+                text.trim_end_matches('\n')
+            } else {
+                text
+            };
+            block.text(text, highlight.properties());
+        }
+
+        let mut drawing = Drawing::new();
+        drawing.draw(block, Paint::default());
+        drawing
+    }
+}
+
+/// How a run of text of a rendered diagnostic is highlighted.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Highlight {
+    Plain,
+    /// The message of the diagnostic.
+    Message,
+    /// Line numbers, and the separators between them and the source lines.
+    Gutter,
+    /// The name of a level, and the primary spans of a diagnostic of that level.
+    Level(Color),
+    /// Spans that are not primary.
+    Secondary,
+    /// Spans that have a suggested replacement.
+    Suggestion,
+}
+
+impl Highlight {
+    fn properties(self) -> Properties {
+        let color = match self {
+            Highlight::Plain | Highlight::Message => None,
+            Highlight::Gutter | Highlight::Secondary => Some(0x0000c0.rgb()),
+            Highlight::Level(color) => Some(color),
+            Highlight::Suggestion => Some(0x00a000.rgb()),
+        };
+        let mut properties = text_properties();
+        properties.color = color;
+        if self != Highlight::Plain {
+            properties.style = Some(font::Style::BOLD);
+        }
+        properties
+    }
+}
+
+struct DiagnosticRenderer<'a> {
+    sources: &'a SourceFiles,
+    /// Show the lines of the source files, instead of the lines rustc included in the spans.
+    read_sources: bool,
+    /// The width of the line numbers.
+    gutter: usize,
+    segments: Vec<Segment>,
+}
+
+impl<'a> DiagnosticRenderer<'a> {
+    fn render(diagnostic: &Diagnostic, sources: &'a SourceFiles, read_sources: bool) -> Self {
+        let mut renderer = DiagnosticRenderer {
+            sources,
+            read_sources,
+            gutter: max_line(diagnostic).to_string().len(),
+            segments: Vec::new(),
+        };
+        renderer.diagnostic(diagnostic);
+        renderer
+    }
+
+    fn diagnostic(&mut self, diagnostic: &Diagnostic) {
+        let level = Highlight::Level(level_color(&diagnostic.level));
+        let name = level_name(&diagnostic.level);
+        match &diagnostic.code {
+            Some(code) => self.push(format!("{}[{}]", name, code.code), level),
+            None => self.push(name, level),
+        }
+        self.push(": ", Highlight::Message);
+        self.push(&diagnostic.message, Highlight::Message);
+        self.push("\n", Highlight::Plain);
+
+        if let Some(primary) = diagnostic.spans.iter().find(|span| span.is_primary) {
+            self.begin(Some(self.source_location(primary, primary.line_start)));
+            self.push(format!("{}--> ", self.pad()), Highlight::Gutter);
+            self.push(format!("{}\n", position(primary)), Highlight::Plain);
+        }
+        self.snippets(&diagnostic.spans, level, false);
+
+        for child in &diagnostic.children {
+            self.child(child);
+        }
+    }
+
+    /// Renders a note or a help below the snippets of its parent.
+    fn child(&mut self, child: &Diagnostic) {
+        let level = Highlight::Level(level_color(&child.level));
+        self.begin(None);
+        self.push(format!("{} = ", self.pad()), Highlight::Gutter);
+        self.push(format!("{}: ", level_name(&child.level)), level);
+        // continued lines are aligned with the first line of the message.
+        let indent = format!(
+            "\n{}   {}  ",
+            self.pad(),
+            " ".repeat(level_name(&child.level).len())
+        );
+        self.push(child.message.replace('\n', &indent), Highlight::Plain);
+        self.push("\n", Highlight::Plain);
+        self.snippets(&child.spans, level, true);
+
+        for child in &child.children {
+            self.child(child);
+        }
+    }
+
+    /// Renders a snippet for each file the spans refer to.
+    ///
+    /// Every snippet but the first is preceded by its location, the first one only if
+    /// `locate_first` is set.
+    fn snippets(&mut self, spans: &[DiagnosticSpan], level: Highlight, locate_first: bool) {
+        let mut files: Vec<&str> = Vec::new();
+        for span in spans {
+            if !files.contains(&span.file_name.as_str()) {
+                files.push(&span.file_name);
+            }
+        }
+
+        for (i, file) in files.into_iter().enumerate() {
+            let spans: Vec<&DiagnosticSpan> =
+                spans.iter().filter(|span| span.file_name == file).collect();
+            if i > 0 || locate_first {
+                let span = spans
+                    .iter()
+                    .find(|span| span.is_primary)
+                    .unwrap_or(&spans[0]);
+                self.begin(Some(self.source_location(span, span.line_start)));
+                self.push(format!("{}::: ", self.pad()), Highlight::Gutter);
+                self.push(format!("{}\n", position(span)), Highlight::Plain);
+            }
+            self.snippet(&spans, level);
+        }
+    }
+
+    /// Renders the lines of a file the spans refer to, each followed by the markers of the
+    /// spans on that line.
+    fn snippet(&mut self, spans: &[&DiagnosticSpan], level: Highlight) {
+        let lines: BTreeSet<usize> = spans.iter().flat_map(|span| shown_lines(span)).collect();
+
+        self.begin(None);
+        self.push(format!("{} |\n", self.pad()), Highlight::Gutter);
+        let mut previous = None;
+        for line in lines {
+            if let Some(previous) = previous {
+                if line > previous + 1 {
+                    self.begin(None);
+                    self.push("...\n", Highlight::Gutter);
+                }
+            }
+            previous = Some(line);
+
+            let covering: Vec<&DiagnosticSpan> = spans
+                .iter()
+                .copied()
+                .filter(|span| line >= span.line_start && line <= span.line_end)
+                .collect();
+            let span = covering
+                .iter()
+                .find(|span| span.is_primary)
+                .or(covering.first());
+            self.begin(span.map(|span| self.source_location(span, line)));
+
+            let text = self.line_text(spans, line);
+            self.push(format!("{:>1$} | ", line, self.gutter), Highlight::Gutter);
+            self.push(format!("{}\n", expand_tabs(&text)), Highlight::Plain);
+
+            for span in spans {
+                if let Some((start, end)) = marked_columns(span, line, &text) {
+                    self.marker(span, line, start, end, level);
+                }
+            }
+        }
+    }
+
+    /// Renders the marker below the columns of a line a span covers, and on the last line of
+    /// the span its label and suggested replacement.
+    fn marker(
+        &mut self,
+        span: &DiagnosticSpan,
+        line: usize,
+        start: usize,
+        end: usize,
+        level: Highlight,
+    ) {
+        let (marker, highlight) = if span.suggested_replacement.is_some() {
+            ("~", Highlight::Suggestion)
+        } else if span.is_primary {
+            ("^", level)
+        } else {
+            ("-", Highlight::Secondary)
+        };
+        self.begin(Some(self.source_location(span, line)));
+        self.push(format!("{} | ", self.pad()), Highlight::Gutter);
+        self.push(" ".repeat(start), Highlight::Plain);
+        self.push(marker.repeat(end - start), highlight);
+
+        if line == span.line_end {
+            if let Some(label) = &span.label {
+                self.push(format!(" {}", label), highlight);
+            }
+            match span.suggested_replacement.as_ref().map(String::as_str) {
+                Some("") => self.push(" remove this", Highlight::Suggestion),
+                Some(replacement) => self.push(
+                    format!(" replace with `{}`", replacement.escape_debug()),
+                    Highlight::Suggestion,
+                ),
+                None => {}
+            }
+        }
+        self.push("\n", Highlight::Plain);
+    }
+
+    /// The text of a line of the file the spans refer to.
+    ///
+    /// The line of the file is used only if the sources are read and it still matches the text
+    /// the compiler reported for the spans, otherwise the reported text is shown.
+    fn line_text(&self, spans: &[&DiagnosticSpan], line: usize) -> String {
+        let reported = spans
+            .iter()
+            .filter(|span| line >= span.line_start)
+            .find_map(|span| span.text.get(line - span.line_start))
+            .map(|line| line.text.clone());
+        let text = if self.read_sources {
+            self.sources.line(&spans[0].file_name, line)
+        } else {
+            None
+        };
+        match (text, reported) {
+            (Some(text), Some(reported)) if text != reported => reported,
+            (Some(text), _) => text,
+            (None, reported) => reported.unwrap_or_default(),
+        }
+    }
+
+    /// The location of a line of a span that is opened in the editor, the column is the start
+    /// of the span on its first line, and the start of the line otherwise.
+    fn source_location(&self, span: &DiagnosticSpan, line: usize) -> SourceLocation {
+        SourceLocation {
+            file: self
+                .sources
+                .resolve(&span.file_name)
+                .unwrap_or_else(|| span.file_name.clone().into()),
+            line,
+            column: if line == span.line_start {
+                span.column_start
+            } else {
+                1
+            },
+        }
+    }
+
+    fn pad(&self) -> String {
+        " ".repeat(self.gutter)
+    }
+
+    /// Starts a new segment if the following lines refer to another location than the current
+    /// segment.
+    fn begin(&mut self, location: Option<SourceLocation>) {
+        match self.segments.last() {
+            Some(segment) if segment.location == location => {}
+            _ => self.segments.push(Segment {
+                runs: Vec::new(),
+                location,
+            }),
+        }
+    }
+
+    /// Appends text to the last run of the current segment if it is highlighted the same.
+    fn push(&mut self, text: impl AsRef<str>, highlight: Highlight) {
+        let text = text.as_ref();
+        if text.is_empty() {
+            return;
+        }
+        if self.segments.is_empty() {
+            self.begin(None);
+        }
+        let runs = &mut self.segments.last_mut().unwrap().runs;
+        match runs.last_mut() {
+            Some((last, last_highlight)) if *last_highlight == highlight => last.push_str(text),
+            _ => runs.push((text.to_owned(), highlight)),
+        }
+    }
+}
+
+/// The largest line number the spans of a diagnostic and its children refer to.
+fn max_line(diagnostic: &Diagnostic) -> usize {
+    let spans = diagnostic.spans.iter().map(|span| span.line_end);
+    let children = diagnostic.children.iter().map(max_line);
+    spans.chain(children).max().unwrap_or(0)
+}
+
+fn position(span: &DiagnosticSpan) -> String {
+    format!(
+        "{}:{}:{}",
+        span.file_name, span.line_start, span.column_start
+    )
+}
+
+/// The lines of a span that are shown in a snippet.
+fn shown_lines(span: &DiagnosticSpan) -> Vec<usize> {
+    let (start, end) = (span.line_start, span.line_end.max(span.line_start));
+    if end - start < MAX_SPAN_LINES {
+        return (start..=end).collect();
+    }
+    let half = MAX_SPAN_LINES / 2;
+    (start..start + half).chain(end + 1 - half..=end).collect()
+}
+
+/// The display columns of a line a span covers, starting at 0, with tabs expanded.
+///
+/// Columns of spans count characters, not bytes. A span that covers no characters is marked at
+/// its start.
+fn marked_columns(span: &DiagnosticSpan, line: usize, text: &str) -> Option<(usize, usize)> {
+    if line < span.line_start || line > span.line_end {
+        return None;
+    }
+    let start = if line == span.line_start {
+        span.column_start
+    } else {
+        text.chars().take_while(|c| c.is_whitespace()).count() + 1
+    };
+    let end = if line == span.line_end {
+        span.column_end
+    } else {
+        text.chars().count() + 1
+    };
+    let start = display_column(text, start);
+    let end = display_column(text, end).max(start + 1);
+    Some((start, end))
+}
+
+/// The display column of a character column that starts at 1. Columns past the end of the
+/// line are treated as if the line continued with spaces.
+fn display_column(text: &str, column: usize) -> usize {
+    let preceding = column.saturating_sub(1);
+    let in_line: usize = text
+        .chars()
+        .take(preceding)
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum();
+    in_line + preceding.saturating_sub(text.chars().count())
+}
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// The name of the level as the compiler prints it.
+pub fn level_name(level: &DiagnosticLevel) -> &'static str {
+    match level {
+        DiagnosticLevel::Ice => "error: internal compiler error",
+        DiagnosticLevel::Error => "error",
+        DiagnosticLevel::Warning => "warning",
+        DiagnosticLevel::Note => "note",
+        DiagnosticLevel::Help => "help",
+        DiagnosticLevel::Unknown => "unknown",
+    }
+}
+
+/// The color the level is shown in.
+pub fn level_color(level: &DiagnosticLevel) -> Color {
+    match level {
+        DiagnosticLevel::Ice | DiagnosticLevel::Error => 0xc00000.rgb(),
+        DiagnosticLevel::Warning => 0xc07000.rgb(),
+        DiagnosticLevel::Note => 0x00a000.rgb(),
+        DiagnosticLevel::Help => 0x0080a0.rgb(),
+        DiagnosticLevel::Unknown => 0x000000.rgb(),
+    }
+}
+
 /// Orders by diagnostic level, from the most severe level to the least.
 pub fn diagnostic_level_ordering(l: &CompilerMessage, r: &CompilerMessage) -> Ordering {
     diagnostic_level_severity(l)
@@ -112,10 +630,12 @@ fn diagnostic_level_severity(compiler_message: &CompilerMessage) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use crate::compiler_message::ToDrawing;
+    use crate::compiler_message::{DiagnosticRenderer, SourceFiles, ToDrawing};
+    use cargo_metadata::diagnostic::Diagnostic;
     use cargo_metadata::{CompilerMessage, Message};
     use emergent_drawing::Render;
     use std::io::Cursor;
+    use std::{env, fs, process};
 
     #[test]
     fn draw_message_rendered() {
@@ -129,6 +649,56 @@ mod tests {
         msg.to_drawing().render()
     }
 
+    #[test]
+    fn render_snippet_with_utf8_source() {
+        let diagnostic: Diagnostic = serde_json::from_str(UTF8_DIAGNOSTIC).unwrap();
+        let sources = SourceFiles::new("/nonexistent");
+        let renderer = DiagnosticRenderer::render(&diagnostic, &sources, true);
+        let text: String = renderer
+            .segments
+            .iter()
+            .flat_map(|segment| segment.runs.iter().map(|(text, _)| text.as_str()))
+            .collect();
+        assert_eq!(
+            text,
+            "error[E0425]: cannot find value `undefined_x` in this scope\n \
+             --> src/lib.rs:3:22\n  \
+             |\n\
+             3 |     let s = \"h\u{e9}llo\"; undefined_x;\n  \
+             |                      ^^^^^^^^^^^ not found in this scope\n  \
+             = help: a local variable with a similar name exists\n"
+        );
+    }
+
+    #[test]
+    fn render_snippet_of_changed_source_from_reported_text() {
+        let directory = env::temp_dir().join(format!("emergent-sources-test-{}", process::id()));
+        fs::create_dir_all(directory.join("src")).unwrap();
+        fs::write(
+            directory.join("src/lib.rs"),
+            concat!(
+                "fn main() {\n",
+                "    // inserted\n",
+                "    // inserted\n",
+                "    let s = \"h\u{e9}llo\"; undefined_x;\n",
+                "}\n"
+            ),
+        )
+        .unwrap();
+
+        let diagnostic: Diagnostic = serde_json::from_str(UTF8_DIAGNOSTIC).unwrap();
+        let sources = SourceFiles::new(&directory);
+        let renderer = DiagnosticRenderer::render(&diagnostic, &sources, true);
+        fs::remove_dir_all(&directory).unwrap();
+
+        let text: String = renderer
+            .segments
+            .iter()
+            .flat_map(|segment| segment.runs.iter().map(|(text, _)| text.as_str()))
+            .collect();
+        assert!(text.contains("3 |     let s = \"h\u{e9}llo\"; undefined_x;\n"));
+    }
+
     fn compiler_messages() -> Vec<CompilerMessage> {
         let msgs = MSG.to_owned() + MSG2 + ANSIMSG;
         let msgs = cargo_metadata::parse_messages(Cursor::new(msgs));
@@ -146,4 +716,6 @@ mod tests {
     const MSG2: &str = r#"{"reason":"compiler-message","package_id":"emergent 0.1.0 (path+file:///C:/emergent)","target":{"kind":["lib"],"crate_types":["lib"],"name":"emergent","src_path":"C:\\emergent\\src/lib/lib.rs","edition":"2018"},"message":{"message":"aborting due to previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting due to previous error\n\n"}}"#;
 
     const ANSIMSG: &str = r#"{"reason":"compiler-message","package_id":"emergent 0.1.0 (path+file:///C:/emergent)","target":{"kind":["lib"],"crate_types":["lib"],"name":"emergent","src_path":"C:\\emergent\\src/lib/lib.rs","edition":"2018","doctest":true},"message":{"message":"expected one of `!` or `::`, found `fn`","code":null,"level":"error","spans":[{"file_name":"src/lib/lib.rs","byte_start":409,"byte_end":409,"line_start":28,"line_end":28,"column_start":13,"column_end":13,"is_primary":false,"text":[{"text":"    #[test]f","highlight_start":13,"highlight_end":13}],"label":"expected one of `!` or `::` here","suggested_replacement":null,"suggestion_applicability":null,"expansion":null},{"file_name":"src/lib/lib.rs","byte_start":414,"byte_end":416,"line_start":29,"line_end":29,"column_start":5,"column_end":7,"is_primary":true,"text":[{"text":"    fn test_in_mod_capture() {","highlight_start":5,"highlight_end":7}],"label":"unexpected token","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":"\u001b[0m\u001b[1m\u001b[38;5;9merror\u001b[0m\u001b[0m\u001b[1m\u001b[38;5;15m: expected one of `!` or `::`, found `fn`\u001b[0m\n\u001b[0m  \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m--> \u001b[0m\u001b[0msrc/lib/lib.rs:29:5\u001b[0m\n\u001b[0m   \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m|\u001b[0m\n\u001b[0m\u001b[1m\u001b[38;5;14m28\u001b[0m\u001b[0m \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m| \u001b[0m\u001b[0m    #[test]f\u001b[0m\n\u001b[0m   \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m| \u001b[0m\u001b[0m            \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m-\u001b[0m\u001b[0m \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14mexpected one of `!` or `::` here\u001b[0m\n\u001b[0m\u001b[1m\u001b[38;5;14m29\u001b[0m\u001b[0m \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m| \u001b[0m\u001b[0m    fn test_in_mod_capture() {\u001b[0m\n\u001b[0m   \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;14m| \u001b[0m\u001b[0m    \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;9m^^\u001b[0m\u001b[0m \u001b[0m\u001b[0m\u001b[1m\u001b[38;5;9munexpected token\u001b[0m\n\n"}}"#;
+
+    const UTF8_DIAGNOSTIC: &str = r#"{"message":"cannot find value `undefined_x` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","byte_start":70,"byte_end":81,"line_start":3,"line_end":3,"column_start":22,"column_end":33,"is_primary":true,"text":[{"text":"    let s = \"h\u00e9llo\"; undefined_x;","highlight_start":22,"highlight_end":33}],"label":"not found in this scope","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"a local variable with a similar name exists","code":null,"level":"help","spans":[],"children":[],"rendered":null}],"rendered":null}"#;
 }
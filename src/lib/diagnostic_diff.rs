@@ -0,0 +1,142 @@
+//! Changes of the compiler diagnostics between test runs.
+
+use crate::suggestion;
+use cargo_metadata::diagnostic::Diagnostic;
+use cargo_metadata::CompilerMessage;
+use std::collections::HashSet;
+
+/// Identifies a diagnostic across runs by its code, its message, and its primary span.
+///
+/// The primary span is identified by its file and the text it highlights, and not by its
+/// position, so that a diagnostic stays the same when lines above it are inserted or removed.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DiagnosticKey {
+    code: Option<String>,
+    message: String,
+    /// The file name and the highlighted text of the primary span.
+    primary_span: Option<(String, String)>,
+}
+
+impl DiagnosticKey {
+    pub fn of(diagnostic: &Diagnostic) -> Self {
+        let primary_span = diagnostic
+            .spans
+            .iter()
+            .find(|s| s.is_primary)
+            .map(|span| (span.file_name.clone(), suggestion::highlighted_text(span)));
+        DiagnosticKey {
+            code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+            message: diagnostic.message.clone(),
+            primary_span,
+        }
+    }
+
+    /// The keys of the diagnostics of compiler messages.
+    pub fn of_messages(messages: &[CompilerMessage]) -> HashSet<DiagnosticKey> {
+        messages.iter().map(|cm| Self::of(&cm.message)).collect()
+    }
+}
+
+/// How a diagnostic relates to the diagnostics of the previous run.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DiagnosticChange {
+    /// The previous run did not report the diagnostic.
+    New,
+    /// The previous run reported the diagnostic, too.
+    Unchanged,
+    /// The previous run reported the diagnostic, but the current run does not.
+    Resolved,
+}
+
+/// The diagnostics of a run compared with the diagnostics of the previous run.
+#[derive(Default, Debug)]
+pub struct DiagnosticDiff {
+    /// The keys of the diagnostics of the previous run, `None` if there is no previous run.
+    previous: Option<HashSet<DiagnosticKey>>,
+    /// The messages of the previous run that the current run does not report anymore, once per
+    /// key.
+    pub resolved: Vec<CompilerMessage>,
+}
+
+impl DiagnosticDiff {
+    pub fn new(previous: Option<&[CompilerMessage]>, current: &[CompilerMessage]) -> Self {
+        let previous_messages = match previous {
+            Some(previous) => previous,
+            None => return Self::default(),
+        };
+        let current = DiagnosticKey::of_messages(current);
+        let mut resolved_keys = HashSet::new();
+        let resolved = previous_messages
+            .iter()
+            .filter(|cm| {
+                let key = DiagnosticKey::of(&cm.message);
+                !current.contains(&key) && resolved_keys.insert(key)
+            })
+            .cloned()
+            .collect();
+        DiagnosticDiff {
+            previous: Some(DiagnosticKey::of_messages(previous_messages)),
+            resolved,
+        }
+    }
+
+    /// How the diagnostic of the current run changed, `None` if there is no previous run to
+    /// compare it with.
+    pub fn change(&self, diagnostic: &Diagnostic) -> Option<DiagnosticChange> {
+        let previous = self.previous.as_ref()?;
+        Some(if previous.contains(&DiagnosticKey::of(diagnostic)) {
+            DiagnosticChange::Unchanged
+        } else {
+            DiagnosticChange::New
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiagnosticChange, DiagnosticDiff};
+    use cargo_metadata::CompilerMessage;
+
+    fn unused_variable(name: &str, line: usize) -> CompilerMessage {
+        let json = format!(
+            r#"{{"package_id":"emergent 0.1.0 (path+file:///emergent)","target":{{"name":"emergent","kind":["lib"],"crate_types":["lib"],"src_path":"/emergent/src/lib.rs","edition":"2018"}},"message":{{"message":"unused variable: `{name}`","code":{{"code":"unused_variables","explanation":null}},"level":"warning","spans":[{{"file_name":"src/lib.rs","byte_start":0,"byte_end":1,"line_start":{line},"line_end":{line},"column_start":9,"column_end":10,"is_primary":true,"text":[{{"text":"    let {name} = 1;","highlight_start":9,"highlight_end":10}}],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}}],"children":[],"rendered":null}}}}"#,
+            name = name,
+            line = line
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn new_unchanged_and_resolved_diagnostics() {
+        let previous = vec![
+            unused_variable("a", 1),
+            unused_variable("b", 2),
+            unused_variable("c", 3),
+        ];
+        // the message of `b` moved to another line, and is duplicated for a second target.
+        let current = vec![
+            unused_variable("a", 1),
+            unused_variable("b", 3),
+            unused_variable("b", 3),
+            unused_variable("d", 4),
+        ];
+
+        let diff = DiagnosticDiff::new(Some(&previous), &current);
+        let changes: Vec<_> = current.iter().map(|cm| diff.change(&cm.message)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                Some(DiagnosticChange::Unchanged),
+                Some(DiagnosticChange::Unchanged),
+                Some(DiagnosticChange::Unchanged),
+                Some(DiagnosticChange::New)
+            ]
+        );
+        assert_eq!(diff.resolved.len(), 1);
+        assert_eq!(diff.resolved[0].message.spans[0].line_start, 3);
+
+        let first_run = DiagnosticDiff::new(None, &current);
+        assert_eq!(first_run.change(&current[0].message), None);
+        assert!(first_run.resolved.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+//! Opening source locations in an external editor.
+
+use crate::test_failure::SourceLocation;
+use std::process::Command;
+use std::{env, thread};
+
+/// The environment variable that overrides the editor command template.
+pub const EDITOR_VAR: &str = "EMERGENT_EDITOR";
+
+/// The editor command template that is used if none is configured.
+const DEFAULT_TEMPLATE: &str = "code -g {file}:{line}:{col}";
+
+/// A command that opens a source location in an editor.
+///
+/// The command is a template in which `{file}`, `{line}`, and `{col}` are replaced by the
+/// location to open.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EditorCommand {
+    template: String,
+}
+
+impl Default for EditorCommand {
+    fn default() -> Self {
+        Self::new(DEFAULT_TEMPLATE)
+    }
+}
+
+impl EditorCommand {
+    pub fn new(template: impl Into<String>) -> Self {
+        EditorCommand {
+            template: template.into(),
+        }
+    }
+
+    /// Returns the editor command configured by the environment variable `EMERGENT_EDITOR`, or
+    /// the template of the project configuration if the variable is not set, or the default
+    /// command.
+    pub fn configured(project_template: Option<&str>) -> Self {
+        match (env::var(EDITOR_VAR), project_template) {
+            (Ok(template), _) => Self::new(template),
+            (Err(_), Some(template)) => Self::new(template),
+            (Err(_), None) => Self::default(),
+        }
+    }
+
+    /// Returns the program and its arguments to open the location with.
+    pub fn arguments(&self, location: &SourceLocation) -> Vec<String> {
+        let file = location.file.to_string_lossy();
+        self.template
+            .split_whitespace()
+            .map(|arg| {
+                arg.replace("{file}", &file)
+                    .replace("{line}", &location.line.to_string())
+                    .replace("{col}", &location.column.to_string())
+            })
+            .collect()
+    }
+
+    /// Launches the editor without waiting for it to exit.
+    ///
+    /// The editor process is waited for on a separate thread, so that it does not remain a zombie
+    /// after it exited.
+    pub fn open(&self, location: &SourceLocation) -> Result<(), failure::Error> {
+        let arguments = self.arguments(location);
+        let (program, arguments) = match arguments.split_first() {
+            Some(split) => split,
+            None => failure::bail!("the editor command is empty"),
+        };
+        debug!("opening {} with {}", location, program);
+        let mut child = Command::new(program).args(arguments).spawn()?;
+        let program = program.clone();
+        thread::spawn(move || match child.wait() {
+            Ok(status) if !status.success() => warn!("editor {} failed: {}", program, status),
+            Ok(_) => {}
+            Err(e) => warn!("failed to wait for the editor {}: {}", program, e),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EditorCommand;
+    use crate::test_failure::SourceLocation;
+
+    #[test]
+    fn substitute_location() {
+        let location = SourceLocation {
+            file: "src/lib.rs".into(),
+            line: 10,
+            column: 5,
+        };
+        assert_eq!(
+            EditorCommand::default().arguments(&location),
+            vec!["code", "-g", "src/lib.rs:10:5"]
+        );
+    }
+}
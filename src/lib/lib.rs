@@ -3,24 +3,36 @@
 #[macro_use]
 extern crate log;
 
-pub use capture::*;
 pub use frame::*;
 pub use line_breaking::*;
 pub use msg::*;
 use std::thread::JoinHandle;
 
-mod capture;
+pub mod bench_chart;
 pub mod compiler_message;
+pub mod diagnostic_diff;
+pub mod editor;
 mod frame;
 pub mod libtest;
 mod line_breaking;
+pub mod lint;
 mod move_predictor;
 mod msg;
+pub mod project_config;
+pub mod report;
+pub mod suggestion;
 mod test_capture;
+pub mod test_failure;
+pub mod test_filter;
+pub mod test_history;
+pub mod test_progress;
 pub mod test_runner;
+pub mod test_stress;
 pub mod test_watcher;
+pub mod ui_state;
 
 pub mod skia;
+pub mod snapshot;
 mod ui_tests;
 
 mod window_application;
@@ -1,34 +1,95 @@
 // https://github.com/rust-lang/libtest/blob/master/libtest/formatters/json.rs
 
+use crate::snapshot::SnapshotComparison;
+use crate::test_failure::FailureDetails;
+use emergent_drawing::artifact::Artifact;
 use failure::Fail;
+use serde::Serialize;
 use serde_json::Value;
-use std::convert::TryInto;
+use std::convert::TryFrom;
+use std::fmt;
 use std::io;
 use std::io::{BufRead, BufReader, Read};
 
 // TODO: make that Vec private.
 // TODO: Use some kind of a newtype macro here?
-#[derive(Clone, PartialEq, Eq, Default, Debug)]
+#[derive(Clone, PartialEq, Default, Serialize, Debug)]
 pub struct TestCaptures(pub Vec<TestCapture>);
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Debug)]
 pub struct TestCapture {
     pub name: String,
     pub result: TestResult,
+    /// The output of the test that does not contain artifacts.
     pub output: String,
+    /// The artifacts the test emitted, in the order they were emitted.
+    pub artifacts: Vec<Artifact>,
+    /// The comparisons of the drawings with their snapshots.
+    pub snapshots: Vec<SnapshotComparison>,
+    /// Details about why the test failed.
+    pub failure: Option<FailureDetails>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+impl TestCapture {
+    /// Creates a capture and separates the artifacts from the output.
+    pub fn new(name: impl Into<String>, result: TestResult, output: &str) -> Self {
+        let mut text = String::new();
+        let mut artifacts = Vec::new();
+        for line in output.lines() {
+            match Artifact::from_line(line) {
+                Some(artifact) => artifacts.push(artifact),
+                None => {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+            }
+        }
+
+        TestCapture {
+            name: name.into(),
+            result,
+            output: text,
+            artifacts,
+            snapshots: Vec::new(),
+            failure: None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Debug)]
 pub enum TestResult {
     Ok(),
     Failed(Option<ExtraData>),
     Ignored,
     AllowedFail,
-    // TODO: Bench
+    /// The benchmark was run and measured.
+    Bench(BenchResult),
+    /// The test exceeded its time limit and was killed.
     Timeout,
+    /// The test passed, but its drawings differ from their snapshots.
+    SnapshotMismatch,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+/// The measurement of a benchmark.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Debug)]
+pub struct BenchResult {
+    /// The median time of an iteration in nanoseconds.
+    pub median: u64,
+    /// The deviation of the iteration times in nanoseconds.
+    pub deviation: u64,
+    /// The throughput, if the benchmark specifies the bytes it processes in an iteration.
+    pub mib_per_second: Option<u64>,
+}
+
+/// The number of tests by their results.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Serialize, Debug)]
+pub struct TestTotals {
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Debug)]
 pub enum ExtraData {
     Message(String),
     StdOut(String),
@@ -38,17 +99,14 @@ pub enum ExtraData {
 pub enum TestResultsError {
     #[fail(display = "expected suite start event {:?}", _0)]
     ExpectedSuiteStart(Event),
-    #[fail(display = "expected test start at test no {}, got: {:?}", index, event)]
-    ExpectedTestStart { index: usize, event: Event },
+    #[fail(display = "unexpected suite start event {:?}", _0)]
+    UnexpectedSuiteStart(Event),
+    #[fail(display = "unexpected output after the suite finished: {}", _0)]
+    UnexpectedOutput(String),
 }
 
 trait EventReader {
     fn read_next_line(&mut self) -> Result<String, io::Error>;
-
-    fn read_line_as_event(&mut self) -> Result<Event, failure::Error> {
-        let line = self.read_next_line()?;
-        parse_event(&line)
-    }
 }
 
 fn parse_event(line: &str) -> Result<Event, failure::Error> {
@@ -87,6 +145,9 @@ pub enum Event {
     TestFinish {
         name: String,
         result: TestResult,
+        /// The captured output of the test, only available when libtest captures output and
+        /// runs with `--show-output`.
+        stdout: Option<String>,
     },
 }
 
@@ -107,57 +168,271 @@ pub enum EventError {
 }
 
 impl TestCaptures {
+    /// The captures of a suite that was not run: a single ignored capture named
+    /// `CaptureParser::PROCESS_CAPTURE_NAME` with `reason` as its output.
+    pub fn skipped(reason: &str) -> Self {
+        TestCaptures(vec![TestCapture::new(
+            CaptureParser::PROCESS_CAPTURE_NAME,
+            TestResult::Ignored,
+            &format!("{}\n", reason),
+        )])
+    }
+
     /// Parses output lines from a complete libtest / suite test into
     /// a number of test captures.
 
     pub fn from_output<R: Read>(reader: R) -> Result<Self, failure::Error> {
         let mut reader = BufReader::new(reader);
+        let mut parser = CaptureParser::new();
+        while !parser.is_complete() {
+            parser.parse_line(reader.read_next_line()?)?;
+        }
+        parser.finish()
+    }
+}
 
-        let num_tests = {
-            match reader.read_line_as_event()? {
-                Event::SuiteStart { test_count } => test_count,
+/// An incremental parser that converts lines of libtest's JSON output into test captures.
+///
+/// Tests may run in parallel, so their events may interleave. Output lines that are not events
+/// are attributed to the running test if there is exactly one. The output of tests that run in
+/// parallel is expected to be captured by libtest and reported with their results.
+#[derive(Debug)]
+pub struct CaptureParser {
+    state: ParserState,
+    captures: Vec<TestCapture>,
+}
+
+/// How a line of output changed the progress of a suite.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SuiteProgress {
+    SuiteStarted {
+        test_count: usize,
+    },
+    TestStarted(String),
+    /// A test finished, its capture is the last of the parser's captures.
+    TestFinished,
+    SuiteFinished,
+    /// A line of output that is not an event.
+    Output,
+}
+
+#[derive(Debug)]
+enum ParserState {
+    SuiteStart,
+    Running {
+        /// The number of tests the suite runs.
+        test_count: usize,
+        /// The tests that are running and their output in the order they were started.
+        running: Vec<(String, Vec<String>)>,
+    },
+    Complete,
+}
+
+impl Default for CaptureParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CaptureParser {
+    /// The name of the capture that is recorded when the test process terminated while no test
+    /// was running.
+    pub const PROCESS_CAPTURE_NAME: &'static str = "(test process)";
+
+    /// The name of the capture that is recorded for the tests of an interrupted suite that were
+    /// not started.
+    pub const NOT_RUN_CAPTURE_NAME: &'static str = "(tests not run)";
+
+    pub fn new() -> Self {
+        CaptureParser {
+            state: ParserState::SuiteStart,
+            captures: Vec::new(),
+        }
+    }
+
+    /// Parses a line of output, including its line terminator, and returns how it changed the
+    /// progress of the suite.
+    pub fn parse_line(&mut self, line: String) -> Result<SuiteProgress, failure::Error> {
+        let progress = match &mut self.state {
+            ParserState::SuiteStart => match parse_event(&line)? {
+                Event::SuiteStart { test_count } => {
+                    self.state = ParserState::Running {
+                        test_count,
+                        running: Vec::new(),
+                    };
+                    SuiteProgress::SuiteStarted { test_count }
+                }
                 e => return Err(TestResultsError::ExpectedSuiteStart(e).into()),
+            },
+            ParserState::Running { running, .. } => match parse_event(&line) {
+                Ok(Event::TestStart { name }) => {
+                    running.push((name.clone(), Vec::new()));
+                    SuiteProgress::TestStarted(name)
+                }
+                Ok(Event::TestFinish {
+                    name,
+                    result,
+                    stdout,
+                }) => {
+                    let mut output = match running.iter().position(|(n, _)| *n == name) {
+                        Some(index) => running.remove(index).1.concat(),
+                        None => String::new(),
+                    };
+                    if let Some(stdout) = stdout {
+                        output.push_str(&stdout);
+                    }
+                    self.captures.push(TestCapture::new(name, result, &output));
+                    SuiteProgress::TestFinished
+                }
+                Ok(Event::SuiteFinish { .. }) => {
+                    self.state = ParserState::Complete;
+                    SuiteProgress::SuiteFinished
+                }
+                Ok(e @ Event::SuiteStart { .. }) => {
+                    return Err(TestResultsError::UnexpectedSuiteStart(e).into())
+                }
+                Err(_) => {
+                    match running.as_mut_slice() {
+                        [(_, output)] => output.push(line),
+                        _ => debug!("unattributed test output: {}", line.trim_end()),
+                    };
+                    SuiteProgress::Output
+                }
+            },
+            ParserState::Complete => {
+                return Err(TestResultsError::UnexpectedOutput(line).into());
             }
         };
 
-        let mut captures = Vec::new();
+        Ok(progress)
+    }
 
-        for i in 0..num_tests {
-            let test_name = match reader.read_line_as_event()? {
-                Event::TestStart { name } => name,
-                e => return Err(TestResultsError::ExpectedTestStart { index: i, event: e }.into()),
-            };
+    /// Returns `true` if the suite has finished.
+    pub fn is_complete(&self) -> bool {
+        match self.state {
+            ParserState::Complete => true,
+            _ => false,
+        }
+    }
 
-            let output = &mut Vec::new();
+    /// The captures of the tests that finished so far.
+    pub fn captures(&self) -> &[TestCapture] {
+        &self.captures
+    }
 
-            let result = loop {
-                let line = reader.read_next_line()?;
-                match parse_event(&line) {
-                    Ok(Event::TestFinish {
-                        ref name,
-                        ref result,
-                    }) if name == &test_name => {
-                        break result.clone();
-                    }
-                    _ => {
-                        output.push(line);
-                    }
-                }
-            };
+    /// Returns the captures of a finished suite.
+    pub fn finish(self) -> Result<TestCaptures, failure::Error> {
+        if !self.is_complete() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(TestCaptures(self.captures))
+    }
 
-            captures.push(TestCapture {
-                name: test_name,
-                result,
-                output: output.concat(),
-            })
+    /// Returns the captures of a suite that was interrupted.
+    ///
+    /// The tests that were running are recorded as failed with `reason` as the failure message.
+    /// If no test was running, a failed capture named `PROCESS_CAPTURE_NAME` is recorded instead.
+    /// The tests that were not started yet are recorded in a failed capture named
+    /// `NOT_RUN_CAPTURE_NAME`.
+    pub fn abort(mut self, reason: &str) -> TestCaptures {
+        let result = TestResult::Failed(Some(ExtraData::Message(reason.into())));
+        let not_started = self.not_started();
+        match self.state {
+            ParserState::Complete => {}
+            ParserState::Running { running, .. } if !running.is_empty() => self.captures.extend(
+                running
+                    .into_iter()
+                    .map(|(name, output)| TestCapture::new(name, result.clone(), &output.concat())),
+            ),
+            _ => self
+                .captures
+                .push(TestCapture::new(Self::PROCESS_CAPTURE_NAME, result, "")),
         }
+        self.captures
+            .extend(Self::not_run_capture(not_started, reason));
+        TestCaptures(self.captures)
+    }
 
-        match reader.read_line_as_event()? {
-            Event::SuiteFinish { .. } => {}
-            e => return Err(TestResultsError::ExpectedSuiteStart(e).into()),
+    /// Returns the captures of a suite that was killed because tests exceeded their time limit.
+    ///
+    /// The running tests named in `expired` get the result `TestResult::Timeout` and keep the
+    /// output they produced so far, followed by `reason`. Other running tests are recorded as
+    /// failed, because they were interrupted. If no test was running, a capture named
+    /// `PROCESS_CAPTURE_NAME` is recorded as timed out instead. The tests that were not started
+    /// yet are recorded in a failed capture named `NOT_RUN_CAPTURE_NAME`.
+    pub fn time_out(mut self, expired: &[String], reason: &str) -> TestCaptures {
+        let not_started = self.not_started();
+        match self.state {
+            ParserState::Complete => {}
+            ParserState::Running { running, .. } if !running.is_empty() => {
+                self.captures
+                    .extend(running.into_iter().map(|(name, mut output)| {
+                        if expired.contains(&name) {
+                            output.push(format!("{}\n", reason));
+                            TestCapture::new(name, TestResult::Timeout, &output.concat())
+                        } else {
+                            let message = "interrupted, because other tests timed out".into();
+                            let result = TestResult::Failed(Some(ExtraData::Message(message)));
+                            TestCapture::new(name, result, &output.concat())
+                        }
+                    }))
+            }
+            _ => self.captures.push(TestCapture::new(
+                Self::PROCESS_CAPTURE_NAME,
+                TestResult::Timeout,
+                &format!("{}\n", reason),
+            )),
         }
+        let not_run = Self::not_run_capture(not_started, "other tests timed out");
+        self.captures.extend(not_run);
+        TestCaptures(self.captures)
+    }
 
-        Ok(TestCaptures(captures))
+    /// The number of tests of the suite that were not started yet.
+    fn not_started(&self) -> usize {
+        match &self.state {
+            ParserState::Running {
+                test_count,
+                running,
+            } => test_count.saturating_sub(self.captures.len() + running.len()),
+            _ => 0,
+        }
+    }
+
+    /// The capture that records the tests of an interrupted suite that were not started, so
+    /// that they do not vanish from the results, `None` if all tests were started.
+    fn not_run_capture(not_started: usize, reason: &str) -> Option<TestCapture> {
+        if not_started == 0 {
+            return None;
+        }
+        let message = format!(
+            "{} tests were not run, because the suite was interrupted: {}",
+            not_started, reason
+        );
+        Some(TestCapture::new(
+            Self::NOT_RUN_CAPTURE_NAME,
+            TestResult::Failed(Some(ExtraData::Message(message))),
+            "",
+        ))
+    }
+}
+
+impl TestTotals {
+    pub fn from_captures<'a>(captures: impl IntoIterator<Item = &'a TestCapture>) -> Self {
+        captures
+            .into_iter()
+            .fold(TestTotals::default(), |mut totals, capture| {
+                match capture.result {
+                    TestResult::Ok() | TestResult::AllowedFail | TestResult::Bench(_) => {
+                        totals.passed += 1
+                    }
+                    TestResult::Failed(_) | TestResult::Timeout | TestResult::SnapshotMismatch => {
+                        totals.failed += 1
+                    }
+                    TestResult::Ignored => totals.ignored += 1,
+                }
+                totals
+            })
     }
 }
 
@@ -171,13 +446,18 @@ impl Event {
                     .ok_or_else(|| EventError::GettingProperty(property.to_owned()))
             };
 
-            let get_usize = |property: &str| {
+            let get_u64 = |property: &str| {
                 m.get(property)
                     .and_then(|v| v.as_u64())
-                    .and_then(|v| v.try_into().ok())
                     .ok_or_else(|| EventError::GettingProperty(property.to_owned()))
             };
 
+            let get_usize = |property: &str| {
+                get_u64(property).and_then(|v| {
+                    usize::try_from(v).map_err(|_| EventError::GettingProperty(property.to_owned()))
+                })
+            };
+
             let run_finish = |r| {
                 Ok(Event::SuiteFinish {
                     result: r,
@@ -194,11 +474,20 @@ impl Event {
                 Ok(Event::TestFinish {
                     name: get_str("name")?.into(),
                     result: r,
+                    stdout: m.get("stdout").and_then(|v| v.as_str()).map(String::from),
                 })
             };
 
-            let event = get_str("event")?;
             let ty = get_str("type")?;
+            // the measurements of benchmarks are reported without an event.
+            if ty == "bench" {
+                return test_finish(TestResult::Bench(BenchResult {
+                    median: get_u64("median")?,
+                    deviation: get_u64("deviation")?,
+                    mib_per_second: m.get("mib_per_second").and_then(|v| v.as_u64()),
+                }));
+            }
+            let event = get_str("event")?;
 
             match (ty, event) {
                 ("suite", "started") => Ok(Event::SuiteStart {
@@ -236,6 +525,35 @@ impl Event {
     }
 }
 
+impl fmt::Display for BenchResult {
+    /// Formats the result like libtest, for example `1,234 ns/iter (+/- 56)`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ns/iter (+/- {})",
+            with_separators(self.median),
+            with_separators(self.deviation)
+        )?;
+        if let Some(mib_per_second) = self.mib_per_second {
+            write!(f, " = {} MB/s", mib_per_second)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a number with commas that separate the thousands.
+fn with_separators(n: u64) -> String {
+    let digits = n.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i != 0 && (digits.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
 #[cfg(test)]
 fn to_event(json: &str) -> Event {
     Event::from_json(&serde_json::from_str(json).unwrap()).unwrap()
@@ -282,7 +600,8 @@ fn parse_test_failed() {
         to_event(r#"{ "type": "test", "name": "test_name", "event": "failed" }"#),
         Event::TestFinish {
             result: TestResult::Failed(None),
-            name: "test_name".to_string()
+            name: "test_name".to_string(),
+            stdout: None
         }
     );
 }
@@ -293,7 +612,157 @@ fn parse_test_ok() {
         to_event(r#"{ "type": "test", "name": "test_name", "event": "ok" }"#),
         Event::TestFinish {
             result: TestResult::Ok(),
-            name: "test_name".to_string()
+            name: "test_name".to_string(),
+            stdout: None
+        }
+    );
+}
+
+#[test]
+fn parse_bench() {
+    let event = to_event(
+        r#"{ "type": "bench", "name": "bench_name", "median": 1234567, "deviation": 89 }"#,
+    );
+    let result = BenchResult {
+        median: 1_234_567,
+        deviation: 89,
+        mib_per_second: None,
+    };
+    assert_eq!(
+        event,
+        Event::TestFinish {
+            result: TestResult::Bench(result),
+            name: "bench_name".to_string(),
+            stdout: None
         }
     );
+    assert_eq!(result.to_string(), "1,234,567 ns/iter (+/- 89)");
+}
+
+#[test]
+fn parse_captures_of_aborted_suite() {
+    let mut parser = CaptureParser::new();
+    let lines = [
+        r#"{ "type": "suite", "event": "started", "test_count": 2 }"#,
+        r#"{ "type": "test", "event": "started", "name": "first" }"#,
+        r#"{ "type": "test", "name": "first", "event": "ok" }"#,
+        r#"{ "type": "test", "event": "started", "name": "second" }"#,
+        "OUTPUT BEFORE ABORT",
+    ];
+    for line in lines.iter() {
+        parser.parse_line(format!("{}\n", line)).unwrap();
+    }
+    assert!(!parser.is_complete());
+
+    let captures = parser.abort("aborted");
+    assert_eq!(
+        captures.0,
+        vec![
+            TestCapture {
+                name: "first".into(),
+                result: TestResult::Ok(),
+                output: "".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
+            },
+            TestCapture {
+                name: "second".into(),
+                result: TestResult::Failed(Some(ExtraData::Message("aborted".into()))),
+                output: "OUTPUT BEFORE ABORT\n".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
+            }
+        ]
+    );
+}
+
+#[test]
+fn parse_captures_of_interleaved_tests() {
+    let mut parser = CaptureParser::new();
+    let lines = [
+        r#"{ "type": "suite", "event": "started", "test_count": 2 }"#,
+        r#"{ "type": "test", "event": "started", "name": "first" }"#,
+        r#"{ "type": "test", "event": "started", "name": "second" }"#,
+        r#"{ "type": "test", "name": "second", "event": "ok", "stdout": "SECOND\n" }"#,
+        r#"{ "type": "test", "name": "first", "event": "failed", "stdout": "FIRST\n" }"#,
+        r#"{ "type": "suite", "event": "failed", "passed": 1, "failed": 1, "allowed_fail": 0, "ignored": 0, "measured": 0, "filtered_out": 0 }"#,
+    ];
+    for line in lines.iter() {
+        parser.parse_line(format!("{}\n", line)).unwrap();
+    }
+
+    let captures = parser.finish().unwrap();
+    assert_eq!(
+        captures.0,
+        vec![
+            TestCapture {
+                name: "second".into(),
+                result: TestResult::Ok(),
+                output: "SECOND\n".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
+            },
+            TestCapture {
+                name: "first".into(),
+                result: TestResult::Failed(None),
+                output: "FIRST\n".into(),
+                artifacts: Vec::new(),
+                snapshots: Vec::new(),
+                failure: None
+            }
+        ]
+    );
+}
+
+#[test]
+fn parse_captures_of_timed_out_suite() {
+    let mut parser = CaptureParser::new();
+    let lines = [
+        r#"{ "type": "suite", "event": "started", "test_count": 2 }"#,
+        r#"{ "type": "test", "event": "started", "name": "first" }"#,
+        r#"{ "type": "test", "event": "started", "name": "second" }"#,
+    ];
+    for line in lines.iter() {
+        parser.parse_line(format!("{}\n", line)).unwrap();
+    }
+
+    let captures = parser.time_out(&["first".into()], "timed out");
+    assert_eq!(captures.0[0].name, "first");
+    assert_eq!(captures.0[0].result, TestResult::Timeout);
+    assert_eq!(captures.0[0].output, "timed out\n");
+    assert_eq!(captures.0[1].name, "second");
+    match &captures.0[1].result {
+        TestResult::Failed(Some(ExtraData::Message(_))) => {}
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn parse_captures_of_suite_timed_out_before_all_tests_started() {
+    let mut parser = CaptureParser::new();
+    let lines = [
+        r#"{ "type": "suite", "event": "started", "test_count": 4 }"#,
+        r#"{ "type": "test", "event": "started", "name": "first" }"#,
+        r#"{ "type": "test", "name": "first", "event": "ok" }"#,
+        r#"{ "type": "test", "event": "started", "name": "second" }"#,
+    ];
+    for line in lines.iter() {
+        parser.parse_line(format!("{}\n", line)).unwrap();
+    }
+
+    let captures = parser.time_out(&["second".into()], "timed out");
+    let names: Vec<&str> = captures.0.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(
+        names,
+        vec!["first", "second", CaptureParser::NOT_RUN_CAPTURE_NAME]
+    );
+    match &captures.0[2].result {
+        TestResult::Failed(Some(ExtraData::Message(message))) => {
+            assert!(message.starts_with("2 tests were not run"))
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
 }
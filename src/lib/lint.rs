@@ -0,0 +1,146 @@
+//! Grouping of the lints that clippy reports.
+
+use crate::compiler_message::diagnostic_level_ordering;
+use cargo_metadata::diagnostic::DiagnosticLevel;
+use cargo_metadata::CompilerMessage;
+use std::collections::BTreeMap;
+
+/// The messages of a clippy lint.
+#[derive(Clone, Debug)]
+pub struct LintGroup {
+    /// The name of the lint, for example `clippy::needless_return`.
+    pub lint: String,
+    /// The messages, the most severe first.
+    pub messages: Vec<CompilerMessage>,
+    /// The messages the previous run reported that are resolved now.
+    pub resolved: Vec<CompilerMessage>,
+}
+
+impl LintGroup {
+    /// The most severe level the lint is reported with, which depends on the level the lint is
+    /// set to. If all messages of the lint are resolved, the level they were reported with.
+    pub fn level(&self) -> &DiagnosticLevel {
+        &self.first().message.level
+    }
+
+    /// The most severe message, or the most severe resolved message if there are no others.
+    fn first(&self) -> &CompilerMessage {
+        self.messages
+            .first()
+            .or_else(|| self.resolved.first())
+            .expect("internal error: lint group without messages")
+    }
+}
+
+/// The name of the clippy lint that produced the message, `None` if the message is not a clippy
+/// lint.
+pub fn clippy_lint(message: &CompilerMessage) -> Option<&str> {
+    let code = message.message.code.as_ref()?;
+    if code.code.starts_with("clippy::") {
+        Some(&code.code)
+    } else {
+        None
+    }
+}
+
+/// Groups the clippy lints of the messages and of the resolved messages by their names, the
+/// groups with the most severe level first, and then ordered by name. Messages that are not
+/// clippy lints are ignored.
+pub fn group_lints(messages: &[CompilerMessage], resolved: &[CompilerMessage]) -> Vec<LintGroup> {
+    let mut lints: BTreeMap<&str, (Vec<CompilerMessage>, Vec<CompilerMessage>)> = BTreeMap::new();
+    for message in messages {
+        if let Some(lint) = clippy_lint(message) {
+            lints.entry(lint).or_default().0.push(message.clone());
+        }
+    }
+    for message in resolved {
+        if let Some(lint) = clippy_lint(message) {
+            lints.entry(lint).or_default().1.push(message.clone());
+        }
+    }
+
+    let mut groups: Vec<LintGroup> = lints
+        .into_iter()
+        .map(|(lint, (mut messages, mut resolved))| {
+            messages.sort_by(diagnostic_level_ordering);
+            resolved.sort_by(diagnostic_level_ordering);
+            LintGroup {
+                lint: lint.to_string(),
+                messages,
+                resolved,
+            }
+        })
+        .collect();
+    // the sort is stable, so groups of the same level stay ordered by name.
+    groups.sort_by(|l, r| diagnostic_level_ordering(l.first(), r.first()));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clippy_lint, group_lints};
+    use cargo_metadata::diagnostic::DiagnosticLevel;
+    use cargo_metadata::CompilerMessage;
+
+    fn message(code: &str, level: &str, line: usize) -> CompilerMessage {
+        let json = format!(
+            r#"{{"package_id":"emergent 0.1.0 (path+file:///emergent)","target":{{"name":"emergent","kind":["lib"],"crate_types":["lib"],"src_path":"/emergent/src/lib.rs","edition":"2018"}},"message":{{"message":"{code}","code":{{"code":"{code}","explanation":null}},"level":"{level}","spans":[{{"file_name":"src/lib.rs","byte_start":0,"byte_end":1,"line_start":{line},"line_end":{line},"column_start":1,"column_end":2,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}}],"children":[],"rendered":null}}}}"#,
+            code = code,
+            level = level,
+            line = line
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn group_lints_by_name_and_severity() {
+        let messages = vec![
+            message("unused_variables", "warning", 1),
+            message("clippy::needless_return", "warning", 2),
+            message("clippy::redundant_clone", "warning", 3),
+            message("clippy::needless_return", "warning", 4),
+            message("clippy::eq_op", "error", 5),
+        ];
+        assert_eq!(clippy_lint(&messages[0]), None);
+
+        let groups = group_lints(&messages, &[]);
+        let summary: Vec<(&str, &DiagnosticLevel, usize)> = groups
+            .iter()
+            .map(|g| (g.lint.as_str(), g.level(), g.messages.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("clippy::eq_op", &DiagnosticLevel::Error, 1),
+                ("clippy::needless_return", &DiagnosticLevel::Warning, 2),
+                ("clippy::redundant_clone", &DiagnosticLevel::Warning, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolved_lints_are_grouped_with_their_lint() {
+        let messages = vec![message("clippy::needless_return", "warning", 1)];
+        let resolved = vec![
+            message("unused_variables", "warning", 2),
+            message("clippy::needless_return", "warning", 3),
+            message("clippy::eq_op", "error", 4),
+        ];
+
+        let groups = group_lints(&messages, &resolved);
+        let summary: Vec<(&str, &DiagnosticLevel, usize, usize)> = groups
+            .iter()
+            .map(|g| {
+                let (messages, resolved) = (g.messages.len(), g.resolved.len());
+                (g.lint.as_str(), g.level(), messages, resolved)
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("clippy::eq_op", &DiagnosticLevel::Error, 0, 1),
+                ("clippy::needless_return", &DiagnosticLevel::Warning, 1, 1),
+            ]
+        );
+    }
+}
@@ -1,14 +1,61 @@
-use crate::test_runner::TestEnvironment;
+use crate::suggestion::Replacement;
+use crate::test_failure::SourceLocation;
+use crate::test_runner::{TestId, TestRunResult};
+use crate::test_stress::StressResult;
 use crate::test_watcher;
+use emergent_ui::DPI;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Msg {
     #[serde(skip)]
     WatcherNotification(Result<test_watcher::Notification, failure::Error>),
-    ToggleTestcase {
+    /// Collapse or expand the capture of a test.
+    ToggleTestcase(TestId),
+    TogglePackage {
         name: String,
     },
+    /// Accept the current drawings of a test as its snapshots.
+    AcceptSnapshots {
+        /// The path of the first snapshot of the test.
+        path: PathBuf,
+    },
+    /// Open a source location in the editor.
+    OpenLocation(SourceLocation),
+    /// Apply suggested replacements of the compiler to the source files.
+    ApplySuggestions(Vec<Replacement>),
+    /// Apply all suggestions of the compiler messages of the run that is shown that can be
+    /// applied automatically.
+    ApplyAllSuggestions,
+    /// Show or hide the warnings that were reported before the session started.
+    ToggleSessionWarnings,
+    /// Show or hide the messages of the named clippy lint.
+    ToggleLint(String),
+    /// Restart the test watcher.
+    RestartWatcher,
+    /// Show the captures of the run with the given number, or of the latest run if `None`.
+    SelectRun(Option<usize>),
+    /// Run the tests with the named run configuration and show its results.
+    SelectConfiguration(String),
+    /// Show only the tests with names that match the filter pattern.
+    SetFilter(String),
+    /// Run only the tests that match the filter pattern.
+    ApplyFilter,
+    /// Show and run all tests.
+    ClearFilter,
+    /// Run a single test and merge its capture into the run that is shown.
+    RunTest(TestId),
+    #[serde(skip)]
+    TestRunCompleted {
+        /// The number of the run the capture is merged into.
+        run: usize,
+        result: Result<TestRunResult, failure::Error>,
+    },
+    /// Run a single test repeatedly and show whether its outcomes differ between the runs.
+    StressTest(TestId),
     #[serde(skip)]
-    RerunTestcases(TestEnvironment),
+    StressTestCompleted(Result<StressResult, failure::Error>),
+    /// The DPI of the window changed, rerun the tests with the new DPI.
+    DpiChanged(DPI),
 }
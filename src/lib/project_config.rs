@@ -0,0 +1,187 @@
+//! The configuration of a project that is loaded from a file in the project directory.
+
+use crate::test_runner::VariantMatrix;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The name of the configuration file in the project directory.
+pub const PROJECT_CONFIG_FILE: &str = "emergent.json";
+
+/// The name of the run configuration that is used if the project does not define any.
+pub const DEFAULT_CONFIGURATION: &str = "default";
+
+/// The configuration of a project.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct ProjectConfig {
+    /// The named configurations the tests can be run with.
+    pub configurations: Vec<RunConfiguration>,
+    /// The variants of the test environment the tests are run in.
+    pub matrix: VariantMatrix,
+    /// The command template that opens source locations in an editor, for example
+    /// `code -g {file}:{line}:{col}`.
+    pub editor: Option<String>,
+}
+
+/// How the tests are compiled and run.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct RunConfiguration {
+    pub name: String,
+    /// The features to activate, passed to cargo with `--features`.
+    pub features: Vec<String>,
+    pub no_default_features: bool,
+    /// Compile the tests with the release profile.
+    pub release: bool,
+    /// Environment variables that are set when the tests are compiled and run.
+    pub env: BTreeMap<String, String>,
+    /// Arguments that are passed to libtest in addition to the ones of the testrunner.
+    pub libtest_args: Vec<String>,
+    /// Run clippy for the tested packages after the tests compiled and show its lints with the
+    /// warnings.
+    pub clippy: bool,
+}
+
+impl Default for RunConfiguration {
+    fn default() -> Self {
+        RunConfiguration {
+            name: DEFAULT_CONFIGURATION.into(),
+            features: Vec::new(),
+            no_default_features: false,
+            release: false,
+            env: BTreeMap::new(),
+            libtest_args: Vec::new(),
+            clippy: false,
+        }
+    }
+}
+
+impl ProjectConfig {
+    /// Loads the configuration from the file `emergent.json` in the project directory.
+    ///
+    /// Returns the default configuration if there is no such file.
+    pub fn load(project_directory: &Path) -> Result<ProjectConfig, failure::Error> {
+        let path = project_directory.join(PROJECT_CONFIG_FILE);
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ProjectConfig::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let config: ProjectConfig = serde_json::from_str(&json)
+            .map_err(|e| failure::format_err!("failed to parse {:?}: {}", path, e))?;
+        config.check_names()?;
+        Ok(config)
+    }
+
+    /// Checks that the run configurations have distinct names, so that each can be selected.
+    ///
+    /// Configurations without a name are named `default`, so multiple configurations must be
+    /// named explicitly.
+    pub fn check_names(&self) -> Result<(), failure::Error> {
+        for (i, configuration) in self.configurations.iter().enumerate() {
+            if self.configurations[..i]
+                .iter()
+                .any(|c| c.name == configuration.name)
+            {
+                failure::bail!(
+                    "more than one run configuration is named `{}`, configurations without a \
+                     name are named `{}`",
+                    configuration.name,
+                    DEFAULT_CONFIGURATION
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The configurations to choose from, a default configuration if none are configured.
+    pub fn run_configurations(&self) -> Vec<RunConfiguration> {
+        if self.configurations.is_empty() {
+            return vec![RunConfiguration::default()];
+        }
+        self.configurations.clone()
+    }
+}
+
+impl RunConfiguration {
+    /// The arguments that are passed to cargo when the tests are compiled.
+    pub fn cargo_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if !self.features.is_empty() {
+            args.push("--features".into());
+            args.push(self.features.join(" "));
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".into());
+        }
+        if self.release {
+            args.push("--release".into());
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProjectConfig, DEFAULT_CONFIGURATION};
+
+    #[test]
+    fn parse_run_configurations() {
+        let json = r#"{
+            "editor": "subl {file}:{line}:{col}",
+            "configurations": [
+                { "name": "debug" },
+                {
+                    "name": "release",
+                    "features": ["gpu", "text"],
+                    "no_default_features": true,
+                    "release": true,
+                    "env": { "RUST_LOG": "debug" },
+                    "libtest_args": ["--test-threads", "1"],
+                    "clippy": true
+                }
+            ]
+        }"#;
+        let config: ProjectConfig = serde_json::from_str(json).unwrap();
+        let configurations = config.run_configurations();
+
+        assert!(configurations[0].cargo_args().is_empty());
+        assert_eq!(
+            configurations[1].cargo_args(),
+            vec![
+                "--features",
+                "gpu text",
+                "--no-default-features",
+                "--release"
+            ]
+        );
+        assert_eq!(configurations[1].env["RUST_LOG"], "debug");
+        assert!(!configurations[0].clippy && configurations[1].clippy);
+        assert_eq!(config.editor.unwrap(), "subl {file}:{line}:{col}");
+
+        let empty = ProjectConfig::default().run_configurations();
+        assert_eq!(empty[0].name, DEFAULT_CONFIGURATION);
+    }
+
+    #[test]
+    fn run_configurations_need_distinct_names() {
+        let parse = |json| serde_json::from_str::<ProjectConfig>(json).unwrap();
+
+        assert!(parse(r#"{ "configurations": [{ "release": true }] }"#)
+            .check_names()
+            .is_ok());
+        assert!(
+            parse(r#"{ "configurations": [{ "release": true }, { "name": "debug" }] }"#)
+                .check_names()
+                .is_ok()
+        );
+        assert!(
+            parse(r#"{ "configurations": [{ "release": true }, { "clippy": true }] }"#)
+                .check_names()
+                .is_err()
+        );
+    }
+}
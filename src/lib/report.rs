@@ -0,0 +1,91 @@
+//! Reports of test runs that can be viewed and processed outside of the testrunner.
+
+use crate::libtest::{ExtraData, TestCapture, TestResult};
+use cargo_metadata::CompilerMessage;
+use emergent_terminal::text_attributor;
+use std::fmt::Write;
+
+pub mod html;
+pub mod junit;
+pub mod svg;
+
+/// Escapes the characters that have a special meaning in XML and HTML text and attributes.
+///
+/// Control characters other than tabs and line breaks are not allowed in XML documents and are
+/// removed.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The summary and the details of why a test failed, `None` if the test did not fail.
+fn failure_of(capture: &TestCapture) -> Option<(String, String)> {
+    let summary = match &capture.result {
+        TestResult::Failed(_) => "test failed",
+        TestResult::Timeout => "test timed out",
+        TestResult::SnapshotMismatch => "drawings differ from their snapshots",
+        _ => return None,
+    };
+
+    let mut details = String::new();
+    match (&capture.failure, &capture.result) {
+        (Some(failure), _) => {
+            details.push_str(&failure.message);
+            if let Some(comparison) = &failure.comparison {
+                write!(
+                    details,
+                    "\n\n  left: {}\n right: {}",
+                    comparison.left, comparison.right
+                )
+                .unwrap();
+            }
+            if let Some(location) = &failure.location {
+                write!(details, "\n\nat {}", location).unwrap();
+            }
+        }
+        (None, TestResult::Failed(Some(ExtraData::Message(message)))) => details.push_str(message),
+        (None, _) => details.push_str(summary),
+    }
+
+    let message = match &capture.failure {
+        Some(failure) => failure.message.clone(),
+        None => summary.to_string(),
+    };
+    Some((message, details))
+}
+
+/// The text of a compiler message without its ANSI escape sequences.
+fn plain_text(message: &CompilerMessage) -> String {
+    match &message.message.rendered {
+        Some(rendered) => text_attributor::attribute_str(rendered)
+            .into_iter()
+            .map(|attributed| attributed.text)
+            .collect(),
+        None => message.message.message.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape;
+
+    #[test]
+    fn escape_markup_and_control_characters() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>\n\u{1b}[31mred"),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;\n[31mred"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Export of test runs as standalone HTML pages.
+
+use super::svg::to_svg;
+use super::{escape, failure_of};
+use crate::libtest::{TestCapture, TestResult};
+use crate::test_runner::TestRunResult;
+use cargo_metadata::CompilerMessage;
+use emergent_drawing::artifact::Artifact;
+use emergent_drawing::MeasureText;
+use emergent_terminal::{color_schemes, term, text_attributor};
+use emergent_ui::DPI;
+use std::fmt::Write;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+pre { font-family: 'JetBrains Mono', monospace; font-size: 0.8em; }
+pre { background: #f8f8f8; padding: 0.5em; }
+.passed { color: #00a000; }
+.failed { color: #c00000; }
+.ignored { color: #808080; }
+.capture { border-top: 1px solid #e0e0e0; padding: 0.5em 0; }
+.failure { color: #c00000; }
+figure { margin: 0.5em 0; }
+figcaption { font-style: italic; }
+td, th { text-align: left; padding: 0 1em 0 0; }
+";
+
+/// Converts the result of a test run to a standalone HTML page.
+///
+/// The drawings of the captures are embedded as inline SVG, their text is measured with the
+/// `MeasureText` implementation `measure` returns for the DPI of the environment variant the
+/// drawing was captured in. Compiler messages are rendered with the colors of their ANSI escape
+/// sequences.
+pub fn html_report<M: MeasureText>(
+    result: &TestRunResult,
+    configuration: &str,
+    dpi: DPI,
+    measure: impl Fn(DPI) -> M,
+) -> String {
+    let mut report = HtmlReport {
+        html: String::new(),
+        drawings: 0,
+    };
+    let title = format!("Test report: {}", escape(configuration));
+    write!(
+        report.html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>{}</style></head><body><h1>{title}</h1>",
+        STYLE,
+        title = title
+    )
+    .unwrap();
+
+    match result {
+        TestRunResult::CompilationFailed(compiler_messages, e) => {
+            write!(
+                report.html,
+                r#"<p class="failed">Compilation failed: {}</p>"#,
+                escape(&e.to_string())
+            )
+            .unwrap();
+            report.compiler_messages(compiler_messages);
+        }
+        TestRunResult::TestsCaptured(compiler_messages, packages) => {
+            report.compiler_messages(compiler_messages);
+            for package in packages {
+                let totals = package.totals();
+                write!(
+                    report.html,
+                    "<h2>{}</h2><p>{} passed, {} failed, {} ignored</p>",
+                    escape(&package.package),
+                    totals.passed,
+                    totals.failed,
+                    totals.ignored
+                )
+                .unwrap();
+                for target in &package.targets {
+                    write!(
+                        report.html,
+                        "<h3>{}</h3>",
+                        escape(&target.target.to_string())
+                    )
+                    .unwrap();
+                    report.captures(&target.captures.0, dpi, &measure(dpi));
+                    for variant in &target.variants {
+                        let heading = format!("{} ({})", target.target, variant.variant);
+                        write!(report.html, "<h3>{}</h3>", escape(&heading)).unwrap();
+                        let dpi = variant.variant.dpi;
+                        report.captures(&variant.captures.0, dpi, &measure(dpi));
+                    }
+                }
+            }
+        }
+    }
+
+    report.html.push_str("</body></html>\n");
+    report.html
+}
+
+struct HtmlReport {
+    html: String,
+    /// The number of drawings embedded so far, used to make the ids inside the SVG elements
+    /// unique.
+    drawings: usize,
+}
+
+impl HtmlReport {
+    fn compiler_messages(&mut self, messages: &[CompilerMessage]) {
+        if messages.is_empty() {
+            return;
+        }
+        self.html.push_str("<h2>Compiler messages</h2>");
+        for message in messages {
+            write!(self.html, "<pre>{}</pre>", compiler_message_html(message)).unwrap();
+        }
+    }
+
+    fn captures(&mut self, captures: &[TestCapture], dpi: DPI, measure: &dyn MeasureText) {
+        for capture in captures {
+            self.capture(capture, dpi, measure);
+        }
+    }
+
+    fn capture(&mut self, capture: &TestCapture, dpi: DPI, measure: &dyn MeasureText) {
+        let (class, result) = match &capture.result {
+            TestResult::Ok() => ("passed", "ok".to_string()),
+            TestResult::AllowedFail => ("passed", "allowed to fail".to_string()),
+            TestResult::Bench(bench) => ("passed", bench.to_string()),
+            TestResult::Ignored => ("ignored", "ignored".to_string()),
+            TestResult::Failed(_) => ("failed", "failed".to_string()),
+            TestResult::Timeout => ("failed", "timed out".to_string()),
+            TestResult::SnapshotMismatch => ("failed", "snapshot mismatch".to_string()),
+        };
+        write!(
+            self.html,
+            r#"<div class="capture"><h4>{} <span class="{}">{}</span></h4>"#,
+            escape(&capture.name),
+            class,
+            escape(&result)
+        )
+        .unwrap();
+
+        if let Some((_, details)) = failure_of(capture) {
+            write!(
+                self.html,
+                r#"<pre class="failure">{}</pre>"#,
+                escape(&details)
+            )
+            .unwrap();
+        }
+        for artifact in &capture.artifacts {
+            self.artifact(artifact, dpi, measure);
+        }
+        if !capture.output.is_empty() {
+            write!(self.html, "<pre>{}</pre>", escape(&capture.output)).unwrap();
+        }
+
+        self.html.push_str("</div>");
+    }
+
+    fn artifact(&mut self, artifact: &Artifact, dpi: DPI, measure: &dyn MeasureText) {
+        write!(
+            self.html,
+            "<figure><figcaption>{}</figcaption>",
+            escape(artifact.title())
+        )
+        .unwrap();
+        match artifact {
+            Artifact::Drawing { drawing, .. } => {
+                self.drawings += 1;
+                let id_prefix = format!("drawing{}-", self.drawings);
+                match to_svg(drawing, dpi, measure, &id_prefix) {
+                    Some(svg) => self.html.push_str(&svg),
+                    None => self.html.push_str("<p>(empty)</p>"),
+                }
+            }
+            Artifact::Text { text, .. } => {
+                write!(self.html, "<pre>{}</pre>", escape(text)).unwrap();
+            }
+            Artifact::Table { rows, .. } => {
+                self.html.push_str("<table>");
+                for (key, value) in rows {
+                    write!(
+                        self.html,
+                        "<tr><th>{}</th><td>{}</td></tr>",
+                        escape(key),
+                        escape(value)
+                    )
+                    .unwrap();
+                }
+                self.html.push_str("</table>");
+            }
+        }
+        self.html.push_str("</figure>");
+    }
+}
+
+/// Converts the rendered text of a compiler message to HTML with the colors and the bold
+/// attributes of its ANSI escape sequences.
+fn compiler_message_html(message: &CompilerMessage) -> String {
+    let rendered = match &message.message.rendered {
+        Some(rendered) => rendered,
+        None => return escape(&message.message.message),
+    };
+
+    let indexed_colors = term::color::List::from(color_schemes::light::PAPER);
+    let mut html = String::new();
+    for attributed in text_attributor::attribute_str(rendered) {
+        let mut style = String::new();
+        if let Some(index) = attributed.attributes.color {
+            let color = indexed_colors[index];
+            write!(
+                style,
+                "color: #{:02x}{:02x}{:02x};",
+                color.r, color.g, color.b
+            )
+            .unwrap();
+        }
+        if attributed.attributes.bold {
+            style.push_str("font-weight: bold;");
+        }
+        if style.is_empty() {
+            html.push_str(&escape(&attributed.text));
+        } else {
+            write!(
+                html,
+                r#"<span style="{}">{}</span>"#,
+                style,
+                escape(&attributed.text)
+            )
+            .unwrap();
+        }
+    }
+    html
+}
@@ -0,0 +1,159 @@
+//! Export of test runs in the JUnit XML format that continuous integration servers understand.
+
+use super::{escape, failure_of, plain_text};
+use crate::libtest::{TestCapture, TestResult, TestTotals};
+use crate::test_runner::TestRunResult;
+use std::fmt::Write;
+
+/// Converts the result of a test run to a JUnit XML document.
+///
+/// Every target of a package is a test suite, and so is every variant of the test environment
+/// the target ran in. If the compilation failed, the document contains a single test case with
+/// an error that lists the compiler messages.
+pub fn junit_xml(result: &TestRunResult, configuration: &str) -> String {
+    let mut suites = Vec::new();
+    let mut errors = 0;
+    match result {
+        TestRunResult::CompilationFailed(compiler_messages, e) => {
+            let messages: Vec<String> = compiler_messages.iter().map(plain_text).collect();
+            let mut suite = String::from(
+                r#"<testsuite name="compilation" tests="1" failures="0" errors="1" skipped="0">"#,
+            );
+            write!(
+                suite,
+                r#"<testcase name="compilation" classname="{}"><error message="{}">{}</error>"#,
+                escape(configuration),
+                escape(&e.to_string()),
+                escape(&messages.join("\n"))
+            )
+            .unwrap();
+            suite.push_str("</testcase></testsuite>");
+            suites.push((TestTotals::default(), suite));
+            errors += 1;
+        }
+        TestRunResult::TestsCaptured(_, packages) => {
+            for package in packages {
+                for target in &package.targets {
+                    let name = format!("{}::{}", package.package, target.target);
+                    suites.push(test_suite(&name, &target.captures.0));
+                    for variant in &target.variants {
+                        let name = format!("{} ({})", name, variant.variant);
+                        suites.push(test_suite(&name, &variant.captures.0));
+                    }
+                }
+            }
+        }
+    }
+
+    let totals = suites
+        .iter()
+        .fold(TestTotals::default(), |sum, (totals, _)| TestTotals {
+            passed: sum.passed + totals.passed,
+            failed: sum.failed + totals.failed,
+            ignored: sum.ignored + totals.ignored,
+        });
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    write!(
+        xml,
+        "\n<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+        escape(configuration),
+        totals.passed + totals.failed + totals.ignored + errors,
+        totals.failed,
+        errors
+    )
+    .unwrap();
+    for (_, suite) in suites {
+        xml.push_str(&suite);
+        xml.push('\n');
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Returns the totals of the captures and the XML of the test suite.
+fn test_suite(name: &str, captures: &[TestCapture]) -> (TestTotals, String) {
+    let totals = TestTotals::from_captures(captures);
+    let mut suite = String::new();
+    write!(
+        suite,
+        r#"<testsuite name="{}" tests="{}" failures="{}" errors="0" skipped="{}">"#,
+        escape(name),
+        captures.len(),
+        totals.failed,
+        totals.ignored
+    )
+    .unwrap();
+    for capture in captures {
+        suite.push_str(&test_case(name, capture));
+    }
+    suite.push_str("</testsuite>");
+    (totals, suite)
+}
+
+fn test_case(suite: &str, capture: &TestCapture) -> String {
+    let mut case = format!(
+        r#"<testcase name="{}" classname="{}">"#,
+        escape(&capture.name),
+        escape(suite)
+    );
+    if let Some((message, details)) = failure_of(capture) {
+        write!(
+            case,
+            r#"<failure message="{}">{}</failure>"#,
+            escape(&message),
+            escape(&details)
+        )
+        .unwrap();
+    }
+    if capture.result == TestResult::Ignored {
+        case.push_str("<skipped/>");
+    }
+    if !capture.output.is_empty() {
+        write!(case, "<system-out>{}</system-out>", escape(&capture.output)).unwrap();
+    }
+    case.push_str("</testcase>");
+    case
+}
+
+#[cfg(test)]
+mod tests {
+    use super::junit_xml;
+    use crate::libtest::{ExtraData, TestCapture, TestCaptures, TestResult};
+    use crate::test_runner::{PackageCaptures, TargetCaptures, TestRunResult, TestTarget};
+
+    #[test]
+    fn failures_skipped_tests_and_output() {
+        let captures = vec![
+            TestCapture::new("passes", TestResult::Ok(), "printed <output>\n"),
+            TestCapture::new(
+                "fails",
+                TestResult::Failed(Some(ExtraData::Message("expected \"a\"".into()))),
+                "",
+            ),
+            TestCapture::new("ignored", TestResult::Ignored, ""),
+        ];
+        let result = TestRunResult::TestsCaptured(
+            Vec::new(),
+            vec![PackageCaptures {
+                package: "emergent".into(),
+                targets: vec![TargetCaptures {
+                    target: TestTarget::Lib,
+                    captures: TestCaptures(captures),
+                    variants: Vec::new(),
+                }],
+            }],
+        );
+
+        let xml = junit_xml(&result, "default");
+
+        assert!(xml.contains(r#"<testsuites name="default" tests="3" failures="1" errors="0">"#));
+        assert!(xml.contains(
+            r#"<testsuite name="emergent::lib" tests="3" failures="1" errors="0" skipped="1">"#
+        ));
+        assert!(xml.contains("<system-out>printed &lt;output&gt;\n</system-out>"));
+        assert!(xml.contains(r#"<failure message="test failed">expected &quot;a&quot;</failure>"#));
+        assert!(xml.contains(
+            r#"<testcase name="ignored" classname="emergent::lib"><skipped/></testcase>"#
+        ));
+    }
+}
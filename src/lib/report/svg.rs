@@ -0,0 +1,506 @@
+//! Conversion of drawings to SVG.
+
+use super::escape;
+use crate::text_as_lines;
+use emergent_drawing as drawing;
+use emergent_drawing::paint::{Cap, Join, Style};
+use emergent_drawing::{
+    BlendMode, Clip, Color, DrawTo, DrawingFastBounds, MeasureText, Paint, Point, Shape, Transform,
+};
+use emergent_ui::DPI;
+use std::fmt::Write;
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+/// The spacing between the lines of a text relative to the font size.
+const LINE_SPACING: f64 = 1.2;
+
+/// Converts a drawing to a standalone SVG element that covers the drawing's bounds.
+///
+/// `id_prefix` makes the ids of the clip paths unique if more than one SVG element is embedded in
+/// the same document. Returns `None` if the drawing is empty or unbounded.
+pub fn to_svg(
+    drawing: &drawing::Drawing,
+    dpi: DPI,
+    measure: &dyn MeasureText,
+    id_prefix: &str,
+) -> Option<String> {
+    let bounds = *drawing.fast_bounds(measure).as_bounds()?;
+    let view_box = (
+        bounds.point.x,
+        bounds.point.y,
+        bounds.width().ceil().max(1.0),
+        bounds.height().ceil().max(1.0),
+    );
+    let mut target = SvgDrawingTarget {
+        svg: String::new(),
+        dpi,
+        id_prefix,
+        clip_paths: 0,
+        view_box,
+        matrix: IDENTITY,
+    };
+    drawing.draw_to(Paint::default(), &mut target);
+
+    let (x, y, w, h) = view_box;
+    Some(format!(
+        r#"<svg xmlns="{}" width="{w}" height="{h}" viewBox="{x} {y} {w} {h}">{}</svg>"#,
+        SVG_NAMESPACE,
+        target.svg,
+        x = x,
+        y = y,
+        w = w,
+        h = h,
+    ))
+}
+
+/// An affine matrix in the order of the SVG `matrix()` transform function.
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+struct SvgDrawingTarget<'a> {
+    svg: String,
+    dpi: DPI,
+    id_prefix: &'a str,
+    /// The number of clip paths defined so far.
+    clip_paths: usize,
+    /// The x, y, width, and height of the `viewBox` of the SVG element.
+    view_box: (f64, f64, f64, f64),
+    /// The matrix that maps the current coordinates to the coordinates of the SVG element.
+    matrix: Matrix,
+}
+
+impl drawing::DrawingTarget for SvgDrawingTarget<'_> {
+    fn fill(&mut self, paint: Paint, _blend_mode: BlendMode) {
+        // the rect covers the view box, so it is mapped back from the coordinates of the SVG
+        // element.
+        let inverse = match invert(&self.matrix) {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let (x, y, w, h) = self.view_box;
+        let mut element = format!(r#"<rect x="{}" y="{}" width="{}" height="{}""#, x, y, w, h);
+        if inverse != IDENTITY {
+            element.push_str(&format!(r#" transform="{}""#, matrix_function(&inverse)));
+        }
+        let paint = Paint {
+            style: Style::Fill,
+            ..paint
+        };
+        self.element(&element, paint);
+    }
+
+    fn draw_shape(&mut self, shape: &Shape, paint: Paint) {
+        match shape {
+            Shape::Point(p) => {
+                let radius = (paint.width / 2.0).max(0.5);
+                let paint = Paint {
+                    style: Style::Fill,
+                    ..paint
+                };
+                let element = format!(r#"<circle cx="{}" cy="{}" r="{}""#, p.x, p.y, radius);
+                self.element(&element, paint);
+            }
+            Shape::Line(line) => {
+                let (p1, p2) = (line.point1, line.point2);
+                let element = format!(
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}""#,
+                    p1.x, p1.y, p2.x, p2.y
+                );
+                self.element(&element, stroked(paint));
+            }
+            Shape::Polygon(polygon) => {
+                let element = format!(r#"<polyline points="{}""#, points(polygon.points()));
+                self.element(&element, stroked(paint));
+            }
+            Shape::Rect(rect) => {
+                let element = format!(
+                    r#"<rect x="{}" y="{}" width="{}" height="{}""#,
+                    rect.left,
+                    rect.top,
+                    rect.width(),
+                    rect.height()
+                );
+                self.element(&element, paint);
+            }
+            Shape::Oval(oval) => {
+                let rect = oval.rect();
+                let element = format!(
+                    r#"<ellipse cx="{}" cy="{}" rx="{}" ry="{}""#,
+                    (rect.left + rect.right) / 2.0,
+                    (rect.top + rect.bottom) / 2.0,
+                    rect.width() / 2.0,
+                    rect.height() / 2.0
+                );
+                self.element(&element, paint);
+            }
+            Shape::RoundedRect(rounded_rect) => {
+                let element = format!(r#"<path d="{}""#, rounded_rect_path(rounded_rect));
+                self.element(&element, paint);
+            }
+            Shape::Circle(circle) => {
+                let element = format!(
+                    r#"<circle cx="{}" cy="{}" r="{}""#,
+                    circle.center.x, circle.center.y, *circle.radius
+                );
+                self.element(&element, paint);
+            }
+            Shape::Arc(arc) => {
+                let element = format!(r#"<path d="{}""#, arc_path(arc));
+                self.element(&element, paint);
+            }
+            Shape::Path(path) => {
+                let element = format!(r#"<path d="{}""#, path_data(path));
+                self.element(&element, paint);
+            }
+            Shape::Image(_, _, _) => warn!("images are not supported in SVG exports"),
+            Shape::Text(text) => self.text(text, paint),
+        }
+    }
+
+    fn clip(&mut self, clip: &Clip, f: impl FnOnce(&mut Self)) {
+        self.clip_paths += 1;
+        let id = format!("{}clip{}", self.id_prefix, self.clip_paths);
+        let shape = match clip {
+            Clip::Rect(rect) => format!(
+                r#"<rect x="{}" y="{}" width="{}" height="{}"/>"#,
+                rect.left,
+                rect.top,
+                rect.width(),
+                rect.height()
+            ),
+            Clip::RoundedRect(rounded_rect) => {
+                format!(r#"<path d="{}"/>"#, rounded_rect_path(rounded_rect))
+            }
+            Clip::Path(path) => format!(r#"<path d="{}"/>"#, path_data(path)),
+        };
+        write!(
+            self.svg,
+            r#"<clipPath id="{id}">{}</clipPath><g clip-path="url(#{id})">"#,
+            shape,
+            id = id
+        )
+        .unwrap();
+        f(self);
+        self.svg.push_str("</g>");
+    }
+
+    fn transform(&mut self, transform: &Transform, f: impl FnOnce(&mut Self)) {
+        // the affine matrix is derived from how the transform maps the origin and the unit
+        // vectors.
+        let origin = transform.map_point(Point::new(0.0, 0.0));
+        let x = transform.map_point(Point::new(1.0, 0.0)) - origin;
+        let y = transform.map_point(Point::new(0.0, 1.0)) - origin;
+        let matrix = [x.x, x.y, y.x, y.y, origin.x, origin.y];
+        write!(self.svg, r#"<g transform="{}">"#, matrix_function(&matrix)).unwrap();
+        let outer = self.matrix;
+        self.matrix = concat(&outer, &matrix);
+        f(self);
+        self.matrix = outer;
+        self.svg.push_str("</g>");
+    }
+}
+
+/// Returns the matrix that applies `inner` first and then `outer`.
+fn concat(outer: &Matrix, inner: &Matrix) -> Matrix {
+    let [a, b, c, d, e, f] = *outer;
+    let [a2, b2, c2, d2, e2, f2] = *inner;
+    [
+        a * a2 + c * b2,
+        b * a2 + d * b2,
+        a * c2 + c * d2,
+        b * c2 + d * d2,
+        a * e2 + c * f2 + e,
+        b * e2 + d * f2 + f,
+    ]
+}
+
+/// The inverse of a matrix, `None` if the matrix is not invertible.
+fn invert(matrix: &Matrix) -> Option<Matrix> {
+    let [a, b, c, d, e, f] = *matrix;
+    let determinant = a * d - b * c;
+    if determinant == 0.0 {
+        return None;
+    }
+    let inverse = [
+        d / determinant,
+        -b / determinant,
+        -c / determinant,
+        a / determinant,
+        (c * f - d * e) / determinant,
+        (b * e - a * f) / determinant,
+    ];
+    // adding zero turns negative zeros into zeros, so that they are not formatted as `-0`.
+    Some([
+        inverse[0] + 0.0,
+        inverse[1] + 0.0,
+        inverse[2] + 0.0,
+        inverse[3] + 0.0,
+        inverse[4] + 0.0,
+        inverse[5] + 0.0,
+    ])
+}
+
+fn matrix_function(matrix: &Matrix) -> String {
+    let [a, b, c, d, e, f] = *matrix;
+    format!("matrix({} {} {} {} {} {})", a, b, c, d, e, f)
+}
+
+impl SvgDrawingTarget<'_> {
+    /// Completes an element with the attributes of the paint.
+    fn element(&mut self, element: &str, paint: Paint) {
+        write!(self.svg, "{}{}/>", element, paint_attributes(paint)).unwrap();
+    }
+
+    /// Writes a text element, each run is a `tspan` and each line of a run starts a new line.
+    fn text(&mut self, text: &drawing::Text, paint: Paint) {
+        let font = &text.font;
+        let size = self.dpi.scale_font_points(*font.size);
+        write!(
+            self.svg,
+            r#"<text x="{}" y="{}" font-size="{}"{}{} xml:space="preserve">"#,
+            text.origin.x,
+            text.origin.y,
+            size,
+            font_attributes(font),
+            fill_attributes(paint.color)
+        )
+        .unwrap();
+
+        for run in &text.runs {
+            match run {
+                drawing::text::Run::Text(string, properties) => {
+                    let mut attributes = String::new();
+                    if let Some(style) = properties.style {
+                        attributes.push_str(&style_attributes(style));
+                    }
+                    if let Some(color) = properties.color {
+                        attributes.push_str(&fill_attributes(color));
+                    }
+                    for (i, line) in text_as_lines(string).enumerate() {
+                        let position = if i == 0 {
+                            String::new()
+                        } else {
+                            format!(r#" x="{}" dy="{}""#, text.origin.x, size * LINE_SPACING)
+                        };
+                        write!(
+                            self.svg,
+                            "<tspan{}{}>{}</tspan>",
+                            position,
+                            attributes,
+                            escape(line)
+                        )
+                        .unwrap();
+                    }
+                }
+                _ => warn!("nested text blocks and drawings are not supported in SVG exports"),
+            }
+        }
+
+        self.svg.push_str("</text>");
+    }
+}
+
+/// Lines and polygons are always stroked.
+fn stroked(paint: Paint) -> Paint {
+    Paint {
+        style: Style::Stroke,
+        ..paint
+    }
+}
+
+fn paint_attributes(paint: Paint) -> String {
+    match paint.style {
+        Style::Fill => fill_attributes(paint.color),
+        Style::Stroke => format!(r#" fill="none"{}"#, stroke_attributes(paint)),
+        Style::FillAndStroke => {
+            format!(
+                "{}{}",
+                fill_attributes(paint.color),
+                stroke_attributes(paint)
+            )
+        }
+    }
+}
+
+fn fill_attributes(color: Color) -> String {
+    let mut attributes = format!(r#" fill="{}""#, css_color(color));
+    if color.alpha() < 1.0 {
+        write!(attributes, r#" fill-opacity="{}""#, color.alpha()).unwrap();
+    }
+    attributes
+}
+
+fn stroke_attributes(paint: Paint) -> String {
+    // a width of zero draws hairlines.
+    let mut attributes = format!(
+        r#" stroke="{}" stroke-width="{}""#,
+        css_color(paint.color),
+        if paint.width == 0.0 { 1.0 } else { paint.width }
+    );
+    if paint.color.alpha() < 1.0 {
+        write!(attributes, r#" stroke-opacity="{}""#, paint.color.alpha()).unwrap();
+    }
+    let cap = match paint.cap {
+        Cap::Butt => "butt",
+        Cap::Round => "round",
+        Cap::Square => "square",
+    };
+    let join = match paint.join {
+        Join::Miter => "miter",
+        Join::Round => "round",
+        Join::Bevel => "bevel",
+    };
+    write!(
+        attributes,
+        r#" stroke-linecap="{}" stroke-linejoin="{}" stroke-miterlimit="{}""#,
+        cap, join, paint.miter
+    )
+    .unwrap();
+    attributes
+}
+
+fn font_attributes(font: &drawing::Font) -> String {
+    let mut attributes = String::new();
+    if !font.name.is_empty() {
+        write!(attributes, r#" font-family="{}""#, escape(&font.name)).unwrap();
+    }
+    attributes.push_str(&style_attributes(font.style));
+    attributes
+}
+
+fn style_attributes(style: drawing::font::Style) -> String {
+    let slant = match style.slant {
+        drawing::font::Slant::Upright => "normal",
+        drawing::font::Slant::Italic => "italic",
+        drawing::font::Slant::Oblique => "oblique",
+    };
+    format!(r#" font-weight="{}" font-style="{}""#, *style.weight, slant)
+}
+
+/// The color as `#rrggbb`, the alpha component is ignored.
+fn css_color(color: Color) -> String {
+    format!("#{:06x}", color.to_u32() & 0x00ff_ffff)
+}
+
+fn points(points: &[Point]) -> String {
+    let points: Vec<String> = points.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+    points.join(" ")
+}
+
+fn path_data(path: &drawing::Path) -> String {
+    use drawing::path::Verb::*;
+    let mut data = String::new();
+    for verb in path.verbs() {
+        match verb {
+            MoveTo(p) => write!(data, "M{} {} ", p.x, p.y),
+            LineTo(p) => write!(data, "L{} {} ", p.x, p.y),
+            QuadTo(p1, p2) => write!(data, "Q{} {} {} {} ", p1.x, p1.y, p2.x, p2.y),
+            // SVG does not support conics, they are approximated with quadratic curves.
+            ConicTo(p1, p2, _) => write!(data, "Q{} {} {} {} ", p1.x, p1.y, p2.x, p2.y),
+            CubicTo(p1, p2, p3) => write!(
+                data,
+                "C{} {} {} {} {} {} ",
+                p1.x, p1.y, p2.x, p2.y, p3.x, p3.y
+            ),
+            Close => write!(data, "Z "),
+        }
+        .unwrap();
+    }
+    data.trim_end().to_string()
+}
+
+/// A path that starts at the top of the left top corner and continues clockwise.
+fn rounded_rect_path(rounded_rect: &drawing::RoundedRect) -> String {
+    let rect = rounded_rect.rect();
+    let [lt, rt, rb, lb] = rounded_rect.corner_radii();
+    let mut data = format!("M{} {} ", rect.left + lt.width, rect.top);
+    let corner = |data: &mut String, radius: &drawing::Extent, x: f64, y: f64| {
+        write!(
+            data,
+            "A{} {} 0 0 1 {} {} ",
+            radius.width, radius.height, x, y
+        )
+        .unwrap();
+    };
+    write!(data, "H{} ", rect.right - rt.width).unwrap();
+    corner(&mut data, rt, rect.right, rect.top + rt.height);
+    write!(data, "V{} ", rect.bottom - rb.height).unwrap();
+    corner(&mut data, rb, rect.right - rb.width, rect.bottom);
+    write!(data, "H{} ", rect.left + lb.width).unwrap();
+    corner(&mut data, lb, rect.left, rect.bottom - lb.height);
+    write!(data, "V{} ", rect.top + lt.height).unwrap();
+    corner(&mut data, lt, rect.left + lt.width, rect.top);
+    data.push('Z');
+    data
+}
+
+/// An arc along the oval, without lines to its center.
+fn arc_path(arc: &drawing::Arc) -> String {
+    let rect = arc.oval.rect();
+    let (cx, cy) = (
+        (rect.left + rect.right) / 2.0,
+        (rect.top + rect.bottom) / 2.0,
+    );
+    let (rx, ry) = (rect.width() / 2.0, rect.height() / 2.0);
+    // a full circle can not be described by a single SVG arc.
+    let sweep = (*arc.sweep).max(-359.99).min(359.99);
+    let point = |degrees: f64| {
+        let radians = degrees.to_radians();
+        (cx + rx * radians.cos(), cy + ry * radians.sin())
+    };
+    let (x1, y1) = point(*arc.start);
+    let (x2, y2) = point(*arc.start + sweep);
+    format!(
+        "M{} {} A{} {} 0 {} {} {} {}",
+        x1,
+        y1,
+        rx,
+        ry,
+        if sweep.abs() > 180.0 { 1 } else { 0 },
+        if sweep > 0.0 { 1 } else { 0 },
+        x2,
+        y2
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_svg;
+    use crate::skia::text::PrimitiveText;
+    use emergent_drawing::functions::{paint, rect};
+    use emergent_drawing::{BlendMode, Clipped, Drawing, DrawingTarget, Transformed, Vector};
+    use emergent_ui::DPI;
+
+    #[test]
+    fn transformed_rect() {
+        let mut drawing = Drawing::new();
+        drawing.draw(rect((0.0, 0.0), (10.0, 20.0)), paint());
+        let drawing = drawing.transformed(Vector::from((5.0, 5.0)));
+        let measure = PrimitiveText::new(DPI::DEFAULT_SCREEN);
+
+        let svg = to_svg(&drawing, DPI::DEFAULT_SCREEN, &measure, "").unwrap();
+
+        assert!(svg.contains(r#"width="10" height="20" viewBox="5 5 10 20""#));
+        assert!(svg.contains(r#"<g transform="matrix(1 0 0 1 5 5)">"#));
+        assert!(svg.contains(r##"<rect x="0" y="0" width="10" height="20" fill="#000000"/>"##));
+    }
+
+    #[test]
+    fn fill_covers_offset_view_box() {
+        let mut fill = Drawing::new();
+        fill.fill(paint(), BlendMode::SourceOver);
+        let drawing = fill
+            .clipped(rect((10.0, 10.0), (20.0, 20.0)))
+            .transformed(Vector::from((5.0, 5.0)));
+        let measure = PrimitiveText::new(DPI::DEFAULT_SCREEN);
+
+        let svg = to_svg(&drawing, DPI::DEFAULT_SCREEN, &measure, "").unwrap();
+
+        assert!(svg.contains(r#"viewBox="15 15 20 20""#));
+        assert!(svg.contains(
+            r#"<rect x="15" y="15" width="20" height="20" transform="matrix(1 0 0 1 -5 -5)""#
+        ));
+    }
+}
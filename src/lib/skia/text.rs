@@ -5,22 +5,30 @@ use emergent_drawing::functions::*;
 use emergent_drawing::{Bounds, FastBounds, FromTestEnvironment, Text, Union};
 use emergent_ui::DPI;
 use skia_safe::{Font, Point, Rect, Shaper, Typeface};
+use std::env;
 
 // Primitive text measurement and text rendering.
 pub struct PrimitiveText {
     dpi: DPI,
+    /// The font family that is used for fonts without a name instead of the default typeface.
+    default_family: Option<String>,
 }
 
 impl PrimitiveText {
     pub fn new(dpi: DPI) -> PrimitiveText {
-        PrimitiveText { dpi }
+        PrimitiveText {
+            dpi,
+            default_family: None,
+        }
     }
 }
 
 impl FromTestEnvironment for PrimitiveText {
+    /// Measures with the DPI and the default font family of the test environment.
     fn from_test_environment() -> PrimitiveText {
         PrimitiveText {
             dpi: DPI::from_test_environment(),
+            default_family: env::var("EMERGENT_TEST_FONT_FAMILY").ok(),
         }
     }
 }
@@ -28,8 +36,12 @@ impl FromTestEnvironment for PrimitiveText {
 impl drawing::MeasureText for PrimitiveText {
     fn measure_text(&self, text: &Text) -> drawing::Bounds {
         let font = &text.font;
-        let typeface = Typeface::from_name(&font.name, font.style.to_skia())
-            .expect("failed to resolve typeface");
+        let name = match &self.default_family {
+            Some(family) if font.name.is_empty() => family,
+            _ => &font.name,
+        };
+        let typeface =
+            Typeface::from_name(name, font.style.to_skia()).expect("failed to resolve typeface");
         let font = Font::from_typeface(&typeface, self.dpi.scale_font_points(*font.size) as f32);
 
         let mut origin = TextOrigin::new(text.origin);
@@ -0,0 +1,197 @@
+//! Comparison of test captures with their golden snapshots.
+
+use crate::libtest::{TestCapture, TestCaptures, TestResult};
+use emergent_drawing::diff::DrawingDiff;
+use emergent_drawing::snapshot::{self, DrawingName};
+use emergent_drawing::Drawing;
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The result of comparing a drawing of a test with its snapshot.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct SnapshotComparison {
+    /// The label of the drawing.
+    pub label: Option<String>,
+    /// The path of the snapshot file.
+    pub path: PathBuf,
+    pub status: SnapshotStatus,
+}
+
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub enum SnapshotStatus {
+    /// The drawing matches the snapshot.
+    Matches,
+    /// There is no snapshot for the drawing yet.
+    New,
+    /// The drawing differs from the recorded snapshot.
+    Changed { recorded: Drawing },
+}
+
+/// Compares the drawings of the captures with the snapshots in the package directory.
+///
+/// `crate_name` is the name of the crate the tests are compiled into. Captures of tests that
+/// passed but have changed drawings get the result `TestResult::SnapshotMismatch`.
+pub fn compare_captures(package_directory: &Path, crate_name: &str, captures: &mut TestCaptures) {
+    for capture in captures.0.iter_mut() {
+        compare_capture(package_directory, crate_name, capture);
+    }
+}
+
+/// Compares the drawings of a capture with their snapshots.
+pub fn compare_capture(package_directory: &Path, crate_name: &str, capture: &mut TestCapture) {
+    if let Err(e) = try_compare_capture(package_directory, crate_name, capture) {
+        warn!("failed to compare snapshots of {}: {}", capture.name, e);
+    }
+}
+
+fn try_compare_capture(
+    package_directory: &Path,
+    crate_name: &str,
+    capture: &mut TestCapture,
+) -> io::Result<()> {
+    let drawings: Vec<_> = capture
+        .artifacts
+        .iter()
+        .filter_map(|a| a.drawing().map(|d| (a.label(), d)))
+        .collect();
+
+    let mut comparisons = Vec::new();
+    // unlabelled drawings are named like `assert_drawing_snapshot!` names them.
+    let mut unlabelled = 0;
+    for (label, drawing) in drawings {
+        let name = DrawingName::next(label, &mut unlabelled);
+        let path = snapshot::snapshot_path(package_directory, crate_name, &capture.name, name);
+        let status = match snapshot::read(&path)? {
+            None => SnapshotStatus::New,
+            Some(recorded) if DrawingDiff::new(&recorded, drawing).is_empty() => {
+                SnapshotStatus::Matches
+            }
+            Some(recorded) => SnapshotStatus::Changed { recorded },
+        };
+        comparisons.push(SnapshotComparison {
+            label: label.map(String::from),
+            path,
+            status,
+        });
+    }
+
+    capture.snapshots = comparisons;
+    capture.update_snapshot_result();
+    Ok(())
+}
+
+impl TestCapture {
+    /// Records the current drawings of the capture as their snapshots.
+    pub fn accept_snapshots(&mut self) -> io::Result<()> {
+        let drawings = self.artifacts.iter().filter_map(|a| a.drawing());
+        for (comparison, drawing) in self.snapshots.iter_mut().zip(drawings) {
+            if comparison.status != SnapshotStatus::Matches {
+                snapshot::write(&comparison.path, drawing)?;
+                comparison.status = SnapshotStatus::Matches;
+            }
+        }
+        self.update_snapshot_result();
+        Ok(())
+    }
+
+    /// Returns `true` if the capture has drawings that are new or differ from their snapshots.
+    pub fn has_pending_snapshots(&self) -> bool {
+        self.snapshots
+            .iter()
+            .any(|c| c.status != SnapshotStatus::Matches)
+    }
+
+    fn update_snapshot_result(&mut self) {
+        let changed = self.snapshots.iter().any(|c| match c.status {
+            SnapshotStatus::Changed { .. } => true,
+            _ => false,
+        });
+
+        match self.result {
+            TestResult::Ok() if changed => self.result = TestResult::SnapshotMismatch,
+            TestResult::SnapshotMismatch if !changed => self.result = TestResult::Ok(),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_captures, SnapshotStatus};
+    use crate::libtest::{TestCapture, TestCaptures, TestResult};
+    use emergent_drawing::artifact::Artifact;
+    use emergent_drawing::functions::{paint, rect};
+    use emergent_drawing::{Drawing, DrawingTarget};
+    use std::{env, fs, process};
+
+    #[test]
+    fn accepted_snapshots_match() {
+        let package_directory =
+            env::temp_dir().join(format!("emergent-snapshot-test-{}", process::id()));
+
+        let mut drawing = Drawing::new();
+        drawing.draw(rect((0, 0), (10, 10)), paint());
+        let artifact = Artifact::Drawing {
+            label: None,
+            drawing,
+        };
+        let output = format!("{}\n", artifact.to_line().unwrap());
+        let mut captures = TestCaptures(vec![TestCapture::new("test", TestResult::Ok(), &output)]);
+
+        compare_captures(&package_directory, "emergent", &mut captures);
+        assert_eq!(captures.0[0].snapshots[0].status, SnapshotStatus::New);
+        assert_eq!(captures.0[0].result, TestResult::Ok());
+
+        captures.0[0].accept_snapshots().unwrap();
+        compare_captures(&package_directory, "emergent", &mut captures);
+        assert_eq!(captures.0[0].snapshots[0].status, SnapshotStatus::Matches);
+
+        let mut changed = Drawing::new();
+        changed.draw(rect((0, 0), (20, 20)), paint());
+        let artifact = Artifact::Drawing {
+            label: None,
+            drawing: changed,
+        };
+        let output = format!("{}\n", artifact.to_line().unwrap());
+        let mut captures = TestCaptures(vec![TestCapture::new("test", TestResult::Ok(), &output)]);
+        compare_captures(&package_directory, "emergent", &mut captures);
+        assert_eq!(captures.0[0].result, TestResult::SnapshotMismatch);
+
+        fs::remove_dir_all(&package_directory).unwrap();
+    }
+
+    #[test]
+    fn drawings_of_a_test_have_distinct_snapshots() {
+        let package_directory =
+            env::temp_dir().join(format!("emergent-snapshot-names-{}", process::id()));
+        let artifacts = [None, Some("after"), None].iter().map(|label| {
+            let mut drawing = Drawing::new();
+            drawing.draw(rect((0, 0), (10, 10)), paint());
+            Artifact::Drawing {
+                label: label.map(String::from),
+                drawing,
+            }
+        });
+        let output: String = artifacts
+            .map(|a| format!("{}\n", a.to_line().unwrap()))
+            .collect();
+        let mut captures = TestCaptures(vec![TestCapture::new(
+            "tests::test",
+            TestResult::Ok(),
+            &output,
+        )]);
+
+        compare_captures(&package_directory, "emergent_test", &mut captures);
+        let directory = package_directory.join("snapshots").join("emergent_test");
+        let paths: Vec<_> = captures.0[0].snapshots.iter().map(|s| &s.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                &directory.join("tests.test.json"),
+                &directory.join("tests.test-after.json"),
+                &directory.join("tests.test#2.json"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Application of the suggestions of compiler diagnostics to the source files.
+
+use crate::compiler_message::SourceFiles;
+use cargo_metadata::diagnostic::{Applicability, Diagnostic, DiagnosticSpan};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// A replacement of a byte range of a source file that the compiler suggested.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct Replacement {
+    /// The name of the file as reported by the compiler.
+    pub file_name: String,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    /// The text the compiler saw in the range, used to detect files that changed since they were
+    /// compiled.
+    pub expected: String,
+    pub text: String,
+}
+
+/// Returns the replacements of the suggestions of a diagnostic and its children that the compiler
+/// considers to be correct and safe to apply.
+pub fn machine_applicable(diagnostic: &Diagnostic) -> Vec<Replacement> {
+    let spans = diagnostic.spans.iter().filter_map(|span| {
+        match (&span.suggested_replacement, &span.suggestion_applicability) {
+            (Some(text), Some(Applicability::MachineApplicable)) => Some(Replacement {
+                file_name: span.file_name.clone(),
+                byte_start: span.byte_start as usize,
+                byte_end: span.byte_end as usize,
+                expected: highlighted_text(span),
+                text: text.clone(),
+            }),
+            _ => None,
+        }
+    });
+    let children = diagnostic.children.iter().flat_map(machine_applicable);
+    spans.chain(children).collect()
+}
+
+/// Applies the replacements to the source files and returns the number of replacements applied.
+///
+/// Duplicate replacements are applied once, and replacements that overlap with another one are
+/// skipped. All files are checked before the first one is written, so if the text of a file
+/// differs from what the compiler saw, or a file can not be read, no file is changed.
+pub fn apply(replacements: &[Replacement], sources: &SourceFiles) -> Result<usize, failure::Error> {
+    let mut files: BTreeMap<&str, Vec<&Replacement>> = BTreeMap::new();
+    for replacement in replacements {
+        files
+            .entry(&replacement.file_name)
+            .or_default()
+            .push(replacement);
+    }
+
+    let mut applied = 0;
+    let mut changed = Vec::new();
+    for (file_name, replacements) in files {
+        let path = match sources.resolve(file_name) {
+            Some(path) => path,
+            None => failure::bail!("source file {} not found", file_name),
+        };
+        let source = fs::read_to_string(&path)?;
+        let (source, count) = replace(file_name, source, replacements)?;
+        changed.push((path, source));
+        applied += count;
+    }
+
+    for (path, source) in changed {
+        fs::write(&path, source)?;
+    }
+    Ok(applied)
+}
+
+/// Applies the replacements to the text of a file and returns the changed text and the number
+/// of replacements applied.
+fn replace(
+    file_name: &str,
+    mut source: String,
+    mut replacements: Vec<&Replacement>,
+) -> Result<(String, usize), failure::Error> {
+    replacements.sort_by_key(|r| (r.byte_start, r.byte_end));
+    replacements.dedup();
+
+    // the replacements are applied from the end of the file to its start, so that the
+    // offsets of the ones that are not applied yet stay valid.
+    let mut applied = 0;
+    let mut applied_start = source.len();
+    for replacement in replacements.into_iter().rev() {
+        let range = replacement.byte_start..replacement.byte_end;
+        if range.end > applied_start {
+            warn!("skipping an overlapping suggestion in {}", file_name);
+            continue;
+        }
+        let original = source.get(range.clone()).map(|s| s.replace("\r\n", "\n"));
+        if original.as_ref() != Some(&replacement.expected) {
+            failure::bail!("{} changed since it was compiled", file_name);
+        }
+        source.replace_range(range, &replacement.text);
+        applied_start = replacement.byte_start;
+        applied += 1;
+    }
+    Ok((source, applied))
+}
+
+/// The text of the source lines the span highlights.
+pub(crate) fn highlighted_text(span: &DiagnosticSpan) -> String {
+    let lines: Vec<String> = span
+        .text
+        .iter()
+        .map(|line| {
+            let start = line.highlight_start.saturating_sub(1);
+            let len = line.highlight_end.saturating_sub(line.highlight_start);
+            line.text.chars().skip(start).take(len).collect()
+        })
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, machine_applicable};
+    use crate::compiler_message::SourceFiles;
+    use cargo_metadata::diagnostic::Diagnostic;
+    use std::{env, fs, process};
+
+    #[test]
+    fn apply_machine_applicable_suggestion() {
+        let directory = env::temp_dir().join(format!("emergent-suggestion-test-{}", process::id()));
+        fs::create_dir_all(directory.join("src")).unwrap();
+        let file = directory.join("src/lib.rs");
+        fs::write(&file, "fn f() {\n    let é = 1;\n    let mut x = é;\n}\n").unwrap();
+
+        let diagnostic: Diagnostic = serde_json::from_str(UNUSED_MUT).unwrap();
+        let replacements = machine_applicable(&diagnostic);
+        assert_eq!(replacements.len(), 1);
+        assert_eq!(replacements[0].expected, "mut ");
+
+        let sources = SourceFiles::new(&directory);
+        // the same warning is reported for each target the file is compiled for.
+        let duplicated = vec![replacements[0].clone(), replacements[0].clone()];
+        assert_eq!(apply(&duplicated, &sources).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "fn f() {\n    let é = 1;\n    let x = é;\n}\n"
+        );
+        assert!(apply(&replacements, &sources).is_err());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn no_file_is_changed_if_one_changed_since_it_was_compiled() {
+        let directory =
+            env::temp_dir().join(format!("emergent-suggestion-files-{}", process::id()));
+        fs::create_dir_all(directory.join("src")).unwrap();
+        let source = "fn f() {\n    let é = 1;\n    let mut x = é;\n}\n";
+        let changed = "fn f() {\n    let é = 2;\n    let x = é;\n}\n";
+        // the file that is checked first is the one that still matches.
+        fs::write(directory.join("src/a.rs"), source).unwrap();
+        fs::write(directory.join("src/b.rs"), changed).unwrap();
+
+        let diagnostic: Diagnostic = serde_json::from_str(UNUSED_MUT).unwrap();
+        let mut replacements = Vec::new();
+        for file_name in &["src/a.rs", "src/b.rs"] {
+            let mut replacement = machine_applicable(&diagnostic).remove(0);
+            replacement.file_name = file_name.to_string();
+            replacements.push(replacement);
+        }
+
+        let sources = SourceFiles::new(&directory);
+        assert!(apply(&replacements, &sources).is_err());
+        let unchanged = fs::read_to_string(directory.join("src/a.rs")).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(unchanged, source);
+    }
+
+    const UNUSED_MUT: &str = r#"{"message":"variable does not need to be mutable","code":{"code":"unused_mut","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","byte_start":33,"byte_end":38,"line_start":3,"line_end":3,"column_start":9,"column_end":14,"is_primary":true,"text":[{"text":"    let mut x = é;","highlight_start":9,"highlight_end":14}],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"remove this `mut`","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":33,"byte_end":37,"line_start":3,"line_end":3,"column_start":9,"column_end":13,"is_primary":true,"text":[{"text":"    let mut x = é;","highlight_start":9,"highlight_end":13}],"label":null,"suggested_replacement":"","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":null}"#;
+}
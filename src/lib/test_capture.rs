@@ -1,23 +1,46 @@
 //! A captured test and its presentation.
 
-use crate::libtest::TestCapture;
+use crate::libtest::{TestCapture, TestResult, TestTotals};
+use crate::snapshot::{SnapshotComparison, SnapshotStatus};
+use crate::test_failure::FailureDetails;
+use crate::test_filter::TestFilter;
+use crate::test_history::{RunOutcome, Timeline};
+use crate::test_runner::{PackageCaptures, TargetCaptures, TestId, TestVariant};
 use crate::Msg;
-use emergent_drawing::functions::{paint, text};
-use emergent_drawing::{font, Drawing, DrawingTarget, Font};
+use emergent_drawing::artifact::Artifact;
+use emergent_drawing::diff::DrawingDiff;
+use emergent_drawing::functions::{paint, rect, text, text_block, text_properties};
+use emergent_drawing::{
+    font, Color, Drawing, DrawingFastBounds, DrawingTarget, Font, MeasureText, Rect, Visualize, RGB,
+};
 use emergent_presentation::Presentation;
 use emergent_presenter::input_processor::Tap;
 use emergent_presenter::{
-    Direction, IndexMappable, InputProcessor, Item, Reducible, View, ViewBuilder,
+    AsData, Direction, IndexAccessible, IndexMappable, InputProcessor, Item, Reducible, View,
+    ViewBuilder,
 };
+use std::collections::HashSet;
+use std::iter;
 
-impl TestCapture {
-    pub fn present(&self, mut b: ViewBuilder<Msg>, show_contents: bool) -> View<Msg> {
-        let nested = b.scoped(&self.name, |mut b| {
-            let header = Item::new(&self.name).map(|mut b, name| {
+impl PackageCaptures {
+    /// Presents the package with its pass / fail totals and, if not collapsed, the captures of
+    /// all its targets that are selected by the filter.
+    ///
+    /// - `timeline` Returns the outcomes of a test in the recorded runs.
+    pub fn present(
+        &self,
+        mut b: ViewBuilder<Msg>,
+        show_contents: bool,
+        collapsed_tests: &HashSet<TestId>,
+        timeline: &dyn Fn(&TestId) -> Timeline,
+        filter: Option<&TestFilter>,
+    ) -> View<Msg> {
+        let nested = b.scoped(&self.package, |b| {
+            let header = Item::new(&self.package).map(|mut b, name| {
                 let name = name.to_string();
-                let presentation = Self::present_header(&name).in_area();
+                let presentation = self.present_header().in_area();
                 b.use_input_processor(|| {
-                    Tap::new().map(move |_| Some(Msg::ToggleTestcase { name: name.clone() }))
+                    Tap::new().map(move |_| Some(Msg::TogglePackage { name: name.clone() }))
                 });
                 b.present(presentation)
             });
@@ -26,15 +49,425 @@ impl TestCapture {
                 return header.reduce(b, ());
             }
 
-            let contents =
-                Item::new(&self.output).map(|b, output| b.present(Self::present_output(output)));
+            // targets without selected tests are hidden.
+            let targets: Vec<&TargetCaptures> = self
+                .targets
+                .iter()
+                .filter(|t| t.captures.0.iter().any(|c| is_selected(filter, &c.name)))
+                .collect();
+            let targets = targets.as_data().map_view(|b, target| {
+                target.present(b, &self.package, collapsed_tests, timeline, filter)
+            });
+
+            header.extend(&targets).reduce(b, Direction::Column)
+        });
+
+        b.wrapped(nested)
+    }
+
+    fn present_header(&self) -> Presentation {
+        let totals = self.totals();
+        let header_font = &Font::new("", font::Style::NORMAL, font::Size::new(28.0));
+
+        let totals_color = if totals.failed != 0 {
+            0xff0000.rgb()
+        } else {
+            0x00a000.rgb()
+        };
+
+        let mut block = text_block(header_font, None);
+        block.text(&self.package, font::Style::BOLD).text(
+            format!(
+                "  {} passed, {} failed, {} ignored",
+                totals.passed, totals.failed, totals.ignored
+            ),
+            totals_color,
+        );
+
+        let mut drawing = Drawing::new();
+        drawing.draw(block, paint());
+        drawing.into()
+    }
+}
+
+impl TargetCaptures {
+    /// Presents the captures of a target that are selected by the filter below a header that
+    /// shows the target's name.
+    ///
+    /// The captures of the other variants of the test environment are shown in one row with the
+    /// expanded captures.
+    pub fn present(
+        &self,
+        mut b: ViewBuilder<Msg>,
+        package: &str,
+        collapsed_tests: &HashSet<TestId>,
+        timeline: &dyn Fn(&TestId) -> Timeline,
+        filter: Option<&TestFilter>,
+    ) -> View<Msg> {
+        let title = self.target.to_string();
+        let nested = b.scoped(&title, |b| {
+            let header = Item::new(&title).map(|b, title| b.present(Self::present_header(title)));
+
+            let captures: Vec<&TestCapture> = self
+                .captures
+                .0
+                .iter()
+                .filter(|c| is_selected(filter, &c.name))
+                .collect();
+            let captures = captures.as_data().map_view(|b, capture| {
+                let test = TestId {
+                    package: package.into(),
+                    target: self.target.clone(),
+                    name: capture.name.clone(),
+                };
+                let show_contents = !collapsed_tests.contains(&test);
+                let timeline = timeline(&test);
+                let primary = Item::new(*capture)
+                    .map(|b, capture| capture.present(b, show_contents, &timeline, &test, true));
+                let variants: Vec<(&TestVariant, &TestCapture)> = if show_contents {
+                    self.variants_of(&capture.name).collect()
+                } else {
+                    Vec::new()
+                };
+                let variants = Item::new(&variants)
+                    .map(|b, variants| TestCapture::present_variants(b, variants));
+                primary.extend(&variants).reduce(b, Direction::Row)
+            });
 
-            header.extend(&contents).reduce(b, Direction::Column)
+            header.extend(&captures).reduce(b, Direction::Column)
         });
 
         b.wrapped(nested)
     }
 
+    fn present_header(title: &str) -> Presentation {
+        let header_font = &Font::new("", font::Style::BOLD, font::Size::new(24.0));
+        let mut drawing = Drawing::new();
+        let text = text(title, header_font, None);
+        drawing.draw_shape(&text.into(), paint());
+        drawing.into()
+    }
+}
+
+impl TestCapture {
+    /// Presents the capture and the outcomes of the test in the runs of the `timeline`.
+    ///
+    /// Tapping the name of the test collapses or expands it. If the test is `runnable`, buttons
+    /// next to its name run it again, once or repeatedly.
+    pub fn present(
+        &self,
+        mut b: ViewBuilder<Msg>,
+        show_contents: bool,
+        timeline: &[(usize, RunOutcome)],
+        test: &TestId,
+        runnable: bool,
+    ) -> View<Msg> {
+        let nested = b.scoped(&self.name, |mut b| {
+            let header = Item::new(&self.name).map(|b, name| {
+                let title = Item::new(name).map(|mut b, name| {
+                    let test = test.clone();
+                    let presentation = Self::present_header(name).in_area();
+                    b.use_input_processor(|| {
+                        Tap::new().map(move |_| Some(Msg::ToggleTestcase(test.clone())))
+                    });
+                    b.present(presentation)
+                });
+                let run = Item::new(test).map(|b, test| {
+                    if runnable {
+                        Self::present_run(b, test)
+                    } else {
+                        b.present(Presentation::Empty)
+                    }
+                });
+                let timeline = timeline
+                    .as_data()
+                    .map_view(|b, (run, outcome)| Self::present_outcome(b, *run, *outcome));
+                title
+                    .extend(&run)
+                    .extend(&timeline)
+                    .reduce(b, Direction::Row)
+            });
+
+            if !show_contents {
+                return header.reduce(b, ());
+            }
+
+            let failure = Item::new(&self.failure).map(|b, failure| match failure {
+                Some(failure) => Self::present_failure(b, failure),
+                None => b.present(Presentation::Empty),
+            });
+
+            let bench = Item::new(&self.result).map(|b, result| match result {
+                TestResult::Bench(result) => b.present(Self::present_text(&result.to_string())),
+                _ => b.present(Presentation::Empty),
+            });
+
+            let accept = Item::new(self).map(|b, capture| capture.present_accept_snapshots(b));
+
+            let artifact_rows = self.artifact_rows();
+            let rows = artifact_rows
+                .as_data()
+                .map_view(|b, row| Self::present_artifact_row(b, row));
+
+            // Output that does not contain artifacts is shown below the artifacts.
+            let output =
+                Item::new(&self.output).map(|b, output| b.present(Self::present_text(output)));
+
+            header
+                .extend(&failure)
+                .extend(&bench)
+                .extend(&accept)
+                .extend(&rows)
+                .extend(&output)
+                .reduce(b, Direction::Column)
+        });
+
+        b.wrapped(nested)
+    }
+
+    /// Returns all the drawings that were rendered by the test.
+    pub fn drawings(&self) -> Vec<&Drawing> {
+        self.artifacts.iter().filter_map(|a| a.drawing()).collect()
+    }
+
+    /// Sets the font family of the texts in all drawings that use the default font.
+    pub fn set_default_font_family(&mut self, family: &str) {
+        for artifact in &mut self.artifacts {
+            if let Artifact::Drawing { drawing, .. } = artifact {
+                drawing.set_default_font_family(family)
+            }
+        }
+    }
+
+    /// Groups the artifacts into rows, so that consecutive drawings are shown side by side.
+    ///
+    /// Drawings are paired with the comparisons with their snapshots.
+    fn artifact_rows(&self) -> Vec<Vec<ArtifactWithSnapshot>> {
+        let mut snapshots = self.snapshots.iter();
+        let mut rows: Vec<Vec<ArtifactWithSnapshot>> = Vec::new();
+        for artifact in &self.artifacts {
+            let is_drawing = artifact.drawing().is_some();
+            let entry = ArtifactWithSnapshot {
+                artifact,
+                snapshot: if is_drawing { snapshots.next() } else { None },
+            };
+            match rows.last_mut() {
+                Some(row) if is_drawing && row[0].artifact.drawing().is_some() => row.push(entry),
+                _ => rows.push(vec![entry]),
+            }
+        }
+        rows
+    }
+
+    /// Presents the panic message, the differences of the compared values, and the location of
+    /// the failure.
+    fn present_failure(b: ViewBuilder<Msg>, failure: &FailureDetails) -> View<Msg> {
+        let message = Item::new(failure).map(|b, failure| {
+            let font = &artifact_font();
+            let mut block = text_block(font, None);
+            block.text(&failure.message, 0xc00000.rgb());
+            if let Some(comparison) = &failure.comparison {
+                let (prefix, left, right, suffix) = comparison.split();
+                let highlighted = |color| {
+                    let mut properties = text_properties();
+                    properties.color = Some(color);
+                    properties.style = Some(font::Style::BOLD);
+                    properties
+                };
+                block
+                    .text("\n\n  left: ", ())
+                    .text(prefix, ())
+                    .text(left, highlighted(0xc00000.rgb()))
+                    .text(suffix, ())
+                    .text("\n right: ", ())
+                    .text(prefix, ())
+                    .text(right, highlighted(0x00a000.rgb()))
+                    .text(suffix, ());
+            }
+            let mut drawing = Drawing::new();
+            drawing.draw(block, paint());
+            b.present(drawing.into())
+        });
+
+        let location = Item::new(&failure.location).map(|mut b, location| {
+            let location = match location {
+                Some(location) => location.clone(),
+                None => return b.present(Presentation::Empty),
+            };
+            let font = &artifact_font();
+            let mut drawing = Drawing::new();
+            drawing.draw(
+                text(format!("at {}", location), font, None),
+                paint().color(0x0000c0.rgb()),
+            );
+            let presentation = Presentation::from(drawing).in_area();
+            b.use_input_processor(|| {
+                Tap::new().map(move |_| Some(Msg::OpenLocation(location.clone())))
+            });
+            b.present(presentation)
+        });
+
+        message.extend(&location).reduce(b, Direction::Column)
+    }
+
+    /// Presents a button that accepts the drawings as snapshots if some are new or have changed.
+    fn present_accept_snapshots(&self, mut b: ViewBuilder<Msg>) -> View<Msg> {
+        let path = match self.snapshots.first() {
+            Some(first) if self.has_pending_snapshots() => first.path.clone(),
+            _ => return b.present(Presentation::Empty),
+        };
+
+        let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+        let mut drawing = Drawing::new();
+        drawing.draw(
+            text("[ accept snapshots ]", font, None),
+            paint().color(0x0000c0.rgb()),
+        );
+        let presentation = Presentation::from(drawing).in_area();
+        b.use_input_processor(|| {
+            Tap::new().map(move |_| Some(Msg::AcceptSnapshots { path: path.clone() }))
+        });
+        b.present(presentation)
+    }
+
+    fn present_artifact_row(b: ViewBuilder<Msg>, row: &[ArtifactWithSnapshot]) -> View<Msg> {
+        row.as_data()
+            .map_view(|b, entry| Self::present_artifact(b, entry))
+            .reduce(b, Direction::Row)
+    }
+
+    fn present_artifact(b: ViewBuilder<Msg>, entry: &ArtifactWithSnapshot) -> View<Msg> {
+        let header = Item::new(entry).map(|b, entry| {
+            let title = match entry.snapshot.map(|s| &s.status) {
+                Some(SnapshotStatus::New) => format!("{} (new)", entry.artifact.title()),
+                Some(SnapshotStatus::Changed { .. }) => {
+                    format!("{} (changed)", entry.artifact.title())
+                }
+                _ => entry.artifact.title().to_string(),
+            };
+            b.present(Self::present_artifact_header(&title))
+        });
+        let contents = Item::new(entry).map(|b, entry| match entry.artifact {
+            Artifact::Drawing { drawing, .. } => match entry.snapshot.map(|s| &s.status) {
+                Some(SnapshotStatus::Changed { recorded }) => {
+                    Self::present_snapshot_change(b, recorded, drawing)
+                }
+                _ => b.present(drawing.clone().into()),
+            },
+            Artifact::Text { text, .. } => b.present(Self::present_text(text)),
+            Artifact::Table { rows, .. } => Self::present_table(b, rows),
+        });
+
+        header.extend(&contents).reduce(b, Direction::Column)
+    }
+
+    /// Presents the drawings of the test in other variants of the test environment side by side.
+    ///
+    /// Each variant is titled with its DPI, font, and color scheme, and its drawings are shown on
+    /// the background of its color scheme.
+    fn present_variants(
+        b: ViewBuilder<Msg>,
+        variants: &[(&TestVariant, &TestCapture)],
+    ) -> View<Msg> {
+        variants
+            .as_data()
+            .map_view(|b, (variant, capture)| {
+                let failed = TestTotals::from_captures(iter::once(*capture)).failed != 0;
+                let header = Item::new(*variant).map(|b, variant| {
+                    let font = &Font::new("", font::Style::ITALIC, font::Size::new(16.0));
+                    let color = if failed {
+                        0xff0000.rgb()
+                    } else {
+                        0x000000.rgb()
+                    };
+                    let mut drawing = Drawing::new();
+                    drawing.draw(text(variant.to_string(), font, None), paint().color(color));
+                    b.present(drawing.into())
+                });
+                let drawings = Item::new(*capture).map(|b, capture| {
+                    let background = variant.color_scheme.background();
+                    let drawings: Vec<Drawing> = capture
+                        .drawings()
+                        .into_iter()
+                        .map(|drawing| on_background(drawing, background, &**b.support()))
+                        .collect();
+                    drawings
+                        .as_data()
+                        .map_view(|b, drawing| b.present(drawing.clone().into()))
+                        .reduce(b, Direction::Column)
+                });
+                header.extend(&drawings).reduce(b, Direction::Column)
+            })
+            .reduce(b, Direction::Row)
+    }
+
+    /// Presents the recorded and the new drawing and their differences side by side.
+    fn present_snapshot_change(
+        b: ViewBuilder<Msg>,
+        recorded: &Drawing,
+        new: &Drawing,
+    ) -> View<Msg> {
+        let changes = DrawingDiff::new(recorded, new).visualize(&b);
+        let sides = vec![
+            ("recorded", recorded.clone()),
+            ("new", new.clone()),
+            ("changes", changes),
+        ];
+        sides
+            .as_data()
+            .map_view(|b, (title, drawing)| {
+                let header = Item::new(title)
+                    .map(|b, title| b.present(Self::present_artifact_header(title)));
+                let drawing =
+                    Item::new(drawing).map(|b, drawing| b.present(drawing.clone().into()));
+                header.extend(&drawing).reduce(b, Direction::Column)
+            })
+            .reduce(b, Direction::Row)
+    }
+
+    /// Presents a button that runs the test again and one that runs it repeatedly to find out if
+    /// it is flaky.
+    fn present_run(b: ViewBuilder<Msg>, test: &TestId) -> View<Msg> {
+        let run = Item::new(test).map(|mut b, test| {
+            let test = test.clone();
+            let presentation = Self::present_button("  [ run ]");
+            b.use_input_processor(|| Tap::new().map(move |_| Some(Msg::RunTest(test.clone()))));
+            b.present(presentation)
+        });
+        let stress = Item::new(test).map(|mut b, test| {
+            let test = test.clone();
+            let presentation = Self::present_button("  [ stress ]  ");
+            b.use_input_processor(|| Tap::new().map(move |_| Some(Msg::StressTest(test.clone()))));
+            b.present(presentation)
+        });
+        run.extend(&stress).reduce(b, Direction::Row)
+    }
+
+    fn present_button(label: &str) -> Presentation {
+        let font = &Font::new("", font::Style::BOLD, font::Size::new(16.0));
+        let mut drawing = Drawing::new();
+        drawing.draw(text(label, font, None), paint().color(0x0000c0.rgb()));
+        Presentation::from(drawing).in_area()
+    }
+
+    /// Presents the outcome of the test in a run as a small square that selects the run when it
+    /// is tapped.
+    fn present_outcome(mut b: ViewBuilder<Msg>, run: usize, outcome: RunOutcome) -> View<Msg> {
+        let color = match outcome {
+            RunOutcome::Passed => 0x00a000.rgb(),
+            RunOutcome::Failed => 0xff0000.rgb(),
+            RunOutcome::DrawingChanged => 0xffa000.rgb(),
+            RunOutcome::Ignored => 0xa0a0a0.rgb(),
+            RunOutcome::NotRun => 0xe0e0e0.rgb(),
+        };
+        let mut drawing = Drawing::new();
+        // the square is drawn with a margin, so that squares of consecutive runs are separated.
+        drawing.draw(rect((4.0, 4.0), (12.0, 12.0)), paint().color(color));
+        let presentation = Presentation::from(drawing).in_area();
+        b.use_input_processor(|| Tap::new().map(move |_| Some(Msg::SelectRun(Some(run)))));
+        b.present(presentation)
+    }
+
     fn present_header(title: &str) -> Presentation {
         let header_font = &Font::new("", font::Style::NORMAL, font::Size::new(20.0));
         let mut drawing = Drawing::new();
@@ -43,22 +476,76 @@ impl TestCapture {
         drawing.into()
     }
 
-    fn present_output(output: &str) -> Presentation {
-        // TODO: render invalid output as text and mark it appropriately
-        if !output.starts_with("> ") {
+    fn present_artifact_header(title: &str) -> Presentation {
+        let header_font = &Font::new("", font::Style::ITALIC, font::Size::new(16.0));
+        let mut drawing = Drawing::new();
+        let text = text(title, header_font, None);
+        drawing.draw_shape(&text.into(), paint());
+        drawing.into()
+    }
+
+    fn present_text(text: &str) -> Presentation {
+        if text.is_empty() {
             return Drawing::new().into();
-        };
+        }
 
-        // TODO: handle parse errors:
-        let drawing: Drawing = serde_json::from_str(&output[2..]).unwrap();
+        let mut block = text_block(&artifact_font(), None);
+        block.text(text, ());
+        let mut drawing = Drawing::new();
+        drawing.draw(block, paint());
         drawing.into()
     }
+
+    /// Presents a table as a column of keys and a column of values.
+    fn present_table(b: ViewBuilder<Msg>, rows: &[(String, String)]) -> View<Msg> {
+        let keys: Vec<String> = rows.iter().map(|(k, _)| format!("{}   ", k)).collect();
+        let values: Vec<&str> = rows.iter().map(|(_, v)| v.as_str()).collect();
+        let columns = vec![keys.join("\n"), values.join("\n")];
+
+        columns
+            .as_data()
+            .map_view(|b, column| b.present(Self::present_text(column)))
+            .reduce(b, Direction::Row)
+    }
+}
+
+/// An artifact and, if it is a drawing, the comparison with its snapshot.
+struct ArtifactWithSnapshot<'a> {
+    artifact: &'a Artifact,
+    snapshot: Option<&'a SnapshotComparison>,
+}
+
+/// Puts the drawing on a background that covers its bounds.
+fn on_background(drawing: &Drawing, background: Color, measure: &dyn MeasureText) -> Drawing {
+    let mut below = Drawing::new();
+    if let Some(bounds) = drawing.fast_bounds(measure).as_bounds() {
+        below.draw(Rect::from(*bounds), paint().color(background));
+    }
+    below.below(drawing.clone())
+}
+
+/// Returns `true` if the filter selects the test with the name, all tests are selected if there
+/// is no filter.
+fn is_selected(filter: Option<&TestFilter>, name: &str) -> bool {
+    filter.map_or(true, |filter| filter.matches(name))
+}
+
+/// The font text and table artifacts are shown in.
+fn artifact_font() -> Font {
+    Font::new(
+        "JetBrains Mono",
+        font::Style::default(),
+        font::Size::new(14.0),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use crate::libtest::{TestCapture, TestResult};
     use crate::skia::test_environment::view_builder;
+    use crate::test_history::RunOutcome;
+    use crate::test_runner::{TestId, TestTarget};
+    use emergent_drawing::artifact::Artifact;
     use emergent_drawing::functions::rect;
     use emergent_drawing::{Drawing, DrawingTarget, Paint, Render, Visualize, RGB};
 
@@ -66,24 +553,54 @@ mod tests {
     fn capture_presentations() {
         let b = view_builder::from_test_environment();
 
-        let output = {
-            let mut drawing = Drawing::new();
-            drawing.draw(rect((0, 0), (64, 64)), Paint::stroke(0x0023_5689.rgb()));
-            format!("> {}", serde_json::to_string(&drawing).unwrap())
-        };
+        let mut drawing = Drawing::new();
+        drawing.draw(rect((0, 0), (64, 64)), Paint::stroke(0x0023_5689.rgb()));
 
-        let capture = TestCapture {
-            name: "[test-name (open)]".to_string(),
-            result: TestResult::Ok(),
-            output,
-        };
+        let artifacts = [
+            Artifact::Drawing {
+                label: Some("before".into()),
+                drawing: drawing.clone(),
+            },
+            Artifact::Drawing {
+                label: Some("after".into()),
+                drawing,
+            },
+            Artifact::Text {
+                label: None,
+                text: "text artifact".into(),
+            },
+            Artifact::Table {
+                label: Some("metrics".into()),
+                rows: vec![
+                    ("width".into(), "64".into()),
+                    ("height".into(), "64".into()),
+                ],
+            },
+        ];
+
+        let mut output = String::new();
+        for artifact in artifacts.iter() {
+            output.push_str(&artifact.to_line().unwrap());
+            output.push('\n');
+        }
+        output.push_str("plain output\n");
+
+        let capture = TestCapture::new("[test-name (open)]", TestResult::Ok(), &output);
+        assert_eq!(capture.artifacts, artifacts);
+        assert_eq!(capture.output, "plain output\n");
 
         // TODO: a more direct way to visualize views would be nice, it's a bit confusing to have to clone
         //       support from context before it is consumed.
 
         let support = b.support().clone();
 
-        let view = capture.present(b, true);
+        let test = TestId {
+            package: "package".into(),
+            target: TestTarget::Lib,
+            name: capture.name.clone(),
+        };
+        let timeline = [(1, RunOutcome::Failed), (2, RunOutcome::Passed)];
+        let view = capture.present(b, true, &timeline, &test, true);
         // TODO: this &* is counter-intuitive too (comes from the Rc wrapper).
         view.into_presentation().visualize(&*support).render();
     }
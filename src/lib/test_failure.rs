@@ -0,0 +1,215 @@
+//! Details about why a test failed, parsed from the panic message libtest reports.
+
+use crate::libtest::{ExtraData, TestCapture, TestResult};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The panic message, its location, and the compared values of a failed assertion.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct FailureDetails {
+    pub message: String,
+    pub location: Option<SourceLocation>,
+    pub comparison: Option<Comparison>,
+}
+
+/// A location in a source file, lines and columns start at 1.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The `left` and `right` values of a failed `assert_eq!` or `assert_ne!`.
+#[derive(Clone, PartialEq, Eq, Serialize, Debug)]
+pub struct Comparison {
+    pub left: String,
+    pub right: String,
+}
+
+const PANICKED_AT: &str = " panicked at ";
+
+impl FailureDetails {
+    /// Returns the details of a failed capture.
+    ///
+    /// Relative source locations are resolved against `workspace_root`.
+    pub fn from_capture(capture: &TestCapture, workspace_root: &Path) -> Option<FailureDetails> {
+        let details = match &capture.result {
+            TestResult::Failed(Some(ExtraData::Message(message))) => {
+                Self::parse(message).or_else(|| Some(Self::from_message(message)))
+            }
+            TestResult::Failed(Some(ExtraData::StdOut(stdout))) => Self::parse(stdout),
+            TestResult::Failed(None) => Self::parse(&capture.output),
+            _ => None,
+        }?;
+
+        Some(details.resolved(workspace_root))
+    }
+
+    fn from_message(message: &str) -> FailureDetails {
+        FailureDetails {
+            message: message.trim_end().to_owned(),
+            location: None,
+            comparison: None,
+        }
+    }
+
+    /// Parses the first panic in the output of a test.
+    ///
+    /// Supports the format `panicked at 'message', file:line:col` as well as the newer format
+    /// `panicked at file:line:col:` that puts the message on the following lines.
+    pub fn parse(output: &str) -> Option<FailureDetails> {
+        let start = output.find(PANICKED_AT)? + PANICKED_AT.len();
+        let rest = &output[start..];
+
+        let (message, location) = if rest.starts_with('\'') {
+            let rest = &rest[1..];
+            rest.match_indices("', ")
+                .find_map(|(i, _)| {
+                    let line = rest[i + 3..].lines().next()?;
+                    let location = SourceLocation::parse(line)?;
+                    Some((&rest[..i], Some(location)))
+                })
+                .unwrap_or((rest.lines().next().unwrap_or_default(), None))
+        } else {
+            let mut lines = rest.splitn(2, '\n');
+            let location = lines
+                .next()
+                .and_then(|l| SourceLocation::parse(l.trim_end().trim_end_matches(':')));
+            let message = lines.next().unwrap_or_default();
+            let end = message
+                .find("\nnote: ")
+                .or_else(|| message.find("\nstack backtrace:"))
+                .unwrap_or_else(|| message.len());
+            (&message[..end], location)
+        };
+
+        Some(FailureDetails {
+            message: message.trim_end().to_owned(),
+            location,
+            comparison: Comparison::parse(message),
+        })
+    }
+
+    fn resolved(self, root: &Path) -> FailureDetails {
+        FailureDetails {
+            location: self.location.map(|l| SourceLocation {
+                file: root.join(l.file),
+                ..l
+            }),
+            ..self
+        }
+    }
+}
+
+impl SourceLocation {
+    /// Parses a location in the form `file:line:col`.
+    pub fn parse(str: &str) -> Option<SourceLocation> {
+        let mut parts = str.rsplitn(3, ':');
+        let column = parts.next()?.parse().ok()?;
+        let line = parts.next()?.parse().ok()?;
+        let file = parts.next()?;
+        if file.is_empty() {
+            return None;
+        }
+        Some(SourceLocation {
+            file: file.into(),
+            line,
+            column,
+        })
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+impl Comparison {
+    /// Parses the `left` and `right` lines of an assertion message.
+    fn parse(message: &str) -> Option<Comparison> {
+        let value = |prefix: &str| {
+            message.lines().find_map(|l| {
+                let l = l.trim_start();
+                if l.starts_with(prefix) {
+                    // older versions of Rust put the values in backticks and separate them by a
+                    // comma.
+                    let v = l[prefix.len()..].trim_end_matches(',');
+                    Some(v.trim_matches('`').to_owned())
+                } else {
+                    None
+                }
+            })
+        };
+
+        Some(Comparison {
+            left: value("left: ")?,
+            right: value("right: ")?,
+        })
+    }
+
+    /// Splits the values into their common prefix, the differing parts of left and right, and
+    /// their common suffix.
+    pub fn split(&self) -> (&str, &str, &str, &str) {
+        let prefix = common_prefix_len(self.left.chars(), self.right.chars());
+        let (left, right) = (&self.left[prefix..], &self.right[prefix..]);
+        let suffix = common_prefix_len(left.chars().rev(), right.chars().rev());
+        (
+            &self.left[..prefix],
+            &left[..left.len() - suffix],
+            &right[..right.len() - suffix],
+            &left[left.len() - suffix..],
+        )
+    }
+}
+
+/// Returns the length in bytes of the common prefix of two char sequences.
+fn common_prefix_len(a: impl Iterator<Item = char>, b: impl Iterator<Item = char>) -> usize {
+    a.zip(b)
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, FailureDetails, SourceLocation};
+
+    #[test]
+    fn parse_assert_eq_panic() {
+        let output = "thread 'tests::compare' panicked at 'assertion failed: `(left == right)`\n  left: `\"abc\"`,\n right: `\"axc\"`', src/lib.rs:10:5\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace.\n";
+        let details = FailureDetails::parse(output).unwrap();
+        assert_eq!(
+            details.location,
+            Some(SourceLocation {
+                file: "src/lib.rs".into(),
+                line: 10,
+                column: 5
+            })
+        );
+        let comparison = details.comparison.unwrap();
+        assert_eq!(comparison.left, "\"abc\"");
+        assert_eq!(comparison.right, "\"axc\"");
+        assert_eq!(comparison.split(), ("\"a", "b", "x", "c\""));
+    }
+
+    #[test]
+    fn parse_panic_with_location_first() {
+        let output = "thread 'tests::compare' panicked at src/lib.rs:10:5:\nassertion `left == right` failed\n  left: 1\n right: 2\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        let details = FailureDetails::parse(output).unwrap();
+        assert_eq!(
+            details.message,
+            "assertion `left == right` failed\n  left: 1\n right: 2"
+        );
+        assert_eq!(details.location.unwrap().line, 10);
+        assert_eq!(
+            details.comparison,
+            Some(Comparison {
+                left: "1".into(),
+                right: "2".into()
+            })
+        );
+    }
+}
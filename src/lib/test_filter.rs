@@ -0,0 +1,97 @@
+//! Filters that select tests by their names.
+
+use serde::{Deserialize, Serialize};
+
+/// Selects tests by their names.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub enum TestFilter {
+    /// Selects tests with names that contain the pattern, or, if the pattern contains the
+    /// wildcards `*` or `?`, that match it as a glob.
+    Pattern(String),
+    /// Selects the test with exactly that name.
+    Exact(String),
+}
+
+impl TestFilter {
+    /// Creates a filter from a pattern, returns `None` if the pattern is empty.
+    pub fn pattern(pattern: &str) -> Option<TestFilter> {
+        let pattern = pattern.trim();
+        if pattern.is_empty() {
+            return None;
+        }
+        Some(TestFilter::Pattern(pattern.to_owned()))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            TestFilter::Pattern(pattern) if is_glob(pattern) => {
+                let pattern: Vec<char> = pattern.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                glob_matches(&pattern, &name)
+            }
+            TestFilter::Pattern(pattern) => name.contains(pattern.as_str()),
+            TestFilter::Exact(exact) => name == exact,
+        }
+    }
+
+    /// The arguments that make libtest run the selected tests.
+    ///
+    /// libtest supports substring filters only, so globs are passed as their longest literal
+    /// part. This runs more tests than selected, which are then filtered by `matches()`.
+    pub fn libtest_args(&self) -> Vec<String> {
+        match self {
+            TestFilter::Pattern(pattern) if is_glob(pattern) => {
+                let literal = pattern
+                    .split(|c| c == '*' || c == '?')
+                    .max_by_key(|part| part.len())
+                    .unwrap_or_default();
+                if literal.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![literal.to_owned()]
+                }
+            }
+            TestFilter::Pattern(pattern) => vec![pattern.clone()],
+            TestFilter::Exact(exact) => vec![exact.clone(), "--exact".into()],
+        }
+    }
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(|c| c == '*' || c == '?')
+}
+
+/// Matches a name against a glob pattern in which `*` matches any sequence of characters and `?`
+/// matches a single character.
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_matches(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_matches(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && glob_matches(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TestFilter;
+
+    #[test]
+    fn substring_and_glob_filters() {
+        let substring = TestFilter::pattern(" capture ").unwrap();
+        assert!(substring.matches("tests::test_in_mod_capture"));
+        assert!(!substring.matches("tests::env"));
+        assert_eq!(substring.libtest_args(), vec!["capture"]);
+
+        let glob = TestFilter::pattern("tests::*_capture").unwrap();
+        assert!(glob.matches("tests::test_in_mod_capture"));
+        assert!(!glob.matches("test_output_capture"));
+        assert_eq!(glob.libtest_args(), vec!["_capture"]);
+
+        let exact = TestFilter::Exact("tests::env".into());
+        assert!(!exact.matches("tests::env2"));
+        assert_eq!(exact.libtest_args(), vec!["tests::env", "--exact"]);
+
+        assert_eq!(TestFilter::pattern("  "), None);
+    }
+}
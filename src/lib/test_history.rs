@@ -0,0 +1,314 @@
+//! A bounded history of the test runs of a testrunner session.
+
+use crate::libtest::{BenchResult, TestCapture, TestResult};
+use crate::test_runner::{TestId, TestRunResult};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A completed test run.
+#[derive(Debug)]
+pub struct TestRunRecord {
+    /// The number of the run in the session, starting at 1.
+    pub number: usize,
+    /// The time the run was started.
+    pub started: SystemTime,
+    /// The name of the run configuration the tests were run with.
+    pub configuration: String,
+    /// The files that changed and triggered the run, empty if the run was triggered otherwise.
+    pub changed_files: Vec<PathBuf>,
+    pub result: TestRunResult,
+}
+
+/// The outcome of a test in one run.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RunOutcome {
+    Passed,
+    Failed,
+    /// The test passed, but its drawings changed compared to the previous run or its snapshots.
+    DrawingChanged,
+    Ignored,
+    /// The test was not part of the run.
+    NotRun,
+}
+
+/// The outcomes of a test in a number of runs, paired with the run numbers.
+pub type Timeline = Vec<(usize, RunOutcome)>;
+
+/// The measurements of a benchmark in a number of runs, paired with the run numbers.
+pub type BenchSeries = Vec<(usize, BenchResult)>;
+
+/// The runs of a session, the oldest runs are dropped when the capacity is exceeded.
+#[derive(Debug)]
+pub struct TestHistory {
+    runs: VecDeque<TestRunRecord>,
+    capacity: usize,
+    next_number: usize,
+}
+
+impl Default for TestHistory {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl TestHistory {
+    pub const DEFAULT_CAPACITY: usize = 20;
+
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "the capacity of a test history must not be zero"
+        );
+        TestHistory {
+            runs: VecDeque::new(),
+            capacity,
+            next_number: 1,
+        }
+    }
+
+    /// Records a completed run and returns its number.
+    pub fn push(
+        &mut self,
+        started: SystemTime,
+        configuration: String,
+        changed_files: Vec<PathBuf>,
+        result: TestRunResult,
+    ) -> usize {
+        let number = self.next_number;
+        self.next_number += 1;
+        if self.runs.len() == self.capacity {
+            self.runs.pop_front();
+        }
+        self.runs.push_back(TestRunRecord {
+            number,
+            started,
+            configuration,
+            changed_files,
+            result,
+        });
+        number
+    }
+
+    /// The recorded runs, the oldest first.
+    pub fn runs(&self) -> impl DoubleEndedIterator<Item = &TestRunRecord> {
+        self.runs.iter()
+    }
+
+    pub fn latest(&self) -> Option<&TestRunRecord> {
+        self.runs.back()
+    }
+
+    pub fn latest_mut(&mut self) -> Option<&mut TestRunRecord> {
+        self.runs.back_mut()
+    }
+
+    /// The latest run with the named run configuration.
+    pub fn latest_of(&self, configuration: &str) -> Option<&TestRunRecord> {
+        self.runs
+            .iter()
+            .rev()
+            .find(|r| r.configuration == configuration)
+    }
+
+    /// The latest run before the numbered run with the named run configuration.
+    pub fn previous_of(&self, number: usize, configuration: &str) -> Option<&TestRunRecord> {
+        self.runs
+            .iter()
+            .rev()
+            .find(|r| r.number < number && r.configuration == configuration)
+    }
+
+    pub fn get(&self, number: usize) -> Option<&TestRunRecord> {
+        self.runs.iter().find(|r| r.number == number)
+    }
+
+    pub fn get_mut(&mut self, number: usize) -> Option<&mut TestRunRecord> {
+        self.runs.iter_mut().find(|r| r.number == number)
+    }
+
+    /// Returns the outcomes of a test in the recorded runs with the named run configuration,
+    /// paired with the run numbers.
+    pub fn timeline(&self, test: &TestId, configuration: &str) -> Timeline {
+        let mut previous: Option<&TestCapture> = None;
+        self.runs
+            .iter()
+            .filter(|run| run.configuration == configuration)
+            .map(|run| {
+                let capture = run.result.capture(test);
+                let outcome = match capture {
+                    None => RunOutcome::NotRun,
+                    Some(capture) => {
+                        let outcome = RunOutcome::of(capture, previous);
+                        previous = Some(capture);
+                        outcome
+                    }
+                };
+                (run.number, outcome)
+            })
+            .collect()
+    }
+
+    /// Returns the measurements of a benchmark in the recorded runs with the named run
+    /// configuration it was measured in, paired with the run numbers.
+    pub fn bench_series(&self, test: &str, configuration: &str) -> BenchSeries {
+        self.runs
+            .iter()
+            .filter(|run| run.configuration == configuration)
+            .filter_map(|run| {
+                let capture = run.result.captures().find(|c| c.name == test)?;
+                match capture.result {
+                    TestResult::Bench(result) => Some((run.number, result)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl TestRunRecord {
+    /// The time the run started formatted as `HH:MM:SS UTC`.
+    pub fn time_of_day(&self) -> String {
+        let seconds = self
+            .started
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        format!(
+            "{:02}:{:02}:{:02} UTC",
+            seconds / 3600 % 24,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+impl RunOutcome {
+    /// The outcome of a capture compared to the capture of the same test in the previous run it
+    /// was part of.
+    fn of(capture: &TestCapture, previous: Option<&TestCapture>) -> RunOutcome {
+        match capture.result {
+            TestResult::Ok() | TestResult::AllowedFail | TestResult::Bench(_) => {
+                let drawings_changed = previous
+                    .map(|previous| previous.drawings() != capture.drawings())
+                    .unwrap_or(false);
+                if drawings_changed {
+                    RunOutcome::DrawingChanged
+                } else {
+                    RunOutcome::Passed
+                }
+            }
+            TestResult::SnapshotMismatch => RunOutcome::DrawingChanged,
+            TestResult::Failed(_) | TestResult::Timeout => RunOutcome::Failed,
+            TestResult::Ignored => RunOutcome::Ignored,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RunOutcome, TestHistory};
+    use crate::libtest::{BenchResult, TestCapture, TestCaptures, TestResult};
+    use crate::test_runner::{PackageCaptures, TargetCaptures, TestId, TestRunResult, TestTarget};
+    use std::time::SystemTime;
+
+    fn run_result(captures: Vec<TestCapture>) -> TestRunResult {
+        TestRunResult::TestsCaptured(
+            Vec::new(),
+            vec![PackageCaptures {
+                package: "package".into(),
+                targets: vec![TargetCaptures {
+                    target: TestTarget::Lib,
+                    captures: TestCaptures(captures),
+                    variants: Vec::new(),
+                }],
+            }],
+        )
+    }
+
+    #[test]
+    fn timeline_of_bounded_history() {
+        let mut history = TestHistory::new(4);
+        let ok = || TestCapture::new("test", TestResult::Ok(), "");
+        let failed = || TestCapture::new("test", TestResult::Failed(None), "");
+        let mut push = |configuration: &str, captures| {
+            history.push(
+                SystemTime::now(),
+                configuration.into(),
+                Vec::new(),
+                run_result(captures),
+            )
+        };
+
+        push("debug", vec![failed()]);
+        push("debug", vec![ok()]);
+        push("release", vec![ok()]);
+        push("debug", Vec::new());
+        push("debug", vec![failed()]);
+
+        assert!(history.get(1).is_none());
+        let test = TestId {
+            package: "package".into(),
+            target: TestTarget::Lib,
+            name: "test".into(),
+        };
+        assert_eq!(
+            history.timeline(&test, "debug"),
+            vec![
+                (2, RunOutcome::Passed),
+                (4, RunOutcome::NotRun),
+                (5, RunOutcome::Failed)
+            ]
+        );
+        assert_eq!(history.latest_of("release").unwrap().number, 3);
+        assert_eq!(history.previous_of(5, "debug").unwrap().number, 4);
+        assert!(history.previous_of(2, "debug").is_none());
+
+        // tests of the same name in other targets have their own timelines.
+        let other_target = TestId {
+            target: TestTarget::Test("integration".into()),
+            ..test
+        };
+        assert_eq!(
+            history.timeline(&other_target, "debug"),
+            vec![
+                (2, RunOutcome::NotRun),
+                (4, RunOutcome::NotRun),
+                (5, RunOutcome::NotRun)
+            ]
+        );
+    }
+
+    #[test]
+    fn bench_series_of_configuration() {
+        let mut history = TestHistory::default();
+        let bench = |median| {
+            let result = BenchResult {
+                median,
+                deviation: 10,
+                mib_per_second: None,
+            };
+            TestCapture::new("bench", TestResult::Bench(result), "")
+        };
+        for (configuration, captures) in vec![
+            ("release", vec![bench(100)]),
+            ("debug", vec![bench(1000)]),
+            ("release", Vec::new()),
+            ("release", vec![bench(120)]),
+        ] {
+            history.push(
+                SystemTime::now(),
+                configuration.into(),
+                Vec::new(),
+                run_result(captures),
+            );
+        }
+
+        let medians: Vec<(usize, u64)> = history
+            .bench_series("bench", "release")
+            .into_iter()
+            .map(|(run, result)| (run, result.median))
+            .collect();
+        assert_eq!(medians, vec![(1, 100), (4, 120)]);
+    }
+}
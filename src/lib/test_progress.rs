@@ -0,0 +1,155 @@
+//! The progress of a running test run and its presentation.
+
+use crate::compiler_message::ToDrawing;
+use crate::libtest::{TestCapture, TestTotals};
+use crate::test_runner::{TestId, TestRunProgress};
+use crate::Msg;
+use cargo_metadata::CompilerMessage;
+use emergent_drawing::functions::{paint, rect, text};
+use emergent_drawing::{font, Color, Drawing, DrawingTarget, Font, Paint, RGB};
+use emergent_presentation::Presentation;
+use emergent_presenter::{AsData, Direction, IndexAccessible, IndexMappable, Item, Reducible};
+use emergent_presenter::{View, ViewBuilder};
+use std::collections::HashSet;
+
+/// The frames of the spinner that is shown next to running tests.
+const SPINNER_FRAMES: &[char] = &['|', '/', '-', '\\'];
+
+/// The width of the progress bar.
+const PROGRESS_BAR_WIDTH: f64 = 400.0;
+
+/// The state of a test run that is in progress.
+#[derive(Clone, Default, Debug)]
+pub struct TestProgress {
+    /// The package that is being compiled.
+    compiling: Option<String>,
+    /// The compiler messages received so far.
+    compiler_messages: Vec<CompilerMessage>,
+    /// The number of tests of all suites that have started.
+    test_count: usize,
+    /// The names of the tests that are running.
+    running: Vec<String>,
+    /// The captures of the tests that finished, in the order they finished.
+    finished: Vec<(TestId, TestCapture)>,
+    /// Advanced with every progress notification.
+    spinner_frame: usize,
+}
+
+impl TestProgress {
+    pub fn update(&mut self, progress: TestRunProgress) {
+        self.spinner_frame = self.spinner_frame.wrapping_add(1);
+        match progress {
+            TestRunProgress::CompilationStarted { package } => self.compiling = Some(package),
+            TestRunProgress::CompilerMessage(message) => self.compiler_messages.push(message),
+            TestRunProgress::SuiteStarted { test_count, .. } => {
+                self.compiling = None;
+                self.test_count += test_count;
+            }
+            TestRunProgress::TestStarted { name, .. } => self.running.push(name),
+            TestRunProgress::TestFinished {
+                package,
+                target,
+                capture,
+            } => {
+                self.running.retain(|name| *name != capture.name);
+                let test = TestId {
+                    package,
+                    target,
+                    name: capture.name.clone(),
+                };
+                self.finished.push((test, capture))
+            }
+            TestRunProgress::SuiteFinished { .. } => self.running.clear(),
+        }
+    }
+
+    /// The fraction of the tests that finished, between 0 and 1.
+    pub fn fraction_finished(&self) -> f64 {
+        if self.test_count == 0 {
+            return 0.0;
+        }
+        (self.finished.len() as f64 / self.test_count as f64).min(1.0)
+    }
+
+    /// Presents a progress bar, the status of the run, the compiler messages, the running tests,
+    /// and the captures of the tests that finished.
+    pub fn present(&self, b: ViewBuilder<Msg>, collapsed_tests: &HashSet<TestId>) -> View<Msg> {
+        let bar = Item::new(self).map(|b, progress| b.present(progress.present_bar()));
+        let status = Item::new(self).map(|b, progress| b.present(progress.present_status()));
+
+        let messages = self
+            .compiler_messages
+            .as_data()
+            .map_view(|b, cm| b.present(cm.to_drawing().into()));
+
+        let spinner = SPINNER_FRAMES[self.spinner_frame % SPINNER_FRAMES.len()];
+        let running = self.running.as_data().map_view(|b, name| {
+            b.present(Self::present_line(
+                &format!("{} {}", spinner, name),
+                0x808080.rgb(),
+            ))
+        });
+
+        let finished = self.finished.as_data().map_view(|b, (test, capture)| {
+            let show_contents = !collapsed_tests.contains(test);
+            capture.present(b, show_contents, &[], test, false)
+        });
+
+        bar.extend(&status)
+            .extend(&messages)
+            .extend(&running)
+            .extend(&finished)
+            .reduce(b, Direction::Column)
+    }
+
+    fn present_bar(&self) -> Presentation {
+        let height = 8.0;
+        let captures = self.finished.iter().map(|(_, capture)| capture);
+        let color = if TestTotals::from_captures(captures).failed != 0 {
+            0xff0000.rgb()
+        } else {
+            0x00a000.rgb()
+        };
+
+        let mut drawing = Drawing::new();
+        drawing.draw(
+            rect((0.0, 0.0), (PROGRESS_BAR_WIDTH, height)),
+            paint().color(0xe0e0e0.rgb()),
+        );
+        drawing.draw(
+            rect(
+                (0.0, 0.0),
+                (PROGRESS_BAR_WIDTH * self.fraction_finished(), height),
+            ),
+            paint().color(color),
+        );
+        drawing.draw(
+            rect((0.0, 0.0), (PROGRESS_BAR_WIDTH, height)),
+            Paint::stroke(0x808080.rgb()),
+        );
+        drawing.into()
+    }
+
+    fn present_status(&self) -> Presentation {
+        let status = match &self.compiling {
+            Some(package) => format!(
+                "compiling {} ({} messages)",
+                package,
+                self.compiler_messages.len()
+            ),
+            None => format!(
+                "running tests: {} of {} finished",
+                self.finished.len(),
+                self.test_count
+            ),
+        };
+        Self::present_line(&status, 0x000000.rgb())
+    }
+
+    fn present_line(line: &str, color: Color) -> Presentation {
+        let font = &Font::new("", font::Style::NORMAL, font::Size::new(16.0));
+        let mut drawing = Drawing::new();
+        drawing.draw(text(line, font, None), paint().color(color));
+        drawing.into()
+    }
+}
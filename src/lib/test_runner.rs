@@ -1,165 +1,1456 @@
-use crate::libtest::TestCaptures;
-use crate::Capture;
-use cargo::core::compiler;
-use cargo::ops;
-use cargo::ops::{FilterRule, LibRule};
-use cargo_metadata::CompilerMessage;
+use crate::libtest::{
+    CaptureParser, ExtraData, SuiteProgress, TestCapture, TestCaptures, TestResult, TestTotals,
+};
+use crate::project_config::RunConfiguration;
+use crate::snapshot;
+use crate::test_failure::FailureDetails;
+use crate::test_filter::TestFilter;
+use cargo_metadata::{CompilerMessage, Message, Metadata, MetadataCommand, Package, Target};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use emergent_drawing::capture_channel;
 use emergent_drawing::FromTestEnvironment;
-use emergent_ui::DPI;
-use std::env;
-use std::io::Cursor;
+use emergent_ui::{ColorScheme, DPI};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{env, fmt, fs, io};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct TestRunRequest {
     pub project_directory: PathBuf,
+    pub packages: PackageSelection,
+    pub targets: TargetSelection,
+    pub timeouts: Timeouts,
+    /// Selects the tests to run by their names, all tests are run if `None`.
+    pub filter: Option<TestFilter>,
+    /// The features, profile, environment, and libtest arguments the tests are run with.
+    pub configuration: RunConfiguration,
 }
 
+/// The time limits of a test run.
+///
+/// Tests that exceed a limit are killed and recorded with the result `TestResult::Timeout`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Timeouts {
+    /// The time limit for running all the tests of a run, compilation excluded.
+    pub run: Option<Duration>,
+    /// The time limit of each individual test.
+    pub test: Option<Duration>,
+}
+
+/// The selection of the packages of a workspace to run tests for.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PackageSelection {
+    /// The package of the manifest in the project directory.
+    Current,
+    /// All members of the workspace.
+    Workspace,
+    /// The workspace members with the given names.
+    Just(Vec<String>),
+}
+
+/// The selection of the targets of a package to run tests for.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TargetSelection {
+    pub lib: bool,
+    /// Integration tests in `tests/`.
+    pub tests: TargetRule,
+    pub bins: TargetRule,
+    pub examples: TargetRule,
+    pub doc: bool,
+}
+
+/// Selects a number of targets of one kind.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TargetRule {
+    None,
+    All,
+    Just(Vec<String>),
+}
+
+/// A resolved target of a package tests are run for.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub enum TestTarget {
+    Lib,
+    Test(String),
+    Bin(String),
+    Example(String),
+    Doc,
+}
+
+/// Identifies a test by its name and the package and target it belongs to.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
+pub struct TestId {
+    pub package: String,
+    pub target: TestTarget,
+    pub name: String,
+}
+
+/// The test captures of all the targets of a package.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct PackageCaptures {
+    pub package: String,
+    pub targets: Vec<TargetCaptures>,
+}
+
+/// The test captures of a single target.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct TargetCaptures {
+    pub target: TestTarget,
+    /// The captures of the primary variant of the test environment.
+    pub captures: TestCaptures,
+    /// The captures of the other variants of the test environment.
+    pub variants: Vec<VariantCaptures>,
+}
+
+/// The test captures of a target in a variant of the test environment.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct VariantCaptures {
+    pub variant: TestVariant,
+    pub captures: TestCaptures,
+}
+
+/// The environment the tests run in.
+///
+/// The tests run once in each variant of the environment. The captures of the primary variant
+/// are compared with the snapshots and recorded in the history, the captures of the other
+/// variants are shown next to them.
 #[derive(Clone, PartialEq, Debug)]
 pub struct TestEnvironment {
+    /// The DPI of the primary variant.
+    pub dpi: DPI,
+    pub matrix: VariantMatrix,
+}
+
+/// The values the tests are run with in addition to the ones of the primary variant.
+///
+/// Every value is combined with all values of the other dimensions.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct VariantMatrix {
+    /// Factors the DPI of the primary variant is scaled with.
+    pub dpi_scales: Vec<f64>,
+    /// Font families that replace the default font.
+    pub font_families: Vec<String>,
+    pub color_schemes: Vec<ColorScheme>,
+}
+
+/// A variant of the test environment.
+#[derive(Clone, PartialEq, Serialize, Debug)]
+pub struct TestVariant {
     pub dpi: DPI,
+    /// The font family that replaces the default font, `None` if the default font is used.
+    pub font_family: Option<String>,
+    pub color_scheme: ColorScheme,
 }
 
 impl FromTestEnvironment for TestEnvironment {
     fn from_test_environment() -> Self {
-        Self {
-            dpi: DPI::from_test_environment(),
-        }
+        Self::new(DPI::from_test_environment())
     }
 }
 
 #[derive(Debug)]
 pub enum TestRunResult {
     CompilationFailed(Vec<CompilerMessage>, failure::Error),
-    TestsCaptured(Vec<CompilerMessage>, TestCaptures),
+    TestsCaptured(Vec<CompilerMessage>, Vec<PackageCaptures>),
+}
+
+/// The progress of a test run, reported while the tests are compiled and run.
+#[derive(Clone, Debug)]
+pub enum TestRunProgress {
+    /// The compilation of the tests of a package started.
+    CompilationStarted {
+        package: String,
+    },
+    /// The compiler emitted a message.
+    CompilerMessage(CompilerMessage),
+    /// The test suite of a target started.
+    SuiteStarted {
+        package: String,
+        target: TestTarget,
+        test_count: usize,
+    },
+    TestStarted {
+        package: String,
+        target: TestTarget,
+        name: String,
+    },
+    TestFinished {
+        package: String,
+        target: TestTarget,
+        capture: TestCapture,
+    },
+    SuiteFinished {
+        package: String,
+        target: TestTarget,
+    },
+}
+
+/// The events of a single test suite.
+enum SuiteEvent {
+    Started {
+        test_count: usize,
+    },
+    TestStarted(String),
+    /// A test finished, the capture contains the artifacts the test emitted.
+    TestFinished(TestCapture),
+    Finished,
 }
 
 impl TestRunRequest {
     /// Creates a new TestRunRequest for the library
     /// in the given cargo project directory.
     pub fn new_lib(project_directory: &Path) -> TestRunRequest {
+        Self::new(project_directory, TargetSelection::lib())
+    }
+
+    /// Creates a new TestRunRequest for a selection of targets in the given cargo project
+    /// directory.
+    pub fn new(project_directory: &Path, targets: TargetSelection) -> TestRunRequest {
         TestRunRequest {
             project_directory: project_directory.to_owned(),
+            packages: PackageSelection::Current,
+            targets,
+            timeouts: Timeouts::default(),
+            filter: None,
+            configuration: RunConfiguration::default(),
+        }
+    }
+
+    /// Returns a request that runs the tests of the selected packages of the workspace.
+    pub fn with_packages(self, packages: PackageSelection) -> TestRunRequest {
+        TestRunRequest { packages, ..self }
+    }
+
+    /// Returns a request that runs the tests with the given time limits.
+    pub fn with_timeouts(self, timeouts: Timeouts) -> TestRunRequest {
+        TestRunRequest { timeouts, ..self }
+    }
+
+    /// Returns a request that runs only the tests selected by the filter.
+    pub fn with_filter(self, filter: Option<TestFilter>) -> TestRunRequest {
+        TestRunRequest { filter, ..self }
+    }
+
+    /// Returns a request that compiles and runs the tests with the configuration.
+    pub fn with_configuration(self, configuration: RunConfiguration) -> TestRunRequest {
+        TestRunRequest {
+            configuration,
+            ..self
         }
     }
 
+    /// Returns a request that runs a single test only.
+    pub fn for_test(&self, test: &TestId) -> TestRunRequest {
+        TestRunRequest {
+            packages: PackageSelection::Just(vec![test.package.clone()]),
+            targets: TargetSelection::only(&test.target),
+            filter: Some(TestFilter::Exact(test.name.clone())),
+            // the compiler messages of single tests are not shown, so they are not linted.
+            configuration: RunConfiguration {
+                clippy: false,
+                ..self.configuration.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    /// The root directory of the workspace of the project, compiler messages refer to source
+    /// files relative to it.
+    pub fn workspace_root(&self) -> Result<PathBuf, failure::Error> {
+        let metadata = MetadataCommand::new()
+            .manifest_path(self.project_directory.join("Cargo.toml"))
+            .no_deps()
+            .exec()?;
+        Ok(metadata.workspace_root)
+    }
+
     pub fn capture_tests(
         &self,
         environment: TestEnvironment,
     ) -> Result<TestRunResult, failure::Error> {
-        let manifest_path = self.project_directory.join("Cargo.toml");
+        self.capture_tests_with_progress(environment, &mut |_| {})
+    }
+
+    /// Compiles a single test once and runs it `runs` times in the primary variant of the
+    /// environment, `record` is called with the capture of each run.
+    ///
+    /// Each run has its own time limit. Doc tests are compiled by rustdoc in every run.
+    pub fn capture_test_repeatedly(
+        &self,
+        test: &TestId,
+        environment: TestEnvironment,
+        runs: usize,
+        record: &mut dyn FnMut(TestCapture) -> Result<(), failure::Error>,
+    ) -> Result<(), failure::Error> {
+        let request = self.for_test(test);
+        let manifest_path = request.project_directory.join("Cargo.toml");
+        let metadata = MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()?;
+        let packages = request.packages.resolve(&metadata, &manifest_path)?;
+        request.targets.check_names(&packages)?;
+        let package = match packages.first() {
+            Some(package) => package,
+            None => failure::bail!("package {} was not found", test.package),
+        };
+        let variants = environment.variants();
+        let primary = &variants[0];
 
-        // TODO: verify if this is correct (taken from cargo::Config::new()).
-        let current_dir = env::current_dir().unwrap();
-        let home_dir = cargo::util::config::homedir(&current_dir).unwrap();
-
-        let (test_result, captured) = {
-            /*
-            this code may be used to capture / supporess the error output (for example Fresh lines)
-
-            let mut output = Vec::new();
-            let mut output_cursor = Cursor::new(output);
-            let boxed_output_cursor: Box<dyn Write> = Box::new(output_cursor);
-            let shell = cargo::core::Shell::from_write(boxed_output_cursor);
-            */
-
-            // stdout can not be captured this way there is an actual println! used
-            // although there are ways to capture println! calls I can remember, we need
-            // also capture test output.
-
-            let shell = cargo::core::Shell::new();
-            let config = cargo::Config::new(shell, current_dir, home_dir);
-            env::set_var("EMERGENT_TEST_DPI", environment.dpi.0.to_string());
-            let normalized_path = cargo::util::paths::normalize_path(&manifest_path);
-            dbg!(&normalized_path);
-            let workspace = &cargo::core::Workspace::new(&normalized_path, &config)?;
-
-            // build library only for now.
-            let compile_filter = ops::CompileFilter::Only {
-                all_targets: false,
-                // Prefer to test the library of the package for now only.
-                lib: LibRule::True,
-                bins: FilterRule::Just(vec![]),
-                examples: FilterRule::Just(vec![]),
-                tests: FilterRule::Just(vec![]),
-                benches: FilterRule::Just(vec![]),
+        let configuration = &request.configuration;
+        let targets = [test.target.clone()];
+        let executable = match &test.target {
+            TestTarget::Doc => None,
+            target => {
+                let executables = compile_tests(package, &targets, configuration, &mut |_| {})??;
+                match executables.into_iter().find(|(t, _)| t == target) {
+                    Some((_, executable)) => Some(executable),
+                    None => failure::bail!("no test executable for {} {}", package.name, target),
+                }
+            }
+        };
+
+        let filter = request.filter.as_ref();
+        for run in 1..=runs {
+            let command = match &executable {
+                Some(executable) => test_command(package, executable, configuration, filter),
+                None => doc_test_command(package, configuration, filter),
+            };
+            let deadlines = Deadlines {
+                run: request.timeouts.run.map(|run| Instant::now() + run),
+                test: request.timeouts.test,
+            };
+            let captures = run_tests(command, primary, deadlines, &mut |_| {})?;
+            let mut capture = match captures.0.into_iter().find(|c| c.name == test.name) {
+                Some(capture) => capture,
+                None => failure::bail!("test {} was not found in run {}", test.name, run),
             };
+            capture.failure = FailureDetails::from_capture(&capture, &metadata.workspace_root);
+            record(capture)?;
+        }
+        Ok(())
+    }
+
+    /// Captures the tests and reports the progress while they are compiled and run.
+    pub fn capture_tests_with_progress(
+        &self,
+        environment: TestEnvironment,
+        progress: &mut dyn FnMut(TestRunProgress),
+    ) -> Result<TestRunResult, failure::Error> {
+        let manifest_path = self.project_directory.join("Cargo.toml");
+        let metadata = MetadataCommand::new()
+            .manifest_path(&manifest_path)
+            .no_deps()
+            .exec()?;
 
-            let mut compile_options =
-                ops::CompileOptions::new(&config, compiler::CompileMode::Test)?;
-            compile_options.build_config.message_format = compiler::MessageFormat::Json {
-                render_diagnostics: false,
-                short: false,
-                ansi: true,
+        let packages = self.packages.resolve(&metadata, &manifest_path)?;
+        self.targets.check_names(&packages)?;
+        let variants = environment.variants();
+        let (primary, other_variants) = variants
+            .split_first()
+            .expect("internal error: no variant of the test environment");
+
+        let mut compiler_messages = Vec::new();
+        let mut package_captures = Vec::new();
+        // the run deadline starts with the first test suite.
+        let mut run_deadline = None;
+
+        for package in packages {
+            let targets = self.targets.resolve(package);
+            progress(TestRunProgress::CompilationStarted {
+                package: package.name.clone(),
+            });
+            let configuration = &self.configuration;
+            let executables = compile_tests(package, &targets, configuration, &mut |message| {
+                add_compiler_message(&mut compiler_messages, message, progress)
+            })?;
+
+            let executables = match executables {
+                Ok(executables) => executables,
+                Err(e) => return Ok(TestRunResult::CompilationFailed(compiler_messages, e)),
             };
-            compile_options.filter = compile_filter;
 
-            let test_options = &ops::TestOptions {
-                compile_opts: compile_options,
-                no_run: false,
-                no_fail_fast: false,
+            let complete_capture = |crate_name: &str, capture: &mut TestCapture| {
+                snapshot::compare_capture(package_directory(package), crate_name, capture);
+                capture.failure = FailureDetails::from_capture(capture, &metadata.workspace_root);
             };
 
-            // we need a very specific set of arguments to make precise capturing of the output work.
-            let libtest_args: Vec<&str> = vec![
-                "--test-threads",
-                "1",
-                "--nocapture",
-                "-Z",
-                "unstable-options",
-                "--format",
-                "json",
-            ];
-
-            let capture = Capture::stdout();
-            let test_result = ops::run_tests(workspace, test_options, &libtest_args);
-            (test_result, capture.end())
-        };
+            let mut target_captures = Vec::new();
+            for target in targets.iter().cloned() {
+                let executable = match &target {
+                    TestTarget::Doc => None,
+                    target => match executables.iter().find(|(t, _)| t == target) {
+                        Some((_, executable)) => Some(executable),
+                        None => {
+                            warn!("no test executable for {} {}", package.name, target);
+                            continue;
+                        }
+                    },
+                };
+                let filter = self.filter.as_ref();
+                let command = || match executable {
+                    Some(executable) => test_command(package, executable, configuration, filter),
+                    None => doc_test_command(package, configuration, filter),
+                };
+
+                let crate_name = target.crate_name(package);
+                let deadlines = Deadlines {
+                    run: *run_deadline
+                        .get_or_insert_with(|| self.timeouts.run.map(|run| Instant::now() + run)),
+                    test: self.timeouts.test,
+                };
+
+                let mut captures = run_tests(command(), primary, deadlines, &mut |event| {
+                    let package = package.name.clone();
+                    let target = target.clone();
+                    progress(match event {
+                        SuiteEvent::Started { test_count } => TestRunProgress::SuiteStarted {
+                            package,
+                            target,
+                            test_count,
+                        },
+                        SuiteEvent::TestStarted(name) => TestRunProgress::TestStarted {
+                            package,
+                            target,
+                            name,
+                        },
+                        SuiteEvent::TestFinished(mut capture) => {
+                            complete_capture(&crate_name, &mut capture);
+                            TestRunProgress::TestFinished {
+                                package,
+                                target,
+                                capture,
+                            }
+                        }
+                        SuiteEvent::Finished => TestRunProgress::SuiteFinished { package, target },
+                    })
+                })?;
+                for capture in captures.0.iter_mut() {
+                    complete_capture(&crate_name, capture);
+                }
 
-        debug!(">>> TEST RESULT: {:?}", test_result);
-        debug!(">>> CAPTURED BEGIN");
-        debug!("{}", String::from_utf8_lossy(&captured));
-        debug!(">>> CAPTURED END");
-
-        let cursor = Cursor::new(&captured);
-
-        // parse messages from cargo:
-        let mut iterator = cargo_metadata::parse_messages(cursor);
-        let compiler_messages = {
-            let mut messages = Vec::new();
-            for msg in &mut iterator {
-                match msg {
-                    Ok(msg) => {
-                        if let cargo_metadata::Message::CompilerMessage(compiler_message) = msg {
-                            messages.push(compiler_message)
+                // the other variants run silently and are not compared with the snapshots, which
+                // are recorded in the primary variant.
+                let mut variant_captures = Vec::new();
+                for variant in other_variants {
+                    // a variant that fails to run does not prevent the others from running.
+                    let primary_captures = &captures;
+                    let mut captures = run_tests(command(), variant, deadlines, &mut |_| {})
+                        .unwrap_or_else(|e| variant_failed(primary_captures, variant, &e));
+                    for capture in captures.0.iter_mut() {
+                        capture.failure =
+                            FailureDetails::from_capture(capture, &metadata.workspace_root);
+                        if let Some(family) = &variant.font_family {
+                            capture.set_default_font_family(family);
                         }
                     }
-                    Err(_) => break,
+                    variant_captures.push(VariantCaptures {
+                        variant: variant.clone(),
+                        captures,
+                    });
                 }
+
+                target_captures.push(TargetCaptures {
+                    target,
+                    captures,
+                    variants: variant_captures,
+                });
             }
 
-            messages
-        };
+            // clippy runs after the tests, so that it does not delay their results.
+            if configuration.clippy {
+                lint(package, &targets, configuration, &mut |message| {
+                    add_compiler_message(&mut compiler_messages, message, progress)
+                })?;
+            }
 
-        if let Err(e) = test_result {
-            return Ok(TestRunResult::CompilationFailed(compiler_messages, e));
+            package_captures.push(PackageCaptures {
+                package: package.name.clone(),
+                targets: target_captures,
+            })
         }
 
-        // and interpret the rest as test captures.
-        let byte_offset = iterator.byte_offset();
-        let rest = &captured[byte_offset..];
-
-        // TODO: perhaps it's better to separate the compilation of the test code and the running of it?
-
         Ok(TestRunResult::TestsCaptured(
             compiler_messages,
-            TestCaptures::from_output(Cursor::new(rest))?,
+            package_captures,
         ))
     }
 }
 
+/// The arguments passed to libtest.
+///
+/// Tests run in parallel and libtest captures their output. Drawings are transferred through the
+/// capture channel, and `--show-output` makes libtest report the output of passing tests, too.
+const LIBTEST_ARGS: &[&str] = &[
+    "--show-output",
+    "-Z",
+    "unstable-options",
+    "--format",
+    "json",
+];
+
+/// The test executables of the targets of a package.
+type Executables = Vec<(TestTarget, PathBuf)>;
+
+/// Compiles the tests of the targets of a package with the configuration without running them.
+///
+/// Compiler messages are reported while the compiler runs. Returns the test executables or, as
+/// the inner error, why the compilation failed.
+fn compile_tests(
+    package: &Package,
+    targets: &[TestTarget],
+    configuration: &RunConfiguration,
+    on_message: &mut dyn FnMut(CompilerMessage),
+) -> Result<Result<Executables, failure::Error>, failure::Error> {
+    let mut command = cargo_command();
+    command
+        .arg("test")
+        .arg("--no-run")
+        .arg("--message-format")
+        .arg("json-diagnostic-rendered-ansi")
+        .arg("--manifest-path")
+        .arg(&package.manifest_path)
+        .arg("--package")
+        .arg(&package.name)
+        .args(configuration.cargo_args())
+        .envs(&configuration.env);
+
+    let target_args = target_args(targets);
+    if target_args.is_empty() {
+        return Ok(Ok(Vec::new()));
+    }
+    command.args(target_args);
+
+    debug!("compiling: {:?}", command);
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stderr = read_in_background(child.stderr.take().expect("stderr is not piped"));
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is not piped"));
+
+    let mut executables = Vec::new();
+
+    for message in cargo_metadata::parse_messages(stdout) {
+        match message? {
+            Message::CompilerMessage(compiler_message) => on_message(compiler_message),
+            Message::CompilerArtifact(artifact) => {
+                if !artifact.profile.test || artifact.package_id != package.id {
+                    continue;
+                }
+                if let (Some(target), Some(executable)) = (
+                    TestTarget::from_target(&artifact.target),
+                    artifact.executable,
+                ) {
+                    executables.push((target, executable))
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let status = child.wait()?;
+    let stderr = join_reader(stderr)?;
+
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        let error = failure::format_err!(
+            "compilation of {} failed ({}): {}",
+            package.name,
+            status,
+            stderr.trim().lines().last().unwrap_or_default()
+        );
+        return Ok(Err(error));
+    }
+
+    Ok(Ok(executables))
+}
+
+/// Runs clippy for the targets of a package with the configuration and reports its messages.
+///
+/// Clippy exiting with an error, because a lint is denied or clippy is not installed, is logged
+/// and does not fail the test run.
+fn lint(
+    package: &Package,
+    targets: &[TestTarget],
+    configuration: &RunConfiguration,
+    on_message: &mut dyn FnMut(CompilerMessage),
+) -> Result<(), failure::Error> {
+    let target_args = target_args(targets);
+    if target_args.is_empty() {
+        return Ok(());
+    }
+
+    let mut command = cargo_command();
+    command
+        .arg("clippy")
+        .arg("--message-format")
+        .arg("json-diagnostic-rendered-ansi")
+        .arg("--manifest-path")
+        .arg(&package.manifest_path)
+        .arg("--package")
+        .arg(&package.name)
+        .args(target_args)
+        .args(configuration.cargo_args())
+        .envs(&configuration.env);
+
+    debug!("linting: {:?}", command);
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stderr = read_in_background(child.stderr.take().expect("stderr is not piped"));
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is not piped"));
+
+    for message in cargo_metadata::parse_messages(stdout) {
+        if let Message::CompilerMessage(compiler_message) = message? {
+            on_message(compiler_message)
+        }
+    }
+
+    let status = child.wait()?;
+    let stderr = join_reader(stderr)?;
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        warn!(
+            "clippy failed for {} ({}): {}",
+            package.name,
+            status,
+            stderr.trim().lines().last().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// The arguments that select the targets to compile, empty if there is nothing to compile.
+fn target_args(targets: &[TestTarget]) -> Vec<String> {
+    let mut args = Vec::new();
+    for target in targets {
+        let (arg, name) = match target {
+            TestTarget::Lib => ("--lib", None),
+            TestTarget::Test(name) => ("--test", Some(name)),
+            TestTarget::Bin(name) => ("--bin", Some(name)),
+            TestTarget::Example(name) => ("--example", Some(name)),
+            // doc-tests are compiled by rustdoc when they run.
+            TestTarget::Doc => continue,
+        };
+        args.push(arg.to_string());
+        args.extend(name.cloned());
+    }
+    args
+}
+
+/// The command that runs the tests of a test executable of a package that are selected by the
+/// filter.
+fn test_command(
+    package: &Package,
+    executable: &Path,
+    configuration: &RunConfiguration,
+    filter: Option<&TestFilter>,
+) -> Command {
+    let mut command = Command::new(executable);
+    let package_directory = package_directory(package);
+    // cargo runs tests from the package's directory and provides `CARGO_MANIFEST_DIR` at runtime.
+    command
+        .args(LIBTEST_ARGS)
+        .args(&configuration.libtest_args)
+        .args(filter.map(TestFilter::libtest_args).unwrap_or_default())
+        .current_dir(package_directory)
+        .env("CARGO_MANIFEST_DIR", package_directory)
+        .envs(&configuration.env);
+    command
+}
+
+/// The command that compiles and runs the doc-tests of a package that are selected by the
+/// filter.
+fn doc_test_command(
+    package: &Package,
+    configuration: &RunConfiguration,
+    filter: Option<&TestFilter>,
+) -> Command {
+    let mut command = cargo_command();
+    command
+        .arg("test")
+        .arg("--doc")
+        .arg("--manifest-path")
+        .arg(&package.manifest_path)
+        .arg("--package")
+        .arg(&package.name)
+        .args(configuration.cargo_args())
+        .envs(&configuration.env)
+        .arg("--")
+        .args(LIBTEST_ARGS)
+        .args(&configuration.libtest_args)
+        .args(filter.map(TestFilter::libtest_args).unwrap_or_default());
+    command
+}
+
+/// Runs the tests in a child process in a variant of the test environment and captures their
+/// output.
+///
+/// The events of the suite are reported while the tests run. A test process that terminates
+/// without completing the test suite, for example because of an abort or a stack overflow,
+/// results in a failed capture of the test that was running. If a deadline expires, the process
+/// is killed together with the processes it spawned, and the tests that exceeded their time
+/// limit are recorded as timed out. Suites that would start after the deadline of the run
+/// expired are not run and recorded as skipped.
+fn run_tests(
+    mut command: Command,
+    variant: &TestVariant,
+    deadlines: Deadlines,
+    report: &mut dyn FnMut(SuiteEvent),
+) -> Result<TestCaptures, failure::Error> {
+    if deadlines.run_expired() {
+        return Ok(TestCaptures::skipped(
+            "skipped, because the test run exceeded its time limit",
+        ));
+    }
+
+    let capture_directory = capture_directory();
+    fs::create_dir_all(&capture_directory)?;
+    match &variant.font_family {
+        Some(family) => command.env("EMERGENT_TEST_FONT_FAMILY", family),
+        None => command.env_remove("EMERGENT_TEST_FONT_FAMILY"),
+    };
+    command
+        .env("EMERGENT_TEST_DPI", variant.dpi.0.to_string())
+        .env("EMERGENT_TEST_COLOR_SCHEME", variant.color_scheme.name())
+        .env(capture_channel::CAPTURE_DIRECTORY_VAR, &capture_directory)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    debug!("running: {:?}", command);
+    let captures = run_test_process(command, &capture_directory, deadlines, report);
+    if let Err(e) = fs::remove_dir_all(&capture_directory) {
+        warn!("failed to remove {:?}: {}", capture_directory, e);
+    }
+    captures
+}
+
+fn run_test_process(
+    mut command: Command,
+    capture_directory: &Path,
+    deadlines: Deadlines,
+    report: &mut dyn FnMut(SuiteEvent),
+) -> Result<TestCaptures, failure::Error> {
+    let mut child = spawn_process_group(&mut command)?;
+    let stderr = read_in_background(child.stderr.take().expect("stderr is not piped"));
+    let lines = read_lines_in_background(child.stdout.take().expect("stdout is not piped"));
+
+    let mut parser = CaptureParser::new();
+    // the running tests and when they started.
+    let mut running: Vec<(String, Instant)> = Vec::new();
+
+    let expired = loop {
+        let line = match deadlines.next(&running) {
+            Some(deadline) => {
+                let timeout = deadline
+                    .checked_duration_since(Instant::now())
+                    .unwrap_or_default();
+                match lines.recv_timeout(timeout) {
+                    Ok(line) => line,
+                    Err(RecvTimeoutError::Timeout) => break Some(deadlines.expired(&running)),
+                    Err(RecvTimeoutError::Disconnected) => break None,
+                }
+            }
+            None => match lines.recv() {
+                Ok(line) => line,
+                Err(_) => break None,
+            },
+        };
+
+        let mut line = String::from_utf8_lossy(&line?).into_owned();
+        line.push('\n');
+        match parser.parse_line(line)? {
+            SuiteProgress::SuiteStarted { test_count } => {
+                report(SuiteEvent::Started { test_count })
+            }
+            SuiteProgress::TestStarted(name) => {
+                running.push((name.clone(), Instant::now()));
+                report(SuiteEvent::TestStarted(name))
+            }
+            SuiteProgress::TestFinished => {
+                let mut capture = parser
+                    .captures()
+                    .last()
+                    .expect("internal error: no capture of the finished test")
+                    .clone();
+                running.retain(|(name, _)| *name != capture.name);
+                resolve_artifacts(&mut capture, capture_directory);
+                report(SuiteEvent::TestFinished(capture))
+            }
+            SuiteProgress::SuiteFinished => report(SuiteEvent::Finished),
+            SuiteProgress::Output => {}
+        }
+    };
+
+    let mut captures = match expired {
+        Some(expired) => {
+            if let Err(e) = kill_process_group(&mut child) {
+                warn!("failed to kill the test process: {}", e);
+            }
+            let status = child.wait()?;
+            debug!(">>> TEST TIMED OUT: {} ({})", expired.join(", "), status);
+            // the processes that held stdout and stderr open were killed with the test process.
+            drop(lines);
+            let stderr = join_reader(stderr)?;
+            debug!(">>> STDERR BEGIN");
+            debug!("{}", String::from_utf8_lossy(&stderr));
+            debug!(">>> STDERR END");
+            parser.time_out(&expired, &deadlines.reason(&expired))
+        }
+        None => {
+            let status = child.wait()?;
+            let stderr = join_reader(stderr)?;
+
+            debug!(">>> TEST RESULT: {}", status);
+            debug!(">>> STDERR BEGIN");
+            debug!("{}", String::from_utf8_lossy(&stderr));
+            debug!(">>> STDERR END");
+
+            if parser.is_complete() {
+                parser.finish()?
+            } else {
+                // libtest exits with 101 if tests failed, but it does so only after it finished
+                // the suite.
+                let reason = format!(
+                    "test process terminated unexpectedly ({})\n{}",
+                    status,
+                    String::from_utf8_lossy(&stderr)
+                );
+                parser.abort(&reason)
+            }
+        }
+    };
+
+    for capture in &mut captures.0 {
+        resolve_artifacts(capture, capture_directory);
+    }
+    Ok(captures)
+}
+
+/// The captures of a variant the tests could not be run in: the tests of the primary variant
+/// failed with the error.
+fn variant_failed(
+    primary: &TestCaptures,
+    variant: &TestVariant,
+    error: &failure::Error,
+) -> TestCaptures {
+    let message = format!(
+        "failed to run the tests in the variant {}: {}",
+        variant, error
+    );
+    TestCaptures(
+        primary
+            .0
+            .iter()
+            .map(|capture| {
+                let result = TestResult::Failed(Some(ExtraData::Message(message.clone())));
+                TestCapture::new(capture.name.clone(), result, "")
+            })
+            .collect(),
+    )
+}
+
+/// The deadlines of a test process.
+#[derive(Copy, Clone, Debug)]
+struct Deadlines {
+    /// The instant the test run must be completed.
+    run: Option<Instant>,
+    /// The time limit of each test.
+    test: Option<Duration>,
+}
+
+impl Deadlines {
+    /// Returns `true` if the deadline of the run expired.
+    fn run_expired(&self) -> bool {
+        self.run.map_or(false, |run| Instant::now() >= run)
+    }
+
+    /// Returns the deadline that expires next.
+    fn next(&self, running: &[(String, Instant)]) -> Option<Instant> {
+        let test = self
+            .test
+            .and_then(|limit| running.iter().map(|(_, started)| *started + limit).min());
+        match (self.run, test) {
+            (Some(run), Some(test)) => Some(run.min(test)),
+            (run, test) => run.or(test),
+        }
+    }
+
+    /// Returns the names of the running tests whose deadline has expired.
+    fn expired(&self, running: &[(String, Instant)]) -> Vec<String> {
+        let now = Instant::now();
+        let run_expired = self.run_expired();
+        running
+            .iter()
+            .filter(|(_, started)| {
+                run_expired || self.test.map_or(false, |limit| now >= *started + limit)
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Describes why the tests in `expired` were killed.
+    fn reason(&self, expired: &[String]) -> String {
+        match (self.run, self.test) {
+            (Some(run), _) if Instant::now() >= run || expired.is_empty() => {
+                "the test run exceeded its time limit".into()
+            }
+            (_, Some(limit)) => format!("the test exceeded its time limit of {:?}", limit),
+            _ => "the test exceeded its time limit".into(),
+        }
+    }
+}
+
+/// Spawns the command as the leader of a new process group, so that it can be killed together
+/// with the processes it spawns, like the doc tests `cargo test --doc` runs.
+#[cfg(unix)]
+fn spawn_process_group(command: &mut Command) -> io::Result<Child> {
+    use std::os::unix::process::CommandExt;
+    // `setpgid()` is async-signal-safe and can be called between fork and exec.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setpgid(0, 0) == 0 {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        });
+    }
+    command.spawn()
+}
+
+#[cfg(not(unix))]
+fn spawn_process_group(command: &mut Command) -> io::Result<Child> {
+    command.spawn()
+}
+
+/// Kills a child spawned with `spawn_process_group()` and all processes in its group.
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
+
+/// Reads the lines of a stream in a separate thread and sends them without their line terminator.
+///
+/// Empty lines are skipped.
+fn read_lines_in_background(reader: impl Read + Send + 'static) -> Receiver<io::Result<Vec<u8>>> {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    thread::spawn(move || {
+        for line in BufReader::new(reader).split(b'\n') {
+            match line {
+                Ok(ref line) if line.is_empty() => continue,
+                line => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    receiver
+}
+
+/// Reads a stream to its end in a separate thread.
+fn read_in_background(mut reader: impl Read + Send + 'static) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        reader.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    })
+}
+
+fn join_reader(reader: JoinHandle<io::Result<Vec<u8>>>) -> io::Result<Vec<u8>> {
+    reader
+        .join()
+        .expect("internal error: reader thread panicked")
+}
+
+/// Returns a new directory the artifacts of a test process are transferred through.
+fn capture_directory() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
+    env::temp_dir().join(format!("emergent-captures-{}-{}", process::id(), counter))
+}
+
+/// Prepends the artifacts that are referenced in the output of a test to its artifacts and
+/// removes the references from the output.
+fn resolve_artifacts(capture: &mut TestCapture, capture_directory: &Path) {
+    let (output, mut artifacts) = capture_channel::resolve(capture_directory, &capture.output);
+    if !artifacts.is_empty() {
+        artifacts.append(&mut capture.artifacts);
+        capture.artifacts = artifacts;
+        capture.output = output;
+    }
+}
+
+fn cargo_command() -> Command {
+    Command::new(env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+}
+
+fn package_directory(package: &Package) -> &Path {
+    package
+        .manifest_path
+        .parent()
+        .expect("manifest path without a parent directory")
+}
+
+/// Adds a compiler message to the messages of the run and reports it, unless it was reported
+/// already.
+///
+/// Cargo replays the messages of fresh units, so messages of shared dependencies (like the
+/// library) may appear more than once. Clippy reports the warnings of the compiler, too.
+fn add_compiler_message(
+    messages: &mut Vec<CompilerMessage>,
+    message: CompilerMessage,
+    progress: &mut dyn FnMut(TestRunProgress),
+) {
+    if !messages
+        .iter()
+        .any(|m| is_same_compiler_message(m, &message))
+    {
+        progress(TestRunProgress::CompilerMessage(message.clone()));
+        messages.push(message)
+    }
+}
+
+/// Compares compiler messages by the target they were produced for and their contents.
+fn is_same_compiler_message(l: &CompilerMessage, r: &CompilerMessage) -> bool {
+    l.package_id == r.package_id
+        && l.target.name == r.target.name
+        && l.target.kind == r.target.kind
+        && l.message.message == r.message.message
+        && l.message.rendered == r.message.rendered
+}
+
+impl PackageSelection {
+    /// Resolves the selection to the packages of the workspace.
+    ///
+    /// - `manifest_path` The path of the manifest that defines the current package.
+    pub fn resolve<'a>(
+        &self,
+        metadata: &'a Metadata,
+        manifest_path: &Path,
+    ) -> Result<Vec<&'a Package>, failure::Error> {
+        let members = || {
+            metadata
+                .packages
+                .iter()
+                .filter(move |p| metadata.workspace_members.contains(&p.id))
+        };
+
+        match self {
+            PackageSelection::Current => {
+                let manifest_path = fs::canonicalize(manifest_path)?;
+                match members().find(|p| {
+                    fs::canonicalize(&p.manifest_path).ok().as_ref() == Some(&manifest_path)
+                }) {
+                    Some(package) => Ok(vec![package]),
+                    None => failure::bail!(
+                        "{:?} does not define a package, use --workspace to test a workspace",
+                        manifest_path
+                    ),
+                }
+            }
+            PackageSelection::Workspace => Ok(members().collect()),
+            PackageSelection::Just(names) => {
+                let packages: Vec<_> = members().filter(|p| names.contains(&p.name)).collect();
+                if packages.len() != names.len() {
+                    failure::bail!("some of the packages {:?} are not workspace members", names)
+                }
+                Ok(packages)
+            }
+        }
+    }
+}
+
+impl Default for PackageSelection {
+    fn default() -> Self {
+        PackageSelection::Current
+    }
+}
+
+impl Default for Timeouts {
+    /// Limits each test to a minute, so that a test that does not terminate does not block the
+    /// testrunner.
+    fn default() -> Self {
+        Timeouts {
+            run: None,
+            test: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl TargetSelection {
+    /// Selects the library only.
+    pub fn lib() -> Self {
+        TargetSelection {
+            lib: true,
+            tests: TargetRule::None,
+            bins: TargetRule::None,
+            examples: TargetRule::None,
+            doc: false,
+        }
+    }
+
+    /// Selects no targets.
+    pub fn none() -> Self {
+        TargetSelection {
+            lib: false,
+            ..Self::lib()
+        }
+    }
+
+    /// Selects all targets, including doc-tests.
+    pub fn all() -> Self {
+        TargetSelection {
+            lib: true,
+            tests: TargetRule::All,
+            bins: TargetRule::All,
+            examples: TargetRule::All,
+            doc: true,
+        }
+    }
+
+    /// Selects a single target.
+    pub fn only(target: &TestTarget) -> Self {
+        let just = |name: &String| TargetRule::Just(vec![name.clone()]);
+        let none = Self::none();
+        match target {
+            TestTarget::Lib => Self::lib(),
+            TestTarget::Test(name) => TargetSelection {
+                tests: just(name),
+                ..none
+            },
+            TestTarget::Bin(name) => TargetSelection {
+                bins: just(name),
+                ..none
+            },
+            TestTarget::Example(name) => TargetSelection {
+                examples: just(name),
+                ..none
+            },
+            TestTarget::Doc => TargetSelection { doc: true, ..none },
+        }
+    }
+
+    /// Resolves the selection to the targets of the package that can be tested.
+    ///
+    /// Targets that are selected by name but do not exist in the package are ignored, see
+    /// `check_names()`.
+    pub fn resolve(&self, package: &Package) -> Vec<TestTarget> {
+        let targets: Vec<_> = package
+            .targets
+            .iter()
+            .filter_map(TestTarget::from_target)
+            .collect();
+
+        let selected = |target: &TestTarget| match target {
+            TestTarget::Lib => self.lib,
+            TestTarget::Test(name) => self.tests.matches(name),
+            TestTarget::Bin(name) => self.bins.matches(name),
+            TestTarget::Example(name) => self.examples.matches(name),
+            TestTarget::Doc => self.doc,
+        };
+
+        let mut resolved: Vec<_> = targets.iter().filter(|t| selected(t)).cloned().collect();
+        // doc-tests are run after all other targets, like cargo does.
+        if self.doc && targets.contains(&TestTarget::Lib) {
+            resolved.push(TestTarget::Doc);
+        }
+
+        resolved
+    }
+
+    /// Fails with the name of a target that is selected by name but does not exist in any of
+    /// the packages, like cargo does.
+    pub fn check_names(&self, packages: &[&Package]) -> Result<(), failure::Error> {
+        let targets: Vec<TestTarget> = packages
+            .iter()
+            .flat_map(|package| package.targets.iter().filter_map(TestTarget::from_target))
+            .collect();
+        let rules: [(&TargetRule, &str, fn(String) -> TestTarget); 3] = [
+            (&self.tests, "test", TestTarget::Test),
+            (&self.bins, "bin", TestTarget::Bin),
+            (&self.examples, "example", TestTarget::Example),
+        ];
+        for (rule, kind, target) in rules.iter() {
+            if let TargetRule::Just(names) = rule {
+                if let Some(name) = names
+                    .iter()
+                    .find(|n| !targets.contains(&target(n.to_string())))
+                {
+                    failure::bail!("no {} target named `{}`", kind, name);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for TargetSelection {
+    fn default() -> Self {
+        Self::lib()
+    }
+}
+
+impl TargetRule {
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            TargetRule::None => false,
+            TargetRule::All => true,
+            TargetRule::Just(names) => names.iter().any(|n| n == name),
+        }
+    }
+}
+
+impl TestTarget {
+    /// Converts a cargo target to the test target it represents.
+    ///
+    /// Returns `None` for targets that are not tested, like benches and build scripts.
+    fn from_target(target: &Target) -> Option<TestTarget> {
+        const LIB_KINDS: &[&str] = &["lib", "rlib", "dylib", "cdylib", "staticlib", "proc-macro"];
+        let is_kind = |kind: &str| target.kind.iter().any(|k| k == kind);
+        let name = target.name.clone();
+
+        if target.kind.iter().any(|k| LIB_KINDS.contains(&k.as_str())) {
+            Some(TestTarget::Lib)
+        } else if is_kind("test") {
+            Some(TestTarget::Test(name))
+        } else if is_kind("bin") {
+            Some(TestTarget::Bin(name))
+        } else if is_kind("example") {
+            Some(TestTarget::Example(name))
+        } else {
+            None
+        }
+    }
+
+    /// The name of the crate the tests of the target are compiled into.
+    pub fn crate_name(&self, package: &Package) -> String {
+        let name = match self {
+            TestTarget::Lib => package
+                .targets
+                .iter()
+                .find(|t| TestTarget::from_target(t) == Some(TestTarget::Lib))
+                .map_or(package.name.as_str(), |t| t.name.as_str()),
+            TestTarget::Test(name) | TestTarget::Bin(name) | TestTarget::Example(name) => name,
+            // rustdoc compiles every doc test into a crate of this name.
+            TestTarget::Doc => "rust_out",
+        };
+        name.replace('-', "_")
+    }
+}
+
+impl fmt::Display for TestTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestTarget::Lib => write!(f, "lib"),
+            TestTarget::Test(name) => write!(f, "test {}", name),
+            TestTarget::Bin(name) => write!(f, "bin {}", name),
+            TestTarget::Example(name) => write!(f, "example {}", name),
+            TestTarget::Doc => write!(f, "doc-tests"),
+        }
+    }
+}
+
+impl TestRunResult {
+    /// The messages the compiler emitted while the tests were compiled.
+    pub fn compiler_messages(&self) -> &[CompilerMessage] {
+        match self {
+            TestRunResult::CompilationFailed(compiler_messages, _)
+            | TestRunResult::TestsCaptured(compiler_messages, _) => compiler_messages,
+        }
+    }
+
+    /// All the captures of all packages and targets.
+    pub fn captures(&self) -> impl Iterator<Item = &TestCapture> {
+        let packages: &[PackageCaptures] = match self {
+            TestRunResult::CompilationFailed(_, _) => &[],
+            TestRunResult::TestsCaptured(_, packages) => packages,
+        };
+        packages.iter().flat_map(|p| p.captures())
+    }
+
+    /// The capture of a test, `None` if the test was not part of the run.
+    pub fn capture(&self, test: &TestId) -> Option<&TestCapture> {
+        let packages: &[PackageCaptures] = match self {
+            TestRunResult::CompilationFailed(_, _) => &[],
+            TestRunResult::TestsCaptured(_, packages) => packages,
+        };
+        packages
+            .iter()
+            .filter(|p| p.package == test.package)
+            .flat_map(|p| p.targets.iter())
+            .filter(|t| t.target == test.target)
+            .flat_map(|t| t.captures.0.iter())
+            .find(|c| c.name == test.name)
+    }
+
+    pub fn captures_mut(&mut self) -> impl Iterator<Item = &mut TestCapture> {
+        let packages: &mut [PackageCaptures] = match self {
+            TestRunResult::CompilationFailed(_, _) => &mut [],
+            TestRunResult::TestsCaptured(_, packages) => packages,
+        };
+        packages
+            .iter_mut()
+            .flat_map(|p| p.targets.iter_mut())
+            .flat_map(|t| t.captures.0.iter_mut())
+    }
+
+    /// Merges the captures of tests that were run again into this result.
+    ///
+    /// The captures of tests that are part of this result are replaced, others are added. Results
+    /// of failed compilations have no captures to merge into and are left unchanged.
+    pub fn merge(&mut self, rerun: Vec<PackageCaptures>) {
+        let packages = match self {
+            TestRunResult::CompilationFailed(_, _) => return,
+            TestRunResult::TestsCaptured(_, packages) => packages,
+        };
+        for rerun in rerun {
+            match packages.iter_mut().find(|p| p.package == rerun.package) {
+                Some(package) => package.merge(rerun),
+                None => packages.push(rerun),
+            }
+        }
+    }
+}
+
+impl PackageCaptures {
+    /// All the captures of all targets of the package.
+    pub fn captures(&self) -> impl Iterator<Item = &TestCapture> {
+        self.targets.iter().flat_map(|t| t.captures.0.iter())
+    }
+
+    /// Counts the results of all the captures of the package, the captures of every variant of
+    /// the test environment are counted separately.
+    pub fn totals(&self) -> TestTotals {
+        let variants = self
+            .targets
+            .iter()
+            .flat_map(|t| t.variants.iter())
+            .flat_map(|v| v.captures.0.iter());
+        TestTotals::from_captures(self.captures().chain(variants))
+    }
+
+    fn merge(&mut self, rerun: PackageCaptures) {
+        for rerun in rerun.targets {
+            match self.targets.iter_mut().find(|t| t.target == rerun.target) {
+                Some(target) => target.merge(rerun),
+                None => self.targets.push(rerun),
+            }
+        }
+    }
+}
+
+impl TargetCaptures {
+    fn merge(&mut self, rerun: TargetCaptures) {
+        merge_captures(&mut self.captures, rerun.captures);
+        for rerun in rerun.variants {
+            match self
+                .variants
+                .iter_mut()
+                .find(|v| v.variant == rerun.variant)
+            {
+                Some(variant) => merge_captures(&mut variant.captures, rerun.captures),
+                None => self.variants.push(rerun),
+            }
+        }
+    }
+
+    /// The captures of a test in the other variants of the test environment.
+    pub fn variants_of<'a>(
+        &'a self,
+        test: &'a str,
+    ) -> impl Iterator<Item = (&'a TestVariant, &'a TestCapture)> + 'a {
+        self.variants.iter().filter_map(move |v| {
+            v.captures
+                .0
+                .iter()
+                .find(|c| c.name == test)
+                .map(|c| (&v.variant, c))
+        })
+    }
+}
+
+/// Replaces the captures of the tests that were run again, and adds the others.
+fn merge_captures(captures: &mut TestCaptures, rerun: TestCaptures) {
+    for rerun in rerun.0 {
+        match captures.0.iter_mut().find(|c| c.name == rerun.name) {
+            Some(capture) => *capture = rerun,
+            None => captures.0.push(rerun),
+        }
+    }
+}
+
 impl TestEnvironment {
     pub fn new(dpi: DPI) -> Self {
-        TestEnvironment { dpi }
+        TestEnvironment {
+            dpi,
+            matrix: VariantMatrix::default(),
+        }
+    }
+
+    /// Returns an environment that runs the tests in the variants of the matrix, too.
+    pub fn with_matrix(self, matrix: VariantMatrix) -> Self {
+        TestEnvironment { matrix, ..self }
+    }
+
+    /// All variants of the environment, the primary variant first.
+    pub fn variants(&self) -> Vec<TestVariant> {
+        let mut dpis = vec![self.dpi];
+        dpis.extend(
+            self.matrix
+                .dpi_scales
+                .iter()
+                .map(|scale| DPI(self.dpi.0 * scale)),
+        );
+        let mut font_families = vec![None];
+        font_families.extend(self.matrix.font_families.iter().cloned().map(Some));
+        let mut color_schemes = vec![ColorScheme::default()];
+        color_schemes.extend(self.matrix.color_schemes.iter().cloned());
+
+        let mut variants = Vec::new();
+        for dpi in &dpis {
+            for font_family in &font_families {
+                for color_scheme in &color_schemes {
+                    let variant = TestVariant {
+                        dpi: *dpi,
+                        font_family: font_family.clone(),
+                        color_scheme: *color_scheme,
+                    };
+                    if !variants.contains(&variant) {
+                        variants.push(variant);
+                    }
+                }
+            }
+        }
+        variants
+    }
+}
+
+impl fmt::Display for TestVariant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} dpi", self.dpi.0)?;
+        if let Some(font_family) = &self.font_family {
+            write!(f, ", {}", font_family)?;
+        }
+        write!(f, ", {}", self.color_scheme.name())
     }
 }
@@ -0,0 +1,185 @@
+//! Repeated runs of a single test that reveal nondeterministic results and drawings.
+
+use crate::libtest::TestCapture;
+use crate::test_runner::{TestEnvironment, TestId, TestRunRequest};
+use crate::Msg;
+use emergent_drawing::functions::{paint, text};
+use emergent_drawing::{font, Drawing, DrawingTarget, Font, RGB};
+use emergent_presentation::Presentation;
+use emergent_presenter::{AsData, Direction, IndexAccessible, IndexMappable, Item, Reducible};
+use emergent_presenter::{View, ViewBuilder};
+use std::mem;
+
+/// The distinct outcomes of a test that was run repeatedly.
+#[derive(Clone, Debug)]
+pub struct StressResult {
+    pub test: TestId,
+    /// The number of runs recorded.
+    pub runs: usize,
+    /// The distinct outcomes in the order they first occurred.
+    pub variants: Vec<StressVariant>,
+}
+
+/// An outcome of a test that occurred in one or more runs.
+#[derive(Clone, Debug)]
+pub struct StressVariant {
+    /// The capture of the first run with this outcome.
+    pub capture: TestCapture,
+    /// The numbers of the runs with this outcome, starting at 1.
+    pub runs: Vec<usize>,
+    /// The serialized drawings, which are compared with the drawings of the other runs.
+    drawings: Vec<String>,
+}
+
+impl StressResult {
+    pub fn new(test: TestId) -> Self {
+        StressResult {
+            test,
+            runs: 0,
+            variants: Vec::new(),
+        }
+    }
+
+    /// Compiles the test once, runs it `runs` times in the primary variant of the environment,
+    /// and records the outcomes.
+    ///
+    /// Fails if the test does not compile or is not found.
+    pub fn run(
+        request: &TestRunRequest,
+        test: &TestId,
+        environment: &TestEnvironment,
+        runs: usize,
+    ) -> Result<StressResult, failure::Error> {
+        let environment = TestEnvironment::new(environment.dpi);
+        let mut result = StressResult::new(test.clone());
+        request.capture_test_repeatedly(test, environment, runs, &mut |capture| {
+            result.record(capture)
+        })?;
+        Ok(result)
+    }
+
+    /// Records the capture of the next run.
+    ///
+    /// The capture is a new variant if its result or its drawings differ from the ones of all
+    /// the previous runs.
+    pub fn record(&mut self, capture: TestCapture) -> Result<(), failure::Error> {
+        self.runs += 1;
+        let run = self.runs;
+        let drawings = capture
+            .drawings()
+            .into_iter()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let existing = self.variants.iter_mut().find(|v| {
+            mem::discriminant(&v.capture.result) == mem::discriminant(&capture.result)
+                && v.drawings == drawings
+        });
+        match existing {
+            Some(variant) => variant.runs.push(run),
+            None => self.variants.push(StressVariant {
+                capture,
+                runs: vec![run],
+                drawings,
+            }),
+        }
+        Ok(())
+    }
+
+    /// A test is flaky if it did not produce the same result and drawings in every run.
+    pub fn is_flaky(&self) -> bool {
+        self.variants.len() > 1
+    }
+
+    /// Presents whether the test is flaky and the capture of each variant below the runs it
+    /// occurred in.
+    pub fn present(&self, b: ViewBuilder<Msg>) -> View<Msg> {
+        let summary = Item::new(self).map(|b, result| b.present(result.present_summary()));
+        let variants = self.variants.as_data().map_view(|b, variant| {
+            let runs = Item::new(variant).map(|b, variant| b.present(self.present_runs(variant)));
+            let capture = Item::new(&variant.capture)
+                .map(|b, capture| capture.present(b, true, &[], &self.test, false));
+            runs.extend(&capture).reduce(b, Direction::Column)
+        });
+
+        summary.extend(&variants).reduce(b, Direction::Column)
+    }
+
+    fn present_summary(&self) -> Presentation {
+        let (line, color) = if self.is_flaky() {
+            let line = format!(
+                "{} is flaky: {} distinct outcomes in {} runs",
+                self.test.name,
+                self.variants.len(),
+                self.runs
+            );
+            (line, 0xff0000.rgb())
+        } else {
+            let line = format!(
+                "{}: the same outcome in all {} runs",
+                self.test.name, self.runs
+            );
+            (line, 0x00a000.rgb())
+        };
+        let font = &Font::new("", font::Style::BOLD, font::Size::new(20.0));
+        let mut drawing = Drawing::new();
+        drawing.draw(text(line, font, None), paint().color(color));
+        drawing.into()
+    }
+
+    fn present_runs(&self, variant: &StressVariant) -> Presentation {
+        let runs: Vec<String> = variant.runs.iter().map(|run| run.to_string()).collect();
+        let line = format!(
+            "runs {} ({} of {})",
+            runs.join(", "),
+            variant.runs.len(),
+            self.runs
+        );
+        let font = &Font::new("", font::Style::ITALIC, font::Size::new(16.0));
+        let mut drawing = Drawing::new();
+        drawing.draw(text(line, font, None), paint());
+        drawing.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StressResult;
+    use crate::libtest::{TestCapture, TestResult};
+    use crate::test_runner::{TestId, TestTarget};
+    use emergent_drawing::artifact::Artifact;
+    use emergent_drawing::functions::{paint, rect};
+    use emergent_drawing::{Drawing, DrawingTarget};
+
+    fn capture(result: TestResult, width: f64) -> TestCapture {
+        let mut drawing = Drawing::new();
+        drawing.draw(rect((0.0, 0.0), (width, 10.0)), paint());
+        let mut capture = TestCapture::new("animation", result, "");
+        capture.artifacts.push(Artifact::Drawing {
+            label: None,
+            drawing,
+        });
+        capture
+    }
+
+    #[test]
+    fn differing_results_and_drawings_are_variants() {
+        let mut result = StressResult::new(TestId {
+            package: "emergent".into(),
+            target: TestTarget::Lib,
+            name: "animation".into(),
+        });
+        result.record(capture(TestResult::Ok(), 10.0)).unwrap();
+        result.record(capture(TestResult::Ok(), 10.0)).unwrap();
+        assert!(!result.is_flaky());
+
+        result.record(capture(TestResult::Ok(), 11.0)).unwrap();
+        result.record(capture(TestResult::Timeout, 10.0)).unwrap();
+        result.record(capture(TestResult::Ok(), 11.0)).unwrap();
+
+        assert!(result.is_flaky());
+        assert_eq!(result.runs, 5);
+        let runs: Vec<Vec<usize>> = result.variants.iter().map(|v| v.runs.clone()).collect();
+        assert_eq!(runs, vec![vec![1, 2], vec![3, 5], vec![4]]);
+    }
+}
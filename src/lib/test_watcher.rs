@@ -1,9 +1,12 @@
-use crate::test_runner::{TestEnvironment, TestRunRequest, TestRunResult};
+use crate::test_runner::{TestEnvironment, TestRunProgress, TestRunRequest, TestRunResult};
 use clap::ArgMatches;
 use crossbeam_channel;
 use crossbeam_channel::Sender;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::PathBuf;
 use std::sync::{atomic, mpsc, Arc, Mutex};
+use std::time::Duration;
 use std::{fs, mem, thread};
 use watchexec::cli::Args;
 use watchexec::pathop;
@@ -13,12 +16,62 @@ use watchexec::run;
 pub enum Notification {
     /// Watcher stopped because of an error.
     WatcherStopped(failure::Error),
+    /// A test run with the named run configuration has been started because the files changed,
+    /// or, if no files changed, because it was triggered otherwise.
+    TestRunStarted {
+        changed_files: Vec<PathBuf>,
+        configuration: String,
+    },
+    /// A test run made progress.
+    Progress(TestRunProgress),
     /// A test run has been completed.
     TestRunCompleted(Result<TestRunResult, failure::Error>),
+    /// The delay before the restart attempt of a stopped watcher has passed.
+    RestartDue { attempt: u32 },
+}
+
+/// Exponentially growing delays between attempts to restart a stopped watcher.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before a restart attempt, attempts start at 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::max_value());
+        self.initial
+            .checked_mul(factor)
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+
+    /// Sends `Notification::RestartDue` to the `notifier` after the delay of the attempt.
+    pub fn schedule_restart(&self, attempt: u32, notifier: Sender<Notification>) {
+        let delay = self.delay(attempt);
+        debug!("restarting the test watcher in {:?}", delay);
+        thread::spawn(move || {
+            thread::sleep(delay);
+            // the receiver is gone when the application has shut down in the meantime.
+            let _ = notifier.send(Notification::RestartDue { attempt });
+        });
+    }
 }
 
 pub struct TestWatcher {
     full_path: PathBuf,
+    request: Arc<Mutex<TestRunRequest>>,
     environment: Arc<Mutex<TestEnvironment>>,
     notify: mpsc::Sender<notify::RawEvent>,
     shutdown: Box<dyn FnOnce() + Send>,
@@ -42,27 +95,35 @@ impl TestWatcher {
         let mut args = cargo_watch::get_options(false, &ArgMatches::default());
         args.paths.push(req.project_directory.clone());
 
-        let full_path = fs::canonicalize(req.project_directory.clone()).unwrap();
+        let full_path = fs::canonicalize(req.project_directory.clone())?;
 
         let (tx, rx) = mpsc::channel();
         let notify = tx.clone();
         let shutdown_bool = Arc::new(atomic::AtomicBool::new(false));
+        let request = Arc::new(Mutex::new(req));
         let environment = Arc::new(Mutex::new(environment));
 
         let watcher = TestWatcherHandler {
-            request: req,
+            request: request.clone(),
             shutdown: shutdown_bool.clone(),
             notifier: notifier.clone(),
             environment: environment.clone(),
         };
 
         let thread = thread::spawn(move || {
-            if let Err(e) = run::watch_with_handler(args, (tx, rx), watcher) {
-                notifier
-                    .send(Notification::WatcherStopped(e.into()))
-                    // if sending did not work, noone knows that the watcher ended, so panic.
-                    .unwrap();
-            }
+            // a panic of the handler stops the watcher like an error, so that it is restarted.
+            let watched = panic::catch_unwind(AssertUnwindSafe(|| {
+                run::watch_with_handler(args, (tx, rx), watcher)
+            }));
+            let error: failure::Error = match watched {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => e.into(),
+                Err(panic) => {
+                    failure::format_err!("the test watcher panicked: {}", panic_message(&*panic))
+                }
+            };
+            // the receiver is gone when the application has shut down in the meantime.
+            let _ = notifier.send(Notification::WatcherStopped(error));
         });
 
         let shutdown = {
@@ -71,14 +132,12 @@ impl TestWatcher {
             move || {
                 // indicate shutdown.
                 shutdown_bool.store(true, atomic::Ordering::SeqCst);
-                // force an update.
-                notify
-                    .send(notify::RawEvent {
-                        path: Some(full_path),
-                        op: Ok(notify::Op::CHMOD),
-                        cookie: None,
-                    })
-                    .expect("failed to shutdown test watcher");
+                // force an update, this fails if the watcher has already stopped.
+                let _ = notify.send(notify::RawEvent {
+                    path: Some(full_path),
+                    op: Ok(notify::Op::CHMOD),
+                    cookie: None,
+                });
                 // join the thread.
                 thread.join().unwrap()
             }
@@ -87,37 +146,84 @@ impl TestWatcher {
         Ok(TestWatcher {
             full_path,
             notify,
+            request,
             environment,
             shutdown: Box::new(shutdown),
         })
     }
 
+    /// Stops the watcher without waiting for the test run that is in progress.
+    ///
+    /// The watcher thread is signalled to shut down and joined on a separate thread. It does not
+    /// send notifications about its current test run anymore.
+    pub fn stop_in_background(self) {
+        thread::spawn(move || drop(self));
+    }
+
     pub fn update_environment(&mut self, environment: TestEnvironment) {
         *self.environment.lock().unwrap() = environment;
-        self.notify
-            .send(notify::RawEvent {
-                path: Some(self.full_path.clone()),
-                op: Ok(notify::Op::CHMOD),
-                cookie: None,
-            })
-            .expect("failed to notify the test watcher");
+        self.force_update();
+    }
+
+    /// Replaces the request the tests are run with and runs them again.
+    pub fn update_request(&mut self, request: TestRunRequest) {
+        *self.request.lock().unwrap() = request;
+        self.force_update();
+    }
+
+    fn force_update(&self) {
+        let update = self.notify.send(notify::RawEvent {
+            path: Some(self.full_path.clone()),
+            op: Ok(notify::Op::CHMOD),
+            cookie: None,
+        });
+        if update.is_err() {
+            warn!("failed to notify the test watcher, it has stopped");
+        }
     }
 }
 
 struct TestWatcherHandler {
-    request: TestRunRequest,
+    request: Arc<Mutex<TestRunRequest>>,
     shutdown: Arc<atomic::AtomicBool>,
     notifier: Sender<Notification>,
     environment: Arc<Mutex<TestEnvironment>>,
 }
 
 impl TestWatcherHandler {
-    fn capture_tests(&self) {
+    fn capture_tests(&self, changed_files: Vec<PathBuf>) {
+        let request = self.request.lock().unwrap().clone();
+        // the receiver may be gone when the application shuts down.
+        let _ = self.notifier.send(Notification::TestRunStarted {
+            changed_files,
+            configuration: request.configuration.name.clone(),
+        });
         let environment = self.environment.lock().unwrap().clone();
-        let result = self.request.capture_tests(environment);
-        self.notifier
-            .send(Notification::TestRunCompleted(result))
-            .unwrap();
+        let notifier = &self.notifier;
+        let shutdown = &self.shutdown;
+        let result = request.capture_tests_with_progress(environment, &mut |progress| {
+            if !shutdown.load(atomic::Ordering::SeqCst) {
+                // the receiver may be gone when the application shuts down.
+                let _ = notifier.send(Notification::Progress(progress));
+            }
+        });
+        // the watcher that replaced a stopped one runs the tests again.
+        if self.shutdown.load(atomic::Ordering::SeqCst) {
+            return;
+        }
+        // the receiver may be gone when the application shuts down.
+        let _ = self.notifier.send(Notification::TestRunCompleted(result));
+    }
+}
+
+/// The message a thread panicked with.
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown cause"
     }
 }
 
@@ -130,15 +236,43 @@ impl run::Handler for TestWatcherHandler {
     }
 
     fn on_manual(&mut self) -> watchexec::error::Result<bool> {
-        self.capture_tests();
+        self.capture_tests(Vec::new());
         Ok(true)
     }
 
-    fn on_update(&mut self, _ops: &[pathop::PathOp]) -> watchexec::error::Result<bool> {
+    fn on_update(&mut self, ops: &[pathop::PathOp]) -> watchexec::error::Result<bool> {
         if self.shutdown.load(atomic::Ordering::SeqCst) {
             return Ok(false);
         }
-        self.capture_tests();
+        let mut changed_files: Vec<PathBuf> = Vec::new();
+        for op in ops {
+            if !changed_files.contains(&op.path) {
+                changed_files.push(op.path.clone())
+            }
+        }
+        self.capture_tests(changed_files);
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{panic_message, Backoff};
+    use std::panic;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff::default();
+        assert_eq!(backoff.delay(1), Duration::from_millis(500));
+        assert_eq!(backoff.delay(3), Duration::from_secs(2));
+        assert_eq!(backoff.delay(10), Duration::from_secs(30));
+        assert_eq!(backoff.delay(100), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn message_of_panic() {
+        let panic = panic::catch_unwind(|| panic!("handler failed: {}", 1)).unwrap_err();
+        assert_eq!(panic_message(&*panic), "handler failed: 1");
+    }
+}
@@ -0,0 +1,109 @@
+//! The state of the testrunner's user interface that is restored when it starts again.
+
+use crate::test_runner::TestId;
+use emergent_config::Configuration;
+use emergent_presenter::PersistentStates;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The user interface state of a project.
+///
+/// Fields that are missing in a stored state get their default values, so that states stored by
+/// older versions can be loaded.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct ProjectUiState {
+    pub collapsed_tests: HashSet<TestId>,
+    pub collapsed_packages: HashSet<String>,
+    /// The pattern that filters the tests by their names.
+    pub filter: String,
+    /// Hide the warnings that were reported before the session started.
+    pub hide_session_warnings: bool,
+    /// The clippy lints whose messages are hidden.
+    pub hidden_lints: HashSet<String>,
+    /// The focused tabs and the scroll offsets.
+    pub view_states: PersistentStates,
+}
+
+/// The user interface states of all projects, keyed by their project directory.
+///
+/// The states are kept as JSON values and parsed per project, so that a state that can not be
+/// parsed anymore is dropped without dropping the states of the other projects.
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct UiStates {
+    projects: HashMap<PathBuf, Value>,
+}
+
+impl Configuration for UiStates {
+    fn config_path() -> PathBuf {
+        "emergent/ui-states".into()
+    }
+}
+
+impl ProjectUiState {
+    /// Loads the state of the project in `project_directory` or returns the default state if
+    /// none was stored yet.
+    pub fn load(project_directory: &Path) -> ProjectUiState {
+        let state = match UiStates::load()
+            .and_then(|mut states| states.projects.remove(&key(project_directory)))
+        {
+            Some(state) => state,
+            None => return ProjectUiState::default(),
+        };
+        serde_json::from_value(state).unwrap_or_else(|e| {
+            warn!("ignoring the ui state of {:?}: {}", project_directory, e);
+            ProjectUiState::default()
+        })
+    }
+
+    /// Stores the state of the project in `project_directory`.
+    ///
+    /// If the stored states can not be read, they are not overwritten, so that the states of the
+    /// other projects are not lost.
+    pub fn store(&self, project_directory: &Path) {
+        let mut states = match UiStates::load() {
+            Some(states) => states,
+            None if UiStates::is_saved() => {
+                warn!("not saving the ui state, the stored states can not be read");
+                return;
+            }
+            None => UiStates::default(),
+        };
+        let state = match serde_json::to_value(self) {
+            Ok(state) => state,
+            Err(e) => {
+                warn!("failed to serialize the ui state: {}", e);
+                return;
+            }
+        };
+        states.projects.insert(key(project_directory), state);
+        states.save();
+        debug!("saved ui state of {:?}", project_directory);
+    }
+}
+
+/// The canonical project directory, so that the same project is found independently of how its
+/// path was specified.
+fn key(project_directory: &Path) -> PathBuf {
+    fs::canonicalize(project_directory).unwrap_or_else(|_| project_directory.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProjectUiState;
+
+    #[test]
+    fn missing_fields_of_stored_state_get_defaults() {
+        let state: ProjectUiState = serde_json::from_str(r#"{ "filter": "layout" }"#).unwrap();
+        assert_eq!(
+            state,
+            ProjectUiState {
+                filter: "layout".into(),
+                ..ProjectUiState::default()
+            }
+        );
+    }
+}
@@ -26,7 +26,7 @@
 
 use emergent_drawing::Point;
 use emergent_presentation::Presentation;
-use emergent_presenter::{Host, Support, ViewRenderer};
+use emergent_presenter::{Host, PersistentStates, Support, ViewRenderer};
 use emergent_ui::{FrameLayout, ModifiersState, WindowEvent, WindowMessage, WindowState, DPI};
 use std::cell::RefCell;
 use std::time::Instant;
@@ -92,6 +92,20 @@ where
         self.close_requested
     }
 
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    /// The view states that can be persisted.
+    pub fn persistent_states(&self) -> PersistentStates {
+        self.host.borrow().persistent_states()
+    }
+
+    /// Restores persisted view states.
+    pub fn restore_states(&mut self, states: &PersistentStates) {
+        self.host.borrow_mut().restore_states(states)
+    }
+
     fn dispatch_event(&mut self, event: WindowEvent) -> Cmd<WindowApplicationMsg<Msg>> {
         self.window_state.update(event.clone());
 
@@ -107,10 +121,18 @@ where
                 return msgs.into_iter().map(|msg| self.update_model(msg)).collect();
             }
 
+            WindowEvent::ReceivedCharacter(c) => {
+                if let Some(msg) = self.model.received_character(c) {
+                    return self.update_model(msg);
+                }
+            }
+
             WindowEvent::ScaleFactorChanged(frame_layout) => {
                 debug!("DPI change: regenerating host");
                 let dpi = frame_layout.dpi;
+                let states = self.persistent_states();
                 self.host = Host::new((self.support_builder)(dpi)).into();
+                self.restore_states(&states);
 
                 if let Some(dpi_msg) = &self.dpi_msg {
                     let msg = dpi_msg(dpi);
@@ -168,6 +190,11 @@ where
 /// filters.
 pub trait WindowModel<Msg: Send> {
     fn update(&mut self, msg: Msg) -> Cmd<Msg>;
+
+    /// Converts a character typed into the window to a message, `None` ignores it.
+    fn received_character(&self, _c: char) -> Option<Msg> {
+        None
+    }
 }
 
 #[derive(Clone, Default, Debug)]
@@ -1,9 +1,14 @@
 use crate::app::App;
-use clap::Arg;
+use clap::{Arg, ArgMatches, SubCommand};
+use emergent::editor::EditorCommand;
+use emergent::project_config::{ProjectConfig, RunConfiguration};
 use emergent::skia::convert::ToSkia;
 use emergent::skia::path_support::PathSupport;
 use emergent::skia::text::PrimitiveText;
-use emergent::test_runner::{TestEnvironment, TestRunRequest};
+use emergent::test_runner::{
+    PackageSelection, TargetRule, TargetSelection, TestEnvironment, TestRunRequest, Timeouts,
+};
+use emergent::ui_state::ProjectUiState;
 use emergent::{skia, Frame, Msg, WindowApplication, WindowApplicationMsg};
 use emergent_config::WindowPlacement;
 use emergent_drawing::{font, functions, Font, MeasureText};
@@ -11,8 +16,8 @@ use emergent_presenter::Support;
 use emergent_ui as ui;
 use emergent_ui::{measure_fn, Window, DPI};
 use skia_safe::{icu, Typeface};
-use std::time::Instant;
-use std::{env, path, thread};
+use std::time::{Duration, Instant};
+use std::{env, fmt, path, process, thread};
 use tears::{Application, ThreadSpawnExecutor};
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
@@ -26,6 +31,7 @@ use winit::window::WindowBuilder;
 extern crate log;
 
 mod app;
+mod headless;
 mod renderer;
 mod skia_renderer;
 
@@ -41,22 +47,84 @@ fn main() {
                 .help("The directory of a a Cargo project to run tests and watch for changes.")
                 .index(1),
         )
+        .args(&selection_args())
+        .args(&timeout_args())
+        .arg(
+            Arg::with_name("stress-runs")
+                .long("stress-runs")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("20")
+                .validator(validate_runs)
+                .help("How many times a test is run to find out if it is flaky."),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs the tests once without a window and writes the results to disk")
+                .arg(
+                    Arg::with_name("PATH")
+                        .help("The directory of a Cargo project to run tests in.")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .default_value("emergent-results")
+                        .help("The directory the report and the rendered drawings are written to."),
+                )
+                .arg(
+                    Arg::with_name("DPI")
+                        .long("dpi")
+                        .takes_value(true)
+                        .validator(validate_dpi)
+                        .help("The DPI the tests are rendered with."),
+                )
+                .args(&selection_args())
+                .args(&timeout_args()),
+        )
         .get_matches();
 
     // Init text shaping ICU support.
     icu::init();
 
-    let project_path = {
-        let provided = matches.value_of("PATH").map(path::PathBuf::from);
+    if let Some(matches) = matches.subcommand_matches("run") {
+        let project_path = resolve_project_path(matches.value_of("PATH"));
+        let output_path = env::current_dir()
+            .unwrap()
+            .join(matches.value_of("OUTPUT").unwrap());
+        let dpi = matches
+            .value_of("DPI")
+            // validated by `validate_dpi()`.
+            .map(|dpi| DPI(dpi.parse().unwrap()))
+            .unwrap_or(DPI::DEFAULT_SCREEN);
+
+        info!("path: {:?}, output: {:?}", &project_path, &output_path);
+
+        let project_config = project_config(&project_path);
+        let configurations = run_configurations(&project_config, matches);
+        let request = test_run_request(&project_path, matches, &configurations);
+        let environment = TestEnvironment::new(dpi).with_matrix(project_config.matrix);
+        match headless::run(&request, environment, &output_path) {
+            Ok(true) => return,
+            Ok(false) => process::exit(1),
+            Err(e) => {
+                error!("test run failed: {}", e);
+                process::exit(2)
+            }
+        }
+    }
 
-        let current_path = env::current_dir().unwrap();
-        provided
-            .map(|p| current_path.join(p))
-            .unwrap_or(current_path)
-    };
+    let project_path = resolve_project_path(matches.value_of("PATH"));
 
     info!("path: {:?}", &project_path);
 
+    let project_config = project_config(&project_path);
+    let configurations = run_configurations(&project_config, &matches);
+    let editor = EditorCommand::configured(project_config.editor.as_ref().map(String::as_str));
+    let test_run_request = test_run_request(&project_path, &matches, &configurations);
+
     let instance = renderer::new_instance();
 
     let mut event_loop = EventLoop::new();
@@ -76,9 +144,19 @@ fn main() {
     info!("window placement: {:?}", window_placement);
 
     let frame_layout = window_surface.window().frame_layout();
-    let test_environment = TestEnvironment::new(frame_layout.dpi);
-    let test_run_request = TestRunRequest::new_lib(&project_path);
-    let (emergent, initial_cmd) = App::new(test_run_request, test_environment);
+    let test_environment =
+        TestEnvironment::new(frame_layout.dpi).with_matrix(project_config.matrix);
+    // validated by `validate_runs()`.
+    let stress_runs = matches.value_of("stress-runs").unwrap().parse().unwrap();
+    let ui_state = ProjectUiState::load(&project_path);
+    let (emergent, initial_cmd) = App::new(
+        test_run_request,
+        test_environment,
+        configurations,
+        editor,
+        stress_runs,
+        &ui_state,
+    );
 
     info!("spawning application & renderer loop");
 
@@ -90,16 +168,14 @@ fn main() {
         let executor = ThreadSpawnExecutor::default();
         let support_builder =
             |dpi: DPI| Support::new(dpi, PrimitiveText::new(dpi), PathSupport::default());
-        let mut application = Application::new(
-            app_mailbox,
-            WindowApplication::new(
-                emergent,
-                frame_layout.dpi,
-                support_builder,
-                Some(|dpi| Msg::RerunTestcases(TestEnvironment { dpi })),
-            ),
-            executor,
+        let mut window_application = WindowApplication::new(
+            emergent,
+            frame_layout.dpi,
+            support_builder,
+            Some(Msg::DpiChanged),
         );
+        window_application.restore_states(&ui_state.view_states);
+        let mut application = Application::new(app_mailbox, window_application, executor);
         application.schedule(initial_cmd.map(WindowApplicationMsg::Application));
         application.update();
 
@@ -146,6 +222,13 @@ fn main() {
         }
 
         debug!("shutting down renderer loop");
+
+        let window_application = application.model();
+        let ui_state = ProjectUiState {
+            view_states: window_application.persistent_states(),
+            ..window_application.model().ui_state()
+        };
+        ui_state.store(&project_path);
     });
 
     event_loop.run_return(move |event, _, control_flow| {
@@ -202,6 +285,231 @@ fn main() {
     application_thread.join().unwrap();
 }
 
+/// Validates that the DPI argument is a number greater than zero.
+fn validate_dpi(dpi: String) -> Result<(), String> {
+    match dpi.parse::<f64>() {
+        Ok(dpi) if dpi > 0.0 => Ok(()),
+        _ => Err(format!(
+            "expected a number greater than zero, found {}",
+            dpi
+        )),
+    }
+}
+
+/// Arguments that select the packages and targets to run tests for.
+fn selection_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("workspace")
+            .long("workspace")
+            .help("Test all members of the workspace."),
+        Arg::with_name("package")
+            .long("package")
+            .short("p")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Test the named workspace member."),
+        Arg::with_name("lib")
+            .long("lib")
+            .help("Test the library (the default if no other target is selected)."),
+        Arg::with_name("test")
+            .long("test")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Test the named integration test."),
+        Arg::with_name("tests")
+            .long("tests")
+            .help("Test all integration tests."),
+        Arg::with_name("bins")
+            .long("bins")
+            .help("Test all binaries."),
+        Arg::with_name("examples")
+            .long("examples")
+            .help("Test all examples."),
+        Arg::with_name("doc").long("doc").help("Run the doc-tests."),
+        Arg::with_name("all-targets")
+            .long("all-targets")
+            .help("Test all targets, including doc-tests."),
+        Arg::with_name("configuration")
+            .long("configuration")
+            .short("c")
+            .takes_value(true)
+            .value_name("NAME")
+            .help("Run the tests with the named configuration of the project's emergent.json."),
+        Arg::with_name("clippy")
+            .long("clippy")
+            .help("Run clippy after the tests compiled and show its lints with the warnings."),
+    ]
+}
+
+/// Arguments that limit the time tests may take.
+fn timeout_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("timeout")
+            .long("timeout")
+            .takes_value(true)
+            .value_name("SECONDS")
+            .validator(validate_seconds)
+            .help("The time limit for running all tests (no limit by default)."),
+        Arg::with_name("test-timeout")
+            .long("test-timeout")
+            .takes_value(true)
+            .value_name("SECONDS")
+            .validator(validate_seconds)
+            .help("The time limit of each test, 0 disables the limit (60 by default)."),
+    ]
+}
+
+/// Validates that the number of stress runs is a whole number greater than zero.
+fn validate_runs(runs: String) -> Result<(), String> {
+    match runs.parse::<usize>() {
+        Ok(runs) if runs > 0 => Ok(()),
+        _ => Err(format!(
+            "expected a number greater than zero, found {}",
+            runs
+        )),
+    }
+}
+
+/// Validates that a timeout argument is a whole number of seconds.
+fn validate_seconds(seconds: String) -> Result<(), String> {
+    match seconds.parse::<u64>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("expected a number of seconds, found {}", seconds)),
+    }
+}
+
+/// Loads the configuration of the project, exits if it is invalid.
+fn project_config(project_path: &path::Path) -> ProjectConfig {
+    ProjectConfig::load(project_path).unwrap_or_else(|e| {
+        exit_with_error(format!("failed to load the project configuration: {}", e))
+    })
+}
+
+/// Prints an error that prevents the testrunner from starting and exits.
+fn exit_with_error(error: impl fmt::Display) -> ! {
+    eprintln!("error: {}", error);
+    process::exit(2)
+}
+
+/// The run configurations of the project, all of them run clippy if the arguments defined in
+/// `selection_args()` request it.
+fn run_configurations(
+    project_config: &ProjectConfig,
+    matches: &ArgMatches,
+) -> Vec<RunConfiguration> {
+    let mut configurations = project_config.run_configurations();
+    if matches.is_present("clippy") {
+        for configuration in &mut configurations {
+            configuration.clippy = true;
+        }
+    }
+    configurations
+}
+
+/// Creates the test run request from the arguments defined in `selection_args()` and
+/// `timeout_args()`.
+///
+/// The tests are run with the configuration named by the arguments, or with the first.
+fn test_run_request(
+    project_path: &path::Path,
+    matches: &ArgMatches,
+    configurations: &[RunConfiguration],
+) -> TestRunRequest {
+    let packages = match matches.values_of("package") {
+        Some(names) if !matches.is_present("workspace") => {
+            PackageSelection::Just(names.map(String::from).collect())
+        }
+        _ if matches.is_present("workspace") => PackageSelection::Workspace,
+        _ => PackageSelection::Current,
+    };
+
+    let configuration = match matches.value_of("configuration") {
+        Some(name) => configurations
+            .iter()
+            .find(|c| c.name == name)
+            .unwrap_or_else(|| {
+                let names: Vec<&str> = configurations.iter().map(|c| c.name.as_str()).collect();
+                exit_with_error(format!(
+                    "no run configuration named `{}`, the configurations are: {}",
+                    name,
+                    names.join(", ")
+                ))
+            }),
+        None => &configurations[0],
+    };
+
+    TestRunRequest::new(project_path, target_selection(matches))
+        .with_packages(packages)
+        .with_timeouts(timeouts(matches))
+        .with_configuration(configuration.clone())
+}
+
+fn timeouts(matches: &ArgMatches) -> Timeouts {
+    let seconds = |name: &str| {
+        matches.value_of(name).map(|seconds| {
+            // validated by `validate_seconds()`.
+            let seconds: u64 = seconds.parse().unwrap();
+            Duration::from_secs(seconds)
+        })
+    };
+
+    let default = Timeouts::default();
+    Timeouts {
+        run: seconds("timeout").or(default.run),
+        test: match seconds("test-timeout") {
+            Some(limit) if limit == Duration::from_secs(0) => None,
+            Some(limit) => Some(limit),
+            None => default.test,
+        },
+    }
+}
+
+/// Resolves the target selection from the arguments defined in `selection_args()`.
+fn target_selection(matches: &ArgMatches) -> TargetSelection {
+    if matches.is_present("all-targets") {
+        return TargetSelection::all();
+    }
+
+    let rule = |all: &str| {
+        if matches.is_present(all) {
+            TargetRule::All
+        } else {
+            TargetRule::None
+        }
+    };
+
+    let tests = match matches.values_of("test") {
+        Some(names) if !matches.is_present("tests") => {
+            TargetRule::Just(names.map(String::from).collect())
+        }
+        _ => rule("tests"),
+    };
+
+    let mut selection = TargetSelection {
+        lib: matches.is_present("lib"),
+        tests,
+        bins: rule("bins"),
+        examples: rule("examples"),
+        doc: matches.is_present("doc"),
+    };
+
+    if selection == TargetSelection::none() {
+        selection.lib = true;
+    }
+
+    selection
+}
+
+/// Resolves the project path relative to the current directory.
+fn resolve_project_path(provided: Option<&str>) -> path::PathBuf {
+    let current_path = env::current_dir().unwrap();
+    provided
+        .map(|p| current_path.join(p))
+        .unwrap_or(current_path)
+}
+
 // TODO: add a bench for this!
 fn _shaper_perf() {
     icu::init();
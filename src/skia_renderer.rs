@@ -4,11 +4,14 @@ use emergent::skia::convert::ToSkia;
 use emergent::{text_as_lines, Frame, TextOrigin};
 use emergent_drawing as drawing;
 use emergent_drawing::text::With;
-use emergent_drawing::{font, DrawTo, Shape, Transform};
+use emergent_drawing::{
+    font, DrawTo, DrawingFastBounds, MeasureText, Shape, Transform, Transformed,
+};
 use emergent_ui::{Window, DPI};
 use skia_safe::gpu::vk;
 use skia_safe::{
-    gpu, Canvas, CanvasPointMode, Color, ColorType, Font, Paint, Shaper, Surface, Typeface, Vector,
+    gpu, Canvas, CanvasPointMode, Color, ColorType, EncodedImageFormat, Font, Paint, Shaper,
+    Surface, Typeface, Vector,
 };
 use std::convert::TryInto;
 use std::ffi::{c_void, CString};
@@ -187,6 +190,37 @@ impl DrawingBackend for Backend {
 
 impl DrawingSurface for skia_safe::Surface {}
 
+/// Rasterizes a drawing on a CPU surface and encodes it as PNG.
+///
+/// The drawing is moved so that its bounds start at the origin. Returns `None` if the
+/// drawing is empty or unbounded.
+pub fn rasterize_to_png(
+    drawing: &drawing::Drawing,
+    dpi: DPI,
+    measure: &dyn MeasureText,
+) -> Option<Vec<u8>> {
+    let bounds = *drawing.fast_bounds(measure).as_bounds()?;
+    let width = bounds.width().ceil().max(1.0) as i32;
+    let height = bounds.height().ceil().max(1.0) as i32;
+
+    let mut surface = Surface::new_raster_n32_premul((width, height))?;
+    let shaper = Shaper::new(None);
+    {
+        let canvas = surface.canvas();
+        canvas.clear(Color::WHITE);
+        let drawing_target = &mut CanvasDrawingTarget::from_canvas(canvas, dpi, &shaper);
+        drawing
+            .clone()
+            .transformed(-bounds.point.to_vector())
+            .draw_to(drawing::Paint::default(), drawing_target);
+    }
+
+    let data = surface
+        .image_snapshot()
+        .encode_to_data(EncodedImageFormat::PNG)?;
+    Some(data.as_bytes().to_vec())
+}
+
 struct CanvasDrawingTarget<'a> {
     canvas: &'a mut Canvas,
     // shaper: &'a shaper::Shaper,
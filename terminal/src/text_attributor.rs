@@ -25,9 +25,6 @@ pub struct AttributedText {
 
 /// Processes the input string with an ANSI terminal parser and returns spans of attributed text.
 pub fn attribute_str(input: &str) -> Vec<AttributedText> {
-    if !input.is_ascii() {
-        warn!("input is not ascii: {}", &input);
-    }
     attribute_bytes(input.as_bytes())
 }
 
@@ -0,0 +1,51 @@
+use emergent_drawing::{Color, FromTestEnvironment, RGB};
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// The color scheme content is presented in.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        ColorScheme::Light
+    }
+}
+
+impl ColorScheme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorScheme::Light => "light",
+            ColorScheme::Dark => "dark",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ColorScheme> {
+        match name {
+            "light" => Some(ColorScheme::Light),
+            "dark" => Some(ColorScheme::Dark),
+            _ => None,
+        }
+    }
+
+    /// The color of the background content is drawn on.
+    pub fn background(&self) -> Color {
+        match self {
+            ColorScheme::Light => 0xffffff.rgb(),
+            ColorScheme::Dark => 0x202020.rgb(),
+        }
+    }
+}
+
+impl FromTestEnvironment for ColorScheme {
+    fn from_test_environment() -> Self {
+        env::var("EMERGENT_TEST_COLOR_SCHEME")
+            .ok()
+            .and_then(|v| ColorScheme::from_name(&v))
+            .unwrap_or_default()
+    }
+}
@@ -1,6 +1,9 @@
 #[macro_use]
 extern crate log;
 
+mod color_scheme;
+pub use color_scheme::*;
+
 mod frame_layout;
 pub use frame_layout::*;
 